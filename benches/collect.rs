@@ -0,0 +1,105 @@
+//! Throughput of `collect_order_events_from` against an in-memory chain, in events
+//! per second, to catch regressions of the processing pipeline.
+//!
+//! Run with `cargo bench --features mock --bench collect`.
+
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use ethers::types::{Log, H160, H256};
+use tempfile::NamedTempFile;
+use tokio::runtime::Runtime;
+use trade_data_collector::constants;
+use trade_data_collector::event_collector::{
+    collect_order_events_from, load_abi, AbiSource, CollectorConfig,
+};
+use trade_data_collector::mock::MockProvider;
+use trade_data_collector::provider_pool::PooledProviders;
+use trade_data_collector::source::ChainSource;
+
+/// Builds `count` logs alternating between TakeOrder and Clear, several per block,
+/// served by a chain whose calls take `latency_ms`.
+fn synthetic_chain(count: u64, latency_ms: u64) -> Arc<MockProvider> {
+    let abi = load_abi(&AbiSource::default()).expect("ABI file");
+    let signatures = [
+        abi.event(constants::TAKEORDER_EVENT_NAME)
+            .unwrap()
+            .signature(),
+        abi.event(constants::CLEAR_EVENT_NAME).unwrap().signature(),
+    ];
+    let logs = (0..count)
+        .map(|n| Log {
+            topics: vec![signatures[(n % 2) as usize]],
+            block_number: Some((n / 4).into()),
+            log_index: Some((n % 4).into()),
+            transaction_hash: Some(H256::from_low_u64_be(n + 1)),
+            ..Default::default()
+        })
+        .collect();
+    Arc::new(
+        MockProvider::builder()
+            .latest_block(count / 4)
+            .logs(logs)
+            .origin(H160::from_low_u64_be(1))
+            .latency(latency_ms)
+            .build(),
+    )
+}
+
+/// Collects every log of `chain` into a temporary CSV file.
+async fn collect(chain: Arc<MockProvider>, enrich_concurrency: usize) {
+    let output = NamedTempFile::new().unwrap();
+    let config = CollectorConfig {
+        from_block: 0,
+        to_block: chain.latest_block_number().await.unwrap(),
+        chunk_size: 1_000,
+        request_delay_ms: 0,
+        enrich_concurrency,
+        filename: output.path().to_str().unwrap().into(),
+        ..Default::default()
+    };
+    let source: Arc<dyn ChainSource> = chain;
+    let providers = PooledProviders::from_sources(vec![source]).unwrap();
+    let stats = collect_order_events_from(&config, providers).await.unwrap();
+    assert!(stats.total_events > 0);
+}
+
+/// Processing cost alone, with requests answered instantly.
+fn bench_pipeline(c: &mut Criterion) {
+    const EVENTS: u64 = 20_000;
+    let runtime = Runtime::new().unwrap();
+    let chain = synthetic_chain(EVENTS, 0);
+
+    let mut group = c.benchmark_group("pipeline");
+    group.throughput(Throughput::Elements(EVENTS));
+    group.sample_size(10);
+    group.bench_function("instant_provider", |b| {
+        b.to_async(&runtime).iter(|| collect(chain.clone(), 8))
+    });
+    group.finish();
+}
+
+/// Enrichment under provider latency, by enrichment concurrency.
+fn bench_enrich_concurrency(c: &mut Criterion) {
+    const EVENTS: u64 = 400;
+    let runtime = Runtime::new().unwrap();
+    let chain = synthetic_chain(EVENTS, 1);
+
+    let mut group = c.benchmark_group("enrich_concurrency");
+    group.throughput(Throughput::Elements(EVENTS));
+    group.sample_size(10);
+    for concurrency in [1, 8, 32] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(concurrency),
+            &concurrency,
+            |b, &concurrency| {
+                b.to_async(&runtime)
+                    .iter(|| collect(chain.clone(), concurrency))
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_pipeline, bench_enrich_concurrency);
+criterion_main!(benches);
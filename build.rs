@@ -0,0 +1,23 @@
+use std::process::Command;
+
+/// Embeds the crate version, followed by the git commit when built from a
+/// checkout, as `BUILD_VERSION` for `--version`.
+fn main() {
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+
+    let version = env!("CARGO_PKG_VERSION");
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|hash| hash.trim().to_string())
+        .filter(|hash| !hash.is_empty());
+
+    match commit {
+        Some(commit) => println!("cargo:rustc-env=BUILD_VERSION={} ({})", version, commit),
+        None => println!("cargo:rustc-env=BUILD_VERSION={}", version),
+    }
+}
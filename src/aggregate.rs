@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::File;
+
+use csv::{Writer, WriterBuilder};
+use ethers::types::{Log, H256};
+use futures::future::join_all;
+use log::warn;
+use serde::Serialize;
+use tokio::sync::Semaphore;
+
+use crate::constants;
+use crate::rate_limit::RateLimiter;
+use crate::sink::EventSink;
+use crate::source::ChainSource;
+use crate::utils::OrderEvent;
+
+/// Event counts of a single block, written instead of events by `--aggregate-by-block`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct BlockAggregate {
+    pub block_number: u64,
+    pub timestamp: u64,
+    pub take_order_count: u64,
+    pub clear_count: u64,
+}
+
+/// Counts the TakeOrder and Clear logs of each block, in ascending block order.
+///
+/// Timestamps are left at zero; see [`with_timestamps`].
+pub fn aggregate_logs(logs: &[Log], take_order: H256, clear: H256) -> Vec<BlockAggregate> {
+    let mut blocks: BTreeMap<u64, BlockAggregate> = BTreeMap::new();
+    for log in logs {
+        let Some(block_number) = log.block_number.map(|number| number.as_u64()) else {
+            continue;
+        };
+        let aggregate = blocks
+            .entry(block_number)
+            .or_insert_with(|| BlockAggregate {
+                block_number,
+                ..Default::default()
+            });
+        match log.topics.first() {
+            Some(topic) if *topic == take_order => aggregate.take_order_count += 1,
+            Some(topic) if *topic == clear => aggregate.clear_count += 1,
+            _ => {}
+        }
+    }
+    blocks.into_values().collect()
+}
+
+/// Fills in the block timestamps of `aggregates`, fetching up to `concurrency`
+/// blocks at a time, each after waiting on `rate_limiter` when set.
+///
+/// Returns the aggregates whose block could be fetched, in their original order,
+/// and the number of blocks whose fetch failed.
+pub async fn with_timestamps(
+    provider: &dyn ChainSource,
+    aggregates: Vec<BlockAggregate>,
+    concurrency: usize,
+    rate_limiter: Option<&RateLimiter>,
+) -> (Vec<BlockAggregate>, u64) {
+    let semaphore = Semaphore::new(concurrency.max(1));
+    let timestamps = join_all(aggregates.iter().map(|aggregate| async {
+        let _permit = semaphore.acquire().await;
+        if let Some(rate_limiter) = rate_limiter {
+            rate_limiter.acquire().await;
+        }
+        provider.block_timestamp(aggregate.block_number).await
+    }))
+    .await;
+
+    let mut failed = 0;
+    let mut complete = Vec::with_capacity(aggregates.len());
+    for (mut aggregate, timestamp) in aggregates.into_iter().zip(timestamps) {
+        match timestamp {
+            Ok(Some(timestamp)) => {
+                aggregate.timestamp = timestamp;
+                complete.push(aggregate);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                warn!("Failed to fetch block {}: {}", aggregate.block_number, e);
+                failed += 1;
+            }
+        }
+    }
+    (complete, failed)
+}
+
+/// CSV output of per-block aggregates.
+pub struct BlockAggregateSink {
+    writer: Writer<File>,
+    fsync: bool,
+}
+
+impl BlockAggregateSink {
+    /// Creates (or truncates) `filename` and writes the header.
+    pub fn create(filename: &str, fsync: bool) -> Result<Self, Box<dyn Error>> {
+        let file = File::options()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(filename)?;
+        let mut sink = BlockAggregateSink {
+            writer: WriterBuilder::new().has_headers(false).from_writer(file),
+            fsync,
+        };
+        sink.writer.write_record(constants::AGGREGATE_CSV_HEADER)?;
+        sink.sync()?;
+        Ok(sink)
+    }
+
+    /// Opens an existing `filename` for appending rows after its current content.
+    pub fn append(filename: &str, fsync: bool) -> Result<Self, Box<dyn Error>> {
+        let file = File::options().append(true).open(filename)?;
+        Ok(BlockAggregateSink {
+            writer: WriterBuilder::new().has_headers(false).from_writer(file),
+            fsync,
+        })
+    }
+
+    fn sync(&mut self) -> Result<(), Box<dyn Error>> {
+        self.writer.flush()?;
+        if self.fsync {
+            self.writer.get_ref().sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+impl EventSink for BlockAggregateSink {
+    fn write_events(&mut self, _events: &[OrderEvent]) -> Result<(), Box<dyn Error>> {
+        Err("Events cannot be written to a block aggregate output".into())
+    }
+
+    fn write_block_aggregates(
+        &mut self,
+        aggregates: &[BlockAggregate],
+    ) -> Result<(), Box<dyn Error>> {
+        for aggregate in aggregates {
+            self.writer.serialize(aggregate)?;
+        }
+        self.sync()
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), Box<dyn Error>> {
+        self.sync()
+    }
+}
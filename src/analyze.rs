@@ -0,0 +1,104 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, Read};
+
+use csv::{ReaderBuilder, StringRecord};
+
+/// Number of seconds in one histogram bucket (one UTC day).
+pub const HISTOGRAM_BUCKET_SECS: u64 = 86_400;
+
+/// Aggregates computed over an exported events CSV.
+///
+/// Memory use is bounded by the number of unique origins, event types and
+/// histogram buckets; individual rows are never retained.
+#[derive(Debug, Default)]
+pub struct Analysis {
+    /// Number of data rows read.
+    pub total_rows: u64,
+    /// Row count per event type.
+    pub events_by_type: BTreeMap<String, u64>,
+    /// Row count per `tx.origin`.
+    pub events_by_origin: HashMap<String, u64>,
+    /// Row count per day, keyed by the bucket start timestamp.
+    pub daily_histogram: BTreeMap<u64, u64>,
+}
+
+/// Streams CSV rows from `reader` and accumulates an [`Analysis`] in a single pass.
+///
+/// Columns are located by header name, so files with extra columns are supported.
+pub fn analyze_reader<R: Read>(reader: R) -> Result<Analysis, Box<dyn Error>> {
+    let mut rdr = ReaderBuilder::new().has_headers(true).from_reader(reader);
+
+    let headers = rdr.headers()?.clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|header| header == name)
+            .ok_or_else(|| format!("Missing column '{}' in CSV header", name))
+    };
+    let origin_idx = column("tx.origin")?;
+    let event_idx = column("event type")?;
+    let timestamp_idx = column("timestamp")?;
+
+    let mut analysis = Analysis::default();
+
+    // Reuse a single record buffer so rows are never collected
+    let mut record = StringRecord::new();
+    while rdr.read_record(&mut record)? {
+        analysis.total_rows += 1;
+
+        *analysis
+            .events_by_type
+            .entry(record[event_idx].to_string())
+            .or_insert(0) += 1;
+
+        // Avoid allocating a key for origins that were already seen
+        match analysis.events_by_origin.get_mut(&record[origin_idx]) {
+            Some(count) => *count += 1,
+            None => {
+                analysis
+                    .events_by_origin
+                    .insert(record[origin_idx].to_string(), 1);
+            }
+        }
+
+        if let Ok(timestamp) = record[timestamp_idx].parse::<u64>() {
+            let bucket = timestamp - timestamp % HISTOGRAM_BUCKET_SECS;
+            *analysis.daily_histogram.entry(bucket).or_insert(0) += 1;
+        }
+    }
+
+    Ok(analysis)
+}
+
+/// Analyzes the CSV file at `filename`, streaming it from disk.
+pub fn analyze_csv(filename: &str) -> Result<Analysis, Box<dyn Error>> {
+    analyze_reader(BufReader::new(File::open(filename)?))
+}
+
+/// Prints an analysis summary with the `top` most active origins.
+pub fn print_analysis(analysis: &Analysis, top: usize) {
+    println!("Total events: {}", analysis.total_rows);
+
+    println!("Events by type:");
+    for (event_type, count) in &analysis.events_by_type {
+        println!("  {:<16} {}", event_type, count);
+    }
+
+    let mut origins: Vec<(&String, &u64)> = analysis.events_by_origin.iter().collect();
+    origins.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    println!(
+        "Top {} of {} origins:",
+        top.min(origins.len()),
+        origins.len()
+    );
+    for (origin, count) in origins.into_iter().take(top) {
+        println!("  {} {}", origin, count);
+    }
+
+    println!("Events per day (bucket start timestamp):");
+    for (bucket, count) in &analysis.daily_histogram {
+        println!("  {} {}", bucket, count);
+    }
+}
@@ -0,0 +1,307 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::OnceLock;
+
+use apache_avro::types::Value;
+use apache_avro::{from_value, to_value, Reader, Schema, Writer};
+use ethers::types::{H160, H256, U256};
+use serde::{Deserialize, Serialize};
+
+use crate::sink::EventSink;
+use crate::utils::OrderEvent;
+
+/// Version of [`ORDER_EVENT_SCHEMA`], bumped whenever the schema changes. It is
+/// part of the record namespace and stored in the file metadata, so consumers
+/// can tell which revision a file was written with.
+pub const ORDER_EVENT_SCHEMA_VERSION: u32 = 4;
+
+/// File metadata key holding [`ORDER_EVENT_SCHEMA_VERSION`].
+pub const SCHEMA_VERSION_METADATA_KEY: &str = "trade_data_collector.schema_version";
+
+/// Avro schema of the records written by `--format avro`, one per event.
+///
+/// Addresses and hashes are `0x`-prefixed lowercase hex strings, gas prices are
+/// decimal strings of wei, and the decoded event arguments are kept as a JSON
+/// document, as in the JSON formats. Optional columns are nullable unions
+/// defaulting to `null`. Version 2 added `chain_id`, version 3 `gas_price` and
+/// `effective_gas_price`, and version 4 `input`.
+pub const ORDER_EVENT_SCHEMA: &str = r#"{
+  "type": "record",
+  "name": "OrderEvent",
+  "namespace": "trade_data_collector.v4",
+  "fields": [
+    {"name": "tx_origin", "type": "string"},
+    {"name": "event_type", "type": "string"},
+    {"name": "txn_hash", "type": "string"},
+    {"name": "timestamp", "type": "long"},
+    {"name": "block_number", "type": "long"},
+    {"name": "log_index", "type": "long"},
+    {"name": "raw_data", "type": ["null", "string"], "default": null},
+    {"name": "raw_topics", "type": ["null", "string"], "default": null},
+    {"name": "direct_caller", "type": ["null", "string"], "default": null},
+    {"name": "label", "type": ["null", "string"], "default": null},
+    {"name": "chain_id", "type": ["null", "long"], "default": null},
+    {"name": "gas_price", "type": ["null", "string"], "default": null},
+    {"name": "effective_gas_price", "type": ["null", "string"], "default": null},
+    {"name": "input", "type": ["null", "string"], "default": null},
+    {"name": "args", "type": ["null", "string"], "default": null}
+  ]
+}"#;
+
+/// Avro schema of the records written by `--format avro --compact-addresses`.
+///
+/// The same as [`ORDER_EVENT_SCHEMA`], except that `tx_origin` and `txn_hash`
+/// are stored as 20 and 32-byte `fixed` values instead of hex strings.
+pub const COMPACT_ORDER_EVENT_SCHEMA: &str = r#"{
+  "type": "record",
+  "name": "OrderEvent",
+  "namespace": "trade_data_collector.v4",
+  "fields": [
+    {"name": "tx_origin", "type": {"type": "fixed", "name": "Address", "size": 20}},
+    {"name": "event_type", "type": "string"},
+    {"name": "txn_hash", "type": {"type": "fixed", "name": "Hash", "size": 32}},
+    {"name": "timestamp", "type": "long"},
+    {"name": "block_number", "type": "long"},
+    {"name": "log_index", "type": "long"},
+    {"name": "raw_data", "type": ["null", "string"], "default": null},
+    {"name": "raw_topics", "type": ["null", "string"], "default": null},
+    {"name": "direct_caller", "type": ["null", "string"], "default": null},
+    {"name": "label", "type": ["null", "string"], "default": null},
+    {"name": "chain_id", "type": ["null", "long"], "default": null},
+    {"name": "gas_price", "type": ["null", "string"], "default": null},
+    {"name": "effective_gas_price", "type": ["null", "string"], "default": null},
+    {"name": "input", "type": ["null", "string"], "default": null},
+    {"name": "args", "type": ["null", "string"], "default": null}
+  ]
+}"#;
+
+/// Fields of [`COMPACT_ORDER_EVENT_SCHEMA`] stored as `fixed` values, with their size.
+const COMPACT_FIELDS: [(&str, usize); 2] = [("tx_origin", 20), ("txn_hash", 32)];
+
+/// Returns the parsed [`ORDER_EVENT_SCHEMA`].
+pub fn order_event_schema() -> &'static Schema {
+    static SCHEMA: OnceLock<Schema> = OnceLock::new();
+    SCHEMA.get_or_init(|| {
+        Schema::parse_str(ORDER_EVENT_SCHEMA).expect("the OrderEvent Avro schema is valid")
+    })
+}
+
+/// Returns the parsed [`COMPACT_ORDER_EVENT_SCHEMA`].
+pub fn compact_order_event_schema() -> &'static Schema {
+    static SCHEMA: OnceLock<Schema> = OnceLock::new();
+    SCHEMA.get_or_init(|| {
+        Schema::parse_str(COMPACT_ORDER_EVENT_SCHEMA)
+            .expect("the compact OrderEvent Avro schema is valid")
+    })
+}
+
+/// Replaces the hex strings of the [`COMPACT_FIELDS`] of `record` by their bytes.
+fn compact_record(record: Value) -> Result<Value, Box<dyn Error>> {
+    let Value::Record(fields) = record else {
+        return Ok(record);
+    };
+    let fields = fields
+        .into_iter()
+        .map(|(name, value)| -> Result<(String, Value), Box<dyn Error>> {
+            match (
+                COMPACT_FIELDS.iter().find(|(field, _)| *field == name),
+                value,
+            ) {
+                (Some(&(_, size)), Value::String(hex)) => {
+                    let bytes = hex::decode(hex.trim_start_matches("0x"))?;
+                    Ok((name, Value::Fixed(size, bytes)))
+                }
+                (_, value) => Ok((name, value)),
+            }
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(Value::Record(fields))
+}
+
+/// Replaces the `fixed` values of a compact `record` by `0x`-prefixed hex strings,
+/// leaving records written with [`ORDER_EVENT_SCHEMA`] unchanged.
+fn expand_record(record: Value) -> Value {
+    let Value::Record(fields) = record else {
+        return record;
+    };
+    let fields = fields
+        .into_iter()
+        .map(|(name, value)| match value {
+            Value::Fixed(_, bytes) => (name, Value::String(format!("0x{}", hex::encode(bytes)))),
+            value => (name, value),
+        })
+        .collect();
+    Value::Record(fields)
+}
+
+/// An [`OrderEvent`] in the shape of [`ORDER_EVENT_SCHEMA`].
+#[derive(Debug, Serialize, Deserialize)]
+struct AvroOrderEvent {
+    tx_origin: String,
+    event_type: String,
+    txn_hash: String,
+    timestamp: i64,
+    block_number: i64,
+    log_index: i64,
+    raw_data: Option<String>,
+    raw_topics: Option<String>,
+    direct_caller: Option<String>,
+    label: Option<String>,
+    // Absent from version 1 records
+    #[serde(default)]
+    chain_id: Option<i64>,
+    // Absent from version 1 and 2 records
+    #[serde(default)]
+    gas_price: Option<String>,
+    #[serde(default)]
+    effective_gas_price: Option<String>,
+    // Absent from version 1 to 3 records
+    #[serde(default)]
+    input: Option<String>,
+    args: Option<String>,
+}
+
+impl AvroOrderEvent {
+    fn from_event(event: &OrderEvent) -> Result<Self, Box<dyn Error>> {
+        Ok(AvroOrderEvent {
+            tx_origin: format!("{:?}", event.tx_origin),
+            event_type: event.event_type.clone(),
+            txn_hash: format!("{:?}", event.txn_hash),
+            timestamp: i64::try_from(event.timestamp)?,
+            block_number: i64::try_from(event.block_number)?,
+            log_index: i64::try_from(event.log_index)?,
+            raw_data: event.raw_data.clone(),
+            raw_topics: event.raw_topics.clone(),
+            direct_caller: event.direct_caller.clone(),
+            label: event.label.clone(),
+            chain_id: event.chain_id.map(i64::try_from).transpose()?,
+            gas_price: event.gas_price.map(|price| price.to_string()),
+            effective_gas_price: event.effective_gas_price.map(|price| price.to_string()),
+            input: event.input.clone(),
+            args: event.args.as_ref().map(|args| args.to_string()),
+        })
+    }
+
+    fn into_event(self) -> Result<OrderEvent, Box<dyn Error>> {
+        Ok(OrderEvent {
+            tx_origin: self.tx_origin.parse::<H160>()?,
+            event_type: self.event_type,
+            txn_hash: self.txn_hash.parse::<H256>()?,
+            timestamp: u64::try_from(self.timestamp)?,
+            block_number: u64::try_from(self.block_number)?,
+            log_index: u64::try_from(self.log_index)?,
+            raw_data: self.raw_data,
+            raw_topics: self.raw_topics,
+            direct_caller: self.direct_caller,
+            label: self.label,
+            chain_id: self.chain_id.map(u64::try_from).transpose()?,
+            gas_price: self
+                .gas_price
+                .map(|price| U256::from_dec_str(&price))
+                .transpose()?,
+            effective_gas_price: self
+                .effective_gas_price
+                .map(|price| U256::from_dec_str(&price))
+                .transpose()?,
+            input: self.input,
+            args: self
+                .args
+                .map(|args| serde_json::from_str(&args))
+                .transpose()?,
+            indexed_addresses: Vec::new(),
+            value: None,
+        })
+    }
+}
+
+/// Avro object container output, with one [`ORDER_EVENT_SCHEMA`] record per event,
+/// or one [`COMPACT_ORDER_EVENT_SCHEMA`] record when addresses are compact.
+pub struct AvroSink {
+    writer: Writer<'static, File>,
+    file: File,
+    fsync: bool,
+    compact: bool,
+}
+
+impl AvroSink {
+    /// Creates (or truncates) `filename`, storing addresses and hashes as bytes
+    /// when `compact` is set.
+    pub fn create(filename: &str, fsync: bool, compact: bool) -> Result<Self, Box<dyn Error>> {
+        let file = File::options()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(filename)?;
+        let handle = file.try_clone()?;
+        let schema = if compact {
+            compact_order_event_schema()
+        } else {
+            order_event_schema()
+        };
+        let mut writer = Writer::new(schema, file);
+        writer.add_user_metadata(
+            SCHEMA_VERSION_METADATA_KEY.to_string(),
+            ORDER_EVENT_SCHEMA_VERSION.to_string(),
+        )?;
+        Ok(AvroSink {
+            writer,
+            file: handle,
+            fsync,
+            compact,
+        })
+    }
+
+    /// Opens an existing `filename` to add events after its current records.
+    pub fn append(filename: &str, fsync: bool, compact: bool) -> Result<Self, Box<dyn Error>> {
+        // The container is rewritten with a new header, so its records are read first
+        // and copied to a temporary file, which only replaces it once complete. The
+        // sink keeps writing to the renamed file.
+        let existing = read_avro_events(filename)?;
+        let tmp = format!("{}.tmp", filename);
+        let mut sink = AvroSink::create(&tmp, fsync, compact)?;
+        sink.write_events(&existing)?;
+        std::fs::rename(&tmp, filename)?;
+        Ok(sink)
+    }
+
+    fn sync(&mut self) -> Result<(), Box<dyn Error>> {
+        self.writer.flush()?;
+        if self.fsync {
+            self.file.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+impl EventSink for AvroSink {
+    fn write_events(&mut self, events: &[OrderEvent]) -> Result<(), Box<dyn Error>> {
+        for event in events {
+            let record = AvroOrderEvent::from_event(event)?;
+            if self.compact {
+                self.writer.append(compact_record(to_value(record)?)?)?;
+            } else {
+                self.writer.append_ser(record)?;
+            }
+        }
+        self.sync()
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), Box<dyn Error>> {
+        self.sync()
+    }
+}
+
+/// Reads the events of an Avro output written by [`AvroSink`], with any version
+/// of [`ORDER_EVENT_SCHEMA`], compact or not.
+pub fn read_avro_events(filename: &str) -> Result<Vec<OrderEvent>, Box<dyn Error>> {
+    // Records are decoded with the schema they were written with
+    let file = BufReader::new(File::open(filename)?);
+    let reader = Reader::new(file)?;
+    let mut events = Vec::new();
+    for value in reader {
+        let record: AvroOrderEvent = from_value(&expand_record(value?))?;
+        events.push(record.into_event()?);
+    }
+    Ok(events)
+}
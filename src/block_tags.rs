@@ -0,0 +1,52 @@
+use ethers::types::BlockNumber;
+
+use crate::error::CollectorError;
+use crate::source::{ChainSource, SourceError};
+
+/// Parses a block bound given as a number or as one of the `latest`, `safe`,
+/// `finalized` and `earliest` tags, case-insensitively.
+///
+/// `pending` is refused, as the pending block has no number to scan up to.
+pub fn parse_block_bound(input: &str) -> Result<BlockNumber, String> {
+    match input.trim().to_ascii_lowercase().as_str() {
+        "latest" => Ok(BlockNumber::Latest),
+        "safe" => Ok(BlockNumber::Safe),
+        "finalized" => Ok(BlockNumber::Finalized),
+        "earliest" => Ok(BlockNumber::Earliest),
+        "pending" => Err("the pending block cannot bound a scan".to_string()),
+        number => number.parse::<u64>().map(BlockNumber::from).map_err(|_| {
+            format!(
+                "Invalid block '{}': expected a number, latest, safe, finalized or earliest",
+                input
+            )
+        }),
+    }
+}
+
+/// Resolves `bound` to the number of the block it stands for on `source`, as
+/// chunking needs concrete block numbers.
+///
+/// Numbers and `earliest` need no request; a chain without a `safe` or
+/// `finalized` block yet, e.g. before the merge, is an invalid argument.
+pub async fn resolve_block_bound(
+    source: &dyn ChainSource,
+    bound: BlockNumber,
+) -> Result<u64, CollectorError> {
+    let rpc_error =
+        |e: SourceError| CollectorError::Rpc(format!("Failed to resolve block '{}': {}", bound, e));
+    match bound {
+        BlockNumber::Number(number) => Ok(number.as_u64()),
+        BlockNumber::Earliest => Ok(0),
+        BlockNumber::Latest => source.latest_block_number().await.map_err(rpc_error),
+        BlockNumber::Safe | BlockNumber::Finalized => source
+            .tagged_block_number(bound)
+            .await
+            .map_err(rpc_error)?
+            .ok_or_else(|| {
+                CollectorError::InvalidArgs(format!("The chain has no {} block yet", bound))
+            }),
+        BlockNumber::Pending => Err(CollectorError::InvalidArgs(
+            "the pending block cannot bound a scan".to_string(),
+        )),
+    }
+}
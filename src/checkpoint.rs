@@ -0,0 +1,283 @@
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::csv_manager::CsvDialect;
+use crate::sink::OutputFormat;
+
+/// A block range whose events have been fully written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckpointRange {
+    pub from: u64,
+    pub to: u64,
+    /// Number of events written for the range; absent from older checkpoints.
+    #[serde(default)]
+    pub events: u64,
+}
+
+/// Outcome of reconciling a checkpoint with the output file it describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Reconciliation {
+    /// Complete rows found in the output.
+    pub output_rows: u64,
+    /// Recorded ranges dropped because the output lacks some of their rows.
+    pub rewound_ranges: usize,
+    /// Complete rows cut from the output because no recorded range accounts for them.
+    pub dropped_rows: u64,
+    /// Bytes cut from the output, including those of an incomplete last row.
+    pub truncated_bytes: u64,
+}
+
+impl Reconciliation {
+    /// Returns whether the checkpoint and the output already agreed.
+    pub fn is_consistent(&self) -> bool {
+        self.rewound_ranges == 0 && self.truncated_bytes == 0
+    }
+}
+
+/// Durable record of completed chunk ranges, stored as one JSON object per line.
+///
+/// Unlike deriving progress from the output rows, the checkpoint also records
+/// ranges that legitimately produced zero events, so restarts skip exactly the
+/// chunks that were already done.
+#[derive(Debug)]
+pub struct Checkpoint {
+    path: PathBuf,
+    ranges: Vec<CheckpointRange>,
+}
+
+impl Checkpoint {
+    /// Loads the checkpoint at `path`; a missing file yields an empty checkpoint.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref().to_path_buf();
+        let ranges = match fs::read_to_string(&path) {
+            Ok(content) => content
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(serde_json::from_str)
+                .collect::<Result<Vec<CheckpointRange>, _>>()
+                .map_err(|e| format!("Invalid checkpoint file {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Checkpoint { path, ranges })
+    }
+
+    /// Returns the completed ranges in the order they were recorded.
+    pub fn ranges(&self) -> &[CheckpointRange] {
+        &self.ranges
+    }
+
+    /// Returns whether no range has been recorded yet.
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Returns whether every block of `[from, to]` is covered by recorded ranges.
+    ///
+    /// Adjacent or overlapping ranges are combined, so a chunk is recognised as
+    /// done even if it was written by a run using a different chunk size.
+    pub fn contains(&self, from: u64, to: u64) -> bool {
+        let mut ranges = self.ranges.clone();
+        ranges.sort_by_key(|range| range.from);
+
+        // Sweep the sorted ranges, advancing the first uncovered block
+        let mut next = from;
+        for range in ranges {
+            if range.from > next {
+                break;
+            }
+            if range.to >= next {
+                if range.to >= to {
+                    return true;
+                }
+                next = range.to + 1;
+            }
+        }
+        false
+    }
+
+    /// Returns the number of events recorded for the ranges lying within `[from, to]`,
+    /// i.e. already written by the runs being resumed.
+    pub fn events_within(&self, from: u64, to: u64) -> u64 {
+        self.ranges
+            .iter()
+            .filter(|range| range.from >= from && range.to <= to)
+            .map(|range| range.events)
+            .sum()
+    }
+
+    /// Reconciles the checkpoint with the single-file `output` it describes, for
+    /// the crash windows between writing a chunk and recording it.
+    ///
+    /// The output is trusted up to its last complete row. Recorded ranges are kept
+    /// in order while the output holds all of their events; the first range it
+    /// lacks rows of, e.g. because the rows were buffered when the run died, and
+    /// every later one are rewound so their chunks are collected again. Rows past
+    /// the kept ranges, written for a chunk that was never recorded, and an
+    /// incomplete last row are cut from the output so the redone chunks do not
+    /// duplicate them.
+    ///
+    /// Only CSV and JSON Lines outputs have rows that can be counted this way.
+    pub fn reconcile(
+        &mut self,
+        output: &Path,
+        format: OutputFormat,
+        dialect: CsvDialect,
+    ) -> Result<Reconciliation, Box<dyn Error>> {
+        let len = match fs::metadata(output) {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e.into()),
+        };
+        let (header_end, row_ends) = if len == 0 {
+            (0, Vec::new())
+        } else {
+            match format {
+                OutputFormat::Csv => csv_row_ends(output, dialect, len)?,
+                OutputFormat::Jsonl => (0, line_ends(output)?),
+                _ => {
+                    return Err(
+                        format!("Cannot reconcile a checkpoint with {:?} output", format).into(),
+                    )
+                }
+            }
+        };
+
+        // Keep the recorded ranges whose events all made it to the output
+        let output_rows = row_ends.len() as u64;
+        let mut kept_rows = 0;
+        let kept = self
+            .ranges
+            .iter()
+            .take_while(|range| {
+                let fits = kept_rows + range.events <= output_rows;
+                if fits {
+                    kept_rows += range.events;
+                }
+                fits
+            })
+            .count();
+
+        // Cut the output after the last row of the kept ranges. An output without a
+        // complete header is removed, so the resumed run starts it afresh.
+        let keep_bytes = match kept_rows {
+            0 => header_end,
+            rows => row_ends[rows as usize - 1],
+        };
+        if keep_bytes == 0 && len > 0 {
+            fs::remove_file(output)?;
+        } else if keep_bytes < len {
+            let file = File::options().write(true).open(output)?;
+            file.set_len(keep_bytes)?;
+            file.sync_all()?;
+        }
+
+        let rewound_ranges = self.ranges.len() - kept;
+        if rewound_ranges > 0 {
+            self.ranges.truncate(kept);
+            self.rewrite()?;
+        }
+
+        Ok(Reconciliation {
+            output_rows,
+            rewound_ranges,
+            dropped_rows: output_rows - kept_rows,
+            truncated_bytes: len - keep_bytes,
+        })
+    }
+
+    /// Forgets every recorded range, so the whole range is collected again.
+    pub fn clear(&mut self) -> Result<(), Box<dyn Error>> {
+        self.ranges.clear();
+        self.rewrite()
+    }
+
+    /// Replaces the file with the recorded ranges, through a temporary file so a
+    /// crash leaves either the old or the new checkpoint.
+    fn rewrite(&self) -> Result<(), Box<dyn Error>> {
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        let mut file = File::create(&tmp_path)?;
+        for range in &self.ranges {
+            writeln!(file, "{}", serde_json::to_string(range)?)?;
+        }
+        file.sync_all()?;
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+
+    /// Records `[from, to]` as complete with the `events` written for it, appending
+    /// it durably to the file.
+    pub fn save(&mut self, from: u64, to: u64, events: u64) -> Result<(), Box<dyn Error>> {
+        let range = CheckpointRange { from, to, events };
+        let mut file = File::options().create(true).append(true).open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(&range)?)?;
+        file.sync_all()?;
+
+        self.ranges.push(range);
+        Ok(())
+    }
+}
+
+/// Returns the end offsets of the header and of every complete row of the CSV file
+/// `output`, `len` bytes long. A header-less dialect has its header end at 0.
+fn csv_row_ends(
+    output: &Path,
+    dialect: CsvDialect,
+    len: u64,
+) -> Result<(u64, Vec<u64>), Box<dyn Error>> {
+    let mut reader = dialect
+        .reader()
+        .has_headers(false)
+        .flexible(true)
+        .from_path(output)?;
+    let mut ends = Vec::new();
+    let mut record = csv::ByteRecord::new();
+    while reader.read_byte_record(&mut record)? {
+        ends.push(reader.position().byte());
+    }
+
+    // A row cut short by a crash lacks its line terminator
+    if ends.last() == Some(&len) && !ends_with_newline(output)? {
+        ends.pop();
+    }
+
+    let header_end = if dialect.has_header && !ends.is_empty() {
+        ends.remove(0)
+    } else {
+        0
+    };
+    Ok((header_end, ends))
+}
+
+/// Returns the end offsets of the complete, newline-terminated lines of `output`.
+fn line_ends(output: &Path) -> Result<Vec<u64>, Box<dyn Error>> {
+    let mut reader = BufReader::new(File::open(output)?);
+    let mut ends = Vec::new();
+    let mut offset = 0;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 || line.last() != Some(&b'\n') {
+            break;
+        }
+        offset += read as u64;
+        ends.push(offset);
+    }
+    Ok(ends)
+}
+
+/// Returns whether the non-empty file at `path` ends with a newline.
+fn ends_with_newline(path: &Path) -> std::io::Result<bool> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::End(-1))?;
+    let mut last = [0u8];
+    file.read_exact(&mut last)?;
+    Ok(last[0] == b'\n')
+}
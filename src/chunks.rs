@@ -0,0 +1,77 @@
+use std::str::FromStr;
+
+/// Direction in which a block range is traversed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlockOrder {
+    /// Oldest blocks first (from `from_block` upward).
+    #[default]
+    Asc,
+    /// Newest blocks first (from `to_block` downward).
+    Desc,
+}
+
+impl FromStr for BlockOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "asc" => Ok(BlockOrder::Asc),
+            "desc" => Ok(BlockOrder::Desc),
+            _ => Err(format!("Unsupported block order: {}", s)),
+        }
+    }
+}
+
+/// Iterator over inclusive `(start, end)` block chunks covering `[from, to]`.
+///
+/// Chunks are at most `chunk_size` blocks wide. In ascending order the last
+/// chunk may be shorter; in descending order the chunk touching `from` is.
+#[derive(Debug, Clone)]
+pub struct BlockChunks {
+    low: u64,
+    high: u64,
+    chunk_size: u64,
+    order: BlockOrder,
+    exhausted: bool,
+}
+
+impl BlockChunks {
+    /// Creates a chunk iterator over `[from_block, to_block]`.
+    ///
+    /// A `chunk_size` of zero is treated as one block per chunk.
+    pub fn new(from_block: u64, to_block: u64, chunk_size: u64, order: BlockOrder) -> Self {
+        BlockChunks {
+            low: from_block,
+            high: to_block,
+            chunk_size: chunk_size.max(1),
+            order,
+            exhausted: from_block > to_block,
+        }
+    }
+}
+
+impl Iterator for BlockChunks {
+    type Item = (u64, u64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.exhausted {
+            return None;
+        }
+
+        let span = self.chunk_size - 1;
+        let (start, end) = match self.order {
+            BlockOrder::Asc => (self.low, self.low.saturating_add(span).min(self.high)),
+            BlockOrder::Desc => (self.high.saturating_sub(span).max(self.low), self.high),
+        };
+
+        // Shrink the remaining range, stopping once it has been fully covered
+        match self.order {
+            BlockOrder::Asc if end == self.high => self.exhausted = true,
+            BlockOrder::Asc => self.low = end + 1,
+            BlockOrder::Desc if start == self.low => self.exhausted = true,
+            BlockOrder::Desc => self.high = start - 1,
+        }
+
+        Some((start, end))
+    }
+}
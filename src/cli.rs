@@ -1,32 +1,349 @@
-use clap::{Arg, Command};
+use std::time::Duration;
 
+use clap::{Arg, ArgAction, Command};
+use ethers::types::{BlockNumber, H256, U256};
+
+use crate::block_tags::parse_block_bound;
+use crate::chunks::BlockOrder;
 use crate::constants;
+use crate::csv_manager::{parse_delimiter, CsvDialect};
+use crate::dedup::{DedupKey, OnePerTx};
+use crate::logging::LogFormat;
+use crate::order_by::OrderBy;
+use crate::retry::RetryPolicy;
+use crate::rpc_headers::RpcHeader;
+use crate::sampling::Sampling;
+use crate::sink::{OutputFormat, Partitioning};
+use crate::upload::UploadTarget;
+use crate::utils::Calldata;
+use crate::value_filter::parse_wei;
+use crate::webhook::WebhookConfig;
+
+/// Subcommand selected on the command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CliCommand {
+    /// Collects events (the default when no subcommand is given).
+    Collect,
+    /// Validates the environment, API keys, RPC connectivity and ABI file.
+    Doctor,
+    /// Lists the events of the ABI with their signatures and parameters.
+    ValidateAbi,
+    /// Streams an exported CSV and prints aggregate statistics.
+    Analyze {
+        /// Path of the CSV file to analyze.
+        input: String,
+    },
+    /// Prints the JSON Schema of the events of the JSON formats.
+    JsonSchema,
+}
 
 /// Structure to hold command-line arguments for the Trade Data Collector.
 pub struct CliArgs {
+    /// The subcommand to run.
+    pub command: CliCommand,
     /// The blockchain network to connect to (e.g., Mainnet, Testnet).
     pub network: String,
-    /// The smart contract address from which to track events.
+    /// Chain ID recorded for the output instead of the one reported by the RPC node.
+    pub chain_id: Option<u64>,
+    /// Whether the chain ID is added to every row in a `chain_id` column.
+    pub chain_id_column: bool,
+    /// The smart contract address, or ENS name, from which to track events.
     pub contract_address: String,
-    /// The specific event type to filter (e.g., TakeOrderV2, ClearV2).
+    /// Optional file of `address,label` lines to collect from instead of `contract_address`.
+    pub contracts_file: Option<String>,
+    /// ABI file used instead of the default, e.g. that of a proxy's implementation.
+    pub abi_path: Option<String>,
+    /// Whether the ABI is fetched for the implementation behind an EIP-1967 proxy.
+    pub resolve_proxy: bool,
+    /// The specific event type to filter (e.g., TakeOrderV2, ClearV2, or `all`).
     pub event_type: String,
+    /// Raw event signature hashes to filter on instead of `event_type`.
+    pub topic0: Vec<H256>,
+    /// Subset of the matching events to keep.
+    pub sampling: Sampling,
+    /// Fields identifying duplicate events, of which only the first is kept.
+    pub dedup_key: DedupKey,
+    /// Keeps only the first or last event of each transaction, if set.
+    pub one_per_tx: Option<OnePerTx>,
+    /// Seed of the random sampling, if any.
+    pub seed: Option<u64>,
+    /// Number of events after which the collection stops, if any.
+    pub head: Option<u64>,
+    /// Number of last events written when the run finishes, if any.
+    pub tail: Option<u64>,
+    /// Optional first block of the range, a number or a tag such as `finalized`.
+    pub from_block: Option<BlockNumber>,
+    /// Optional last block of the range, a number or a tag such as `safe`.
+    pub to_block: Option<BlockNumber>,
+    /// Optional ISO-8601 start date, converted to the first block at or after it.
+    pub from_date: Option<String>,
+    /// Optional ISO-8601 end date, converted to the last block at or before it.
+    pub to_date: Option<String>,
+    /// Optional transaction whose block starts the range.
+    pub from_tx: Option<H256>,
+    /// Known contract creation block, used instead of the Etherscan lookup.
+    pub contract_creation_block: Option<u64>,
+    /// Hash of the single block to fetch logs from instead of scanning a range.
+    pub block_hash: Option<H256>,
+    /// Explicit WebSocket RPC URLs; the first is the primary, the rest are failovers.
+    pub rpc_urls: Vec<String>,
+    /// Headers sent with the WebSocket upgrade request of every RPC connection.
+    pub rpc_headers: Vec<RpcHeader>,
+    /// Infura API key overriding `INFURA_API_KEY`, if given.
+    pub infura_key: Option<String>,
+    /// Etherscan API key overriding `ETHERSCAN_API_KEY`, if given.
+    pub etherscan_key: Option<String>,
+    /// The direction in which the block range is traversed (asc or desc).
+    pub order: BlockOrder,
+    /// The format of the output file (csv, jsonl, json or avro), or a table printed instead.
+    pub format: OutputFormat,
+    /// Whether the JSON array output is written incrementally instead of at the end.
+    pub array_streaming: bool,
+    /// Whether binary outputs store addresses and hashes as bytes instead of hex.
+    pub compact_addresses: bool,
+    /// Columns the complete output is sorted by, if any.
+    pub order_by: Option<OrderBy>,
+    /// Estimated bytes of buffered events above which the run fails, if set.
+    pub max_in_flight_bytes: Option<u64>,
+    /// Splits the output into one file per window of blocks or per UTC day, if set.
+    pub partition: Option<Partitioning>,
+    /// Postgres connection URL the events are inserted into instead of a file, if any.
+    pub postgres: Option<String>,
+    /// Webhook the events are also POSTed to, with its batching and retries, if any.
+    pub webhook: Option<WebhookConfig>,
+    /// Delimiter and header presence of the CSV output.
+    pub csv_dialect: CsvDialect,
+    /// Number of blocks per `get_logs` call, overriding the network default.
+    pub chunk_size: Option<u64>,
+    /// Maximum number of blocks per `get_logs` call, overriding the network default.
+    pub max_block_span: Option<u64>,
+    /// Pause between chunks in milliseconds, overriding the network default.
+    pub delay_ms: Option<u64>,
+    /// Cap on the RPC requests per second, 0 for none; tuned for the network if unset.
+    pub rps_limit: Option<u32>,
+    /// Number of most recent blocks left out of the scan.
+    pub confirmations: u64,
+    /// Maximum number of logs of a chunk enriched concurrently.
+    pub enrich_concurrency: usize,
+    /// Directory receiving one descriptively named output file per contract, if set.
+    pub output_dir: Option<String>,
+    /// Error log of a previous run whose failed chunks are collected again, if set.
+    pub retry_failed: Option<String>,
+    /// File of `from,to` block ranges collected instead of the whole range, if any.
+    pub ranges_file: Option<String>,
+    /// Whether new heads are followed after backfilling up to the latest block.
+    pub catch_up_then_follow: bool,
+    /// Number of blocks before each new head re-queried while following.
+    pub lookback_overlap: u64,
+    /// Optional checkpoint file recording completed chunk ranges.
+    pub checkpoint: Option<String>,
+    /// Whether the checkpoint is reconciled with the output before resuming.
+    pub resume_from_checkpoint: bool,
+    /// Whether the raw log data and topics are appended to each row.
+    pub raw: bool,
+    /// Whether each decoded event parameter is written to a column of its own.
+    pub flatten_args: bool,
+    /// Whether each indexed address parameter is written to a column of its own.
+    pub indexed_addresses: bool,
+    /// Whether the immediate caller of the contract is traced into an extra column.
+    pub include_internal: bool,
+    /// Whether rows are written from log data alone, skipping the block and transaction fetches.
+    pub no_enrich: bool,
+    /// Smallest transaction value of the kept events, in wei, if any.
+    pub min_value: Option<U256>,
+    /// Largest transaction value of the kept events, in wei, if any.
+    pub max_value: Option<U256>,
+    /// Whether the gas price paid by each transaction is written to a column.
+    pub gas_price: bool,
+    /// Whether each transaction receipt is fetched for its effective gas price.
+    pub with_receipts: bool,
+    /// How much of each transaction input is written to a column, if any.
+    pub calldata: Option<Calldata>,
+    /// Whether per-block event counts are written instead of individual events.
+    pub aggregate_by_block: bool,
+    /// Whether the run aborts instead of leaving any event out of the output.
+    pub strict: bool,
+    /// Whether appending to an output collected with another ABI is allowed.
+    pub force: bool,
+    /// Whether the output file is fsynced after every chunk.
+    pub fsync: bool,
+    /// Whether the timing of every chunk is logged and summarized.
+    pub timing: bool,
+    /// Whether the `eth_getLogs` filter is logged as JSON before the first query.
+    pub dump_filter: bool,
+    /// Port on which Prometheus metrics are served, if any.
+    pub metrics_port: Option<u16>,
+    /// Bucket and prefix the output is uploaded to after a successful run, if any.
+    pub upload: Option<UploadTarget>,
+    /// S3-compatible endpoint used instead of AWS for the upload.
+    pub s3_endpoint: Option<String>,
+    /// Whether the effective configuration is printed instead of collecting.
+    pub print_config: bool,
+    /// Whether numbers in human-facing output use thousands separators.
+    pub pretty: bool,
+    /// Whether colors are disabled in terminal output.
+    pub no_color: bool,
+    /// Whether contract creation blocks are looked up again instead of read from the cache.
+    pub no_cache: bool,
+    /// Number of times a failed Etherscan request is retried, with exponential backoff.
+    pub max_retries: u32,
+    /// The format of emitted log lines (text or json).
+    pub log_format: LogFormat,
 }
 
+/// Usage examples shown after the options in `--help`.
+const EXAMPLES: &str = "\
+Examples:
+  Collect every event of the default contract on Mainnet:
+    trade-data-collector
+
+  Collect TakeOrderV2 events on Base between two dates, as JSON lines:
+    trade-data-collector --network Base --event TakeOrderV2 \\
+      --from-date 2024-01-01 --to-date 2024-02-01 --format jsonl
+
+  Resume an interrupted run, keeping 12 blocks away from the chain tip:
+    trade-data-collector --checkpoint progress.txt --confirmations 12
+
+  Check the environment, then summarize an export:
+    trade-data-collector doctor
+    trade-data-collector analyze --input order_events.csv";
+
 /// Parses command-line arguments and returns a `CliArgs` struct.
 ///
 /// This function utilizes the `clap` crate to handle CLI arguments.
 /// It defines and retrieves values for the following options:
 /// - `--network` (`-n`): Specifies the blockchain network (default: Mainnet).
-/// - `--contract` (`-c`): Specifies the smart contract address (default: 0x0ea6d458488d1cf51695e1d6e4744e6fb715d37c).
-/// - `--event` (`-e`): Specifies the event type to filter (optional).
+/// - `--chain-id` (`--network-id`): Records this chain ID instead of the one reported by the RPC
+///   node; a mismatch with `--network` is warned about.
+/// - `--chain-id-column`: Adds the chain ID to every row in a `chain_id` column.
+/// - `--contract` (`-c`): Specifies the smart contract address or ENS name, resolved before the
+///   scan (default: 0x0ea6d458488d1cf51695e1d6e4744e6fb715d37c).
+/// - `--contracts-file`: Reads `address,label` lines and collects from every listed contract.
+/// - `--abi`: Loads the ABI from a file other than `IOrderBookV4.json`, e.g. the implementation
+///   ABI of a proxy contract.
+/// - `--resolve-proxy`: Reads the EIP-1967 implementation slot of each contract and fetches the
+///   implementation ABI from Etherscan.
+/// - `--validate-abi`: Lists every event of the ABI with its topic0 and parameters, and checks
+///   that the collected events are declared, without collecting.
+/// - `--print-config`: Prints the effective configuration as JSON, with API keys and
+///   passwords redacted, and exits without collecting.
+/// - `--event` (`-e`): Specifies the event type to filter; omitted, empty or `all` selects every
+///   collected event.
+/// - `--topic0` (`--event-signature`): Filters by raw 32-byte event signature hashes instead of
+///   `--event`, without loading the ABI. An explicit `--event` must name the same events.
+/// - `--sample-rate` / `--stride`: Keeps a random fraction or every Nth event only; `--seed`
+///   makes the random sample reproducible.
+/// - `--head` / `--tail`: Writes only the first N events, stopping the scan once they are
+///   written, or only the last N, buffered until the run finishes.
+/// - `--dedup-key`: Keeps only the first event per transaction (`tx`), log (`tx-log`) or event
+///   type within a transaction (`tx-event`) (default: none).
+/// - `--one-per-tx`: Keeps only the `first` or `last` event (by log index) of each transaction.
+/// - `--from-block` / `--to-block`: Restricts the range to blocks given by number or by the
+///   `latest`, `safe`, `finalized` or `earliest` tags, resolved once before the scan.
+/// - `--from-date` / `--to-date`: Restricts the range to blocks between two ISO-8601 dates.
+/// - `--from-tx`: Starts at the block of a transaction, fetched by its hash.
+/// - `--contract-creation-block`: Starts at a known creation block instead of looking it up on
+///   Etherscan, so that no Etherscan API key is needed.
+/// - `--block-hash`: Fetches the logs of exactly one block by hash, which is reorg-safe,
+///   instead of scanning a range.
+/// - `--rpc-url`: WebSocket RPC URL(s) to use instead of the network default (repeatable
+///   or comma-separated; later URLs are failovers).
+/// - `--infura-key` / `--etherscan-key`: API keys taking precedence over the `INFURA_API_KEY`
+///   and `ETHERSCAN_API_KEY` environment variables.
+/// - `--rpc-header`: `Name: value` header sent when connecting to the RPC endpoints, such as
+///   `Authorization: Bearer <token>`.
+/// - `--order`: Traverses blocks in ascending or descending order (default: asc).
+/// - `doctor`: Subcommand running preflight checks instead of a collection.
+/// - `analyze [--input <FILE>]`: Subcommand printing aggregates of an exported CSV.
+/// - `json-schema`: Subcommand printing the JSON Schema of the events of the JSON formats, with
+///   the optional columns enabled by the other flags.
+/// - `--format`: Writes CSV rows, JSON lines, a JSON array or Avro records; all but CSV
+///   include the decoded event arguments (default: csv). `table` prints the events to the
+///   terminal instead.
+/// - `--array-streaming`: Writes the `json` array as events are collected instead of keeping
+///   it in memory until the run finishes.
+/// - `--compact-addresses`: Stores transaction origins and hashes as 20 and 32-byte binary
+///   values in `avro` outputs; text outputs ignore it.
+/// - `--order-by`: Sorts the complete `csv` or `jsonl` output by comma-separated columns, each
+///   optionally followed by `:desc`.
+/// - `--max-in-flight-bytes`: Fails the run once the events buffered by the output, such as
+///   the `json` array, are estimated to take more bytes than this.
+/// - `--delimiter` / `--no-header`: Separates CSV fields with another character (`\t` or `tab`
+///   for TSV) and leaves out the header row.
+/// - `--partition-blocks`: Writes one output file per window of N blocks.
+/// - `--partition-by day`: Writes one output file per UTC day of the block timestamps, e.g.
+///   `order_events.2024-01-15.csv`.
+/// - `--postgres`: Inserts the events into the `order_events` table of a Postgres database,
+///   created if absent, instead of writing a file.
+/// - `--webhook`: POSTs the events as JSON to a URL as they are collected, in batches of
+///   `--webhook-batch` events or every `--webhook-interval` seconds, retrying failed
+///   deliveries up to `--webhook-retries` times.
+/// - `--chunk-size` / `--delay-ms`: Blocks per `get_logs` call and pause between chunks,
+///   defaulting to values tuned for the network.
+/// - `--max-block-span`: Caps the blocks of a single `get_logs` call for providers rejecting
+///   wider ranges, querying larger chunks in several calls.
+/// - `--rps-limit`: Caps the RPC requests per second across every concurrent call (default:
+///   tuned for the network, 0 for no limit).
+/// - `--confirmations`: Stops the scan this many blocks below the latest block (default: 0).
+/// - `--enrich-concurrency`: Fetches the blocks and transactions of up to N logs at a time
+///   (default: 8).
+/// - `--checkpoint`: Records completed chunks to a file and skips them on restart.
+/// - `--resume-from-checkpoint`: Reconciles the checkpoint with the output before resuming,
+///   rewinding chunks whose rows the output lacks.
+/// - `--output-dir`: Writes each contract to `<network>_<contract>_<from>-<to>.<ext>` in a
+///   directory instead of the single `order_events` file.
+/// - `--retry-failed`: Collects again only the chunks recorded in an error log, such as the
+///   `order_events.errors.jsonl` written next to the output when a chunk fails.
+/// - `--ranges-file`: Collects only the `from,to` block ranges listed one per line in a file.
+/// - `--catch-up-then-follow`: Backfills up to the latest block, then subscribes to new heads and
+///   keeps appending their events, filling the blocks mined during the handoff.
+/// - `--lookback-overlap`: Re-queries that many blocks before each new head while following,
+///   writing only the logs not written yet.
+/// - `--raw`: Appends the hex-encoded log data and topics to each row.
+/// - `--flatten-args`: Appends an `arg_<name>` column per parameter of the collected events,
+///   left empty for events without it.
+/// - `--indexed-addresses`: Appends a `topic_<name>` column per indexed address parameter of
+///   the collected events, decoded from the log topics, e.g. the counterparties of a trade.
+/// - `--include-internal`: Adds a `direct_caller` column traced with `trace_transaction`.
+/// - `--no-enrich`: Writes `block_number` and `log_index` columns from the logs alone instead
+///   of fetching the block and transaction of each log for `tx.origin` and `timestamp`.
+/// - `--min-value` / `--max-value`: Keeps only the events whose transaction sent at least or at
+///   most the given amount, in `wei`, `gwei` or `eth` (e.g. `1.5eth`); skipped events are
+///   counted in the summary.
+/// - `--gas-price`: Adds a `gas_price` column, in wei, from the transaction of each event.
+/// - `--with-receipts`: With `--gas-price`, also fetches each transaction receipt for an
+///   `effective_gas_price` column, what EIP-1559 transactions actually paid.
+/// - `--with-calldata`: Adds an `input` column with the hex calldata of each transaction, or
+///   only its 4-byte selector with `--calldata selector`.
+/// - `--strict`: Aborts on the first event that would be left out, such as a failed chunk or a
+///   log whose block or transaction is missing, instead of skipping it.
+/// - `--force`: Appends to an output whose metadata records another ABI or other events,
+///   instead of failing.
+/// - `--aggregate-by-block`: Writes one row of TakeOrder and Clear counts per block, fetching
+///   no transactions.
+/// - `--fsync`: Forces each written chunk to disk before continuing.
+/// - `--timing`: Logs the blocks, logs, `get_logs` and enrichment durations and rows of every
+///   chunk, and adds min/avg/max chunk durations to the summary.
+/// - `--dump-filter`: Logs the `eth_getLogs` filter as JSON before the first query, to replay
+///   it with curl or cast.
+/// - `--metrics-port`: Serves Prometheus metrics over HTTP on the given port.
+/// - `--upload`: Uploads the output to `s3://bucket/prefix` after a successful run, with the
+///   standard AWS credentials; `--s3-endpoint` targets an S3-compatible service instead.
+/// - `--pretty` / `--no-pretty`: Groups numbers with thousands separators (default: pretty).
+/// - `--no-cache`: Looks contract creation blocks up on Etherscan even when cached locally.
+/// - `--max-retries`: Retries Etherscan requests failing with a network error or the rate
+///   limit this many times, with exponential backoff (default: 3).
+/// - `--no-color`: Disables colors in the end-of-run summary (also disabled by `NO_COLOR` or
+///   when stdout is not a terminal).
+/// - `--log-format`: Emits human-readable (`text`) or structured (`json`) logs (default: text).
 ///
 /// # Returns
 /// A `CliArgs` struct containing the parsed values from the command line.
 pub fn parse_cli_args() -> CliArgs {
     let matches = Command::new("Trade Data Collector")
-        .version("1.0")
-        .author("Mike")
+        .version(env!("BUILD_VERSION"))
         .about("Collects and exports trade order events from a DEX")
+        .after_help(EXAMPLES)
         .arg(
             Arg::new("network")
                 .short('n')
@@ -36,6 +353,21 @@ pub fn parse_cli_args() -> CliArgs {
                 .default_value("Mainnet")
                 .help("Specifies the blockchain network to use (default: Mainnet)"),
         )
+        .arg(
+            Arg::new("chain-id")
+                .long("chain-id")
+                .visible_alias("network-id")
+                .num_args(1)
+                .value_name("ID")
+                .value_parser(clap::value_parser!(u64))
+                .help("Chain ID recorded for the output (default: reported by the RPC node)"),
+        )
+        .arg(
+            Arg::new("chain-id-column")
+                .long("chain-id-column")
+                .action(ArgAction::SetTrue)
+                .help("Adds the chain ID to every row in a chain_id column"),
+        )
         .arg(
             Arg::new("contract")
                 .short('c')
@@ -43,22 +375,899 @@ pub fn parse_cli_args() -> CliArgs {
                 .num_args(1)
                 .value_name("CONTRACT_ADDRESS")
                 .default_value(constants::DEFAULT_CONTRACT_ADDRESS)
-                .help("The smart contract address to track events from"),
+                .help("The smart contract address, or ENS name, to track events from"),
+        )
+        .arg(
+            Arg::new("contracts-file")
+                .long("contracts-file")
+                .num_args(1)
+                .value_name("FILE")
+                .conflicts_with("checkpoint")
+                .help(
+                    "Collects from every `address,label` line of FILE, tagging rows with the label",
+                ),
+        )
+        .arg(
+            Arg::new("abi")
+                .long("abi")
+                .num_args(1)
+                .value_name("FILE")
+                .help("Loads the contract ABI from FILE (default: ./IOrderBookV4.json)"),
+        )
+        .arg(
+            Arg::new("resolve-proxy")
+                .long("resolve-proxy")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["abi", "topic0"])
+                .help("Fetches the ABI of the implementation behind an EIP-1967 proxy from Etherscan"),
+        )
+        .arg(
+            Arg::new("validate-abi")
+                .long("validate-abi")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["resolve-proxy", "topic0"])
+                .help("Lists the events of the ABI with their topic0 and parameters, then exits"),
+        )
+        .arg(
+            Arg::new("print-config")
+                .long("print-config")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("validate-abi")
+                .help("Prints the effective configuration, secrets redacted, then exits"),
         )
         .arg(
             Arg::new("event")
                 .short('e')
                 .long("event")
+                .num_args(0..=1)
                 .value_name("EVENT_TYPE")
-                .default_value(constants::DEFAULT)
-                .help("Filters by a specific event type (e.g., TakeOrderV2, ClearV2)"),
+                .default_value(constants::ALL_EVENTS)
+                .default_missing_value(constants::ALL_EVENTS)
+                .help("Filters by a specific event type (TakeOrderV2, ClearV2) or all events"),
+        )
+        .arg(
+            Arg::new("topic0")
+                .long("topic0")
+                .visible_alias("event-signature")
+                .num_args(1)
+                .value_name("SIGNATURE")
+                .action(ArgAction::Append)
+                .value_delimiter(',')
+                .value_parser(|s: &str| {
+                    s.parse::<H256>()
+                        .map_err(|_| format!("Invalid 32-byte event signature: {}", s))
+                })
+                .help("Filters by raw event signature hash(es) instead of --event, without an ABI"),
+        )
+        .arg(
+            Arg::new("sample-rate")
+                .long("sample-rate")
+                .num_args(1)
+                .value_name("RATE")
+                .value_parser(|s: &str| match s.parse::<f64>() {
+                    Ok(rate) if rate > 0.0 && rate <= 1.0 => Ok(rate),
+                    _ => Err(format!("Sample rate must be in (0, 1]: {}", s)),
+                })
+                .conflicts_with("stride")
+                .help("Keeps each event with probability RATE, e.g. 0.1 for about 10%"),
+        )
+        .arg(
+            Arg::new("stride")
+                .long("stride")
+                .num_args(1)
+                .value_name("N")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .help("Keeps every Nth event only"),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .num_args(1)
+                .value_name("SEED")
+                .value_parser(clap::value_parser!(u64))
+                .requires("sample-rate")
+                .help("Seeds --sample-rate so the same events are kept on every run"),
+        )
+        .arg(
+            Arg::new("head")
+                .long("head")
+                .num_args(1)
+                .value_name("N")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .conflicts_with_all([
+                    "tail",
+                    "contracts-file",
+                    "checkpoint",
+                    "aggregate-by-block",
+                    "catch-up-then-follow",
+                ])
+                .help("Stops once the first N events are written"),
+        )
+        .arg(
+            Arg::new("tail")
+                .long("tail")
+                .num_args(1)
+                .value_name("N")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .conflicts_with_all([
+                    "contracts-file",
+                    "checkpoint",
+                    "aggregate-by-block",
+                    "catch-up-then-follow",
+                    "array-streaming",
+                    "webhook",
+                ])
+                .help("Writes only the last N events, buffered until the run finishes"),
+        )
+        .arg(
+            Arg::new("dedup-key")
+                .long("dedup-key")
+                .num_args(1)
+                .value_name("KEY")
+                .value_parser(["none", "tx", "tx-log", "tx-event"])
+                .default_value("none")
+                .help("Keeps only the first event per transaction, log or transaction and type"),
+        )
+        .arg(
+            Arg::new("one-per-tx")
+                .long("one-per-tx")
+                .num_args(1)
+                .value_name("MODE")
+                .value_parser(["first", "last"])
+                .help("Keeps only the first or last event (by log index) of each transaction"),
+        )
+        .arg(
+            Arg::new("from-block")
+                .long("from-block")
+                .num_args(1)
+                .value_name("BLOCK")
+                .value_parser(parse_block_bound)
+                .conflicts_with("from-date")
+                .help("Starts at BLOCK: a number, latest, safe, finalized or earliest"),
+        )
+        .arg(
+            Arg::new("to-block")
+                .long("to-block")
+                .num_args(1)
+                .value_name("BLOCK")
+                .value_parser(parse_block_bound)
+                .conflicts_with("to-date")
+                .help("Ends at BLOCK: a number, latest, safe, finalized or earliest"),
+        )
+        .arg(
+            Arg::new("from-date")
+                .long("from-date")
+                .num_args(1)
+                .value_name("DATE")
+                .help("Starts at the first block at or after DATE (YYYY-MM-DD or RFC 3339)"),
+        )
+        .arg(
+            Arg::new("to-date")
+                .long("to-date")
+                .num_args(1)
+                .value_name("DATE")
+                .help("Ends at the last block at or before DATE (YYYY-MM-DD or RFC 3339)"),
+        )
+        .arg(
+            Arg::new("from-tx")
+                .long("from-tx")
+                .num_args(1)
+                .value_name("HASH")
+                .value_parser(|s: &str| {
+                    s.parse::<H256>()
+                        .map_err(|_| format!("Invalid 32-byte transaction hash: {}", s))
+                })
+                .conflicts_with_all(["from-block", "from-date"])
+                .help("Starts at the block the transaction with hash HASH was mined in"),
+        )
+        .arg(
+            Arg::new("contract-creation-block")
+                .long("contract-creation-block")
+                .num_args(1)
+                .value_name("BLOCK")
+                .value_parser(clap::value_parser!(u64))
+                .conflicts_with_all(["contracts-file", "block-hash"])
+                .help("Contract creation BLOCK, skipping the Etherscan lookup"),
+        )
+        .arg(
+            Arg::new("block-hash")
+                .long("block-hash")
+                .num_args(1)
+                .value_name("HASH")
+                .value_parser(|s: &str| {
+                    s.parse::<H256>()
+                        .map_err(|_| format!("Invalid 32-byte block hash: {}", s))
+                })
+                .conflicts_with_all([
+                    "from-block",
+                    "to-block",
+                    "from-date",
+                    "to-date",
+                    "from-tx",
+                    "retry-failed",
+                    "checkpoint",
+                ])
+                .help("Fetches the logs of the block with hash HASH only, immune to reorgs"),
+        )
+        .arg(
+            Arg::new("rpc-url")
+                .long("rpc-url")
+                .num_args(1)
+                .value_name("WS_RPC_URL")
+                .action(ArgAction::Append)
+                .value_delimiter(',')
+                .help("WebSocket RPC URL(s); the first is primary, later ones are failovers"),
+        )
+        .arg(
+            Arg::new("infura-key")
+                .long("infura-key")
+                .num_args(1)
+                .value_name("KEY")
+                .help("Infura API key, overriding the INFURA_API_KEY environment variable"),
+        )
+        .arg(
+            Arg::new("etherscan-key")
+                .long("etherscan-key")
+                .num_args(1)
+                .value_name("KEY")
+                .help("Etherscan API key, overriding the ETHERSCAN_API_KEY environment variable"),
+        )
+        .arg(
+            Arg::new("rpc-header")
+                .long("rpc-header")
+                .num_args(1)
+                .value_name("HEADER")
+                .action(ArgAction::Append)
+                .value_parser(|s: &str| s.parse::<RpcHeader>())
+                .help("`Name: value` header sent when connecting, e.g. \"Authorization: Bearer TOKEN\""),
+        )
+        .arg(
+            Arg::new("order")
+                .long("order")
+                .num_args(1)
+                .value_name("ORDER")
+                .value_parser(["asc", "desc"])
+                .default_value("asc")
+                .help("Traverses blocks oldest-first (asc) or newest-first (desc)"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .num_args(1)
+                .value_name("FORMAT")
+                .value_parser(["csv", "jsonl", "json", "avro", "table"])
+                .default_value("csv")
+                .help(
+                    "Writes CSV rows, or JSON lines, a JSON array or Avro records with \
+                     decoded event arguments; `table` prints the events instead",
+                ),
+        )
+        .arg(
+            Arg::new("array-streaming")
+                .long("array-streaming")
+                .action(ArgAction::SetTrue)
+                .requires("format")
+                .help("Writes the JSON array of --format json incrementally, chunk by chunk"),
+        )
+        .arg(
+            Arg::new("compact-addresses")
+                .long("compact-addresses")
+                .action(ArgAction::SetTrue)
+                .help("Stores addresses and hashes as bytes in binary outputs (avro)"),
+        )
+        .arg(
+            Arg::new("order-by")
+                .long("order-by")
+                .num_args(1)
+                .value_name("COLUMNS")
+                .value_parser(|s: &str| s.parse::<OrderBy>())
+                .conflicts_with_all([
+                    "no-header",
+                    "partition-blocks",
+                    "partition-by",
+                    "postgres",
+                    "catch-up-then-follow",
+                ])
+                .help("Sorts the output by COLUMNS, e.g. timestamp,log_index:desc"),
+        )
+        .arg(
+            Arg::new("max-in-flight-bytes")
+                .long("max-in-flight-bytes")
+                .num_args(1)
+                .value_name("BYTES")
+                .value_parser(clap::value_parser!(u64))
+                .help("Fails once the events buffered by the output exceed this many bytes"),
+        )
+        .arg(
+            Arg::new("delimiter")
+                .long("delimiter")
+                .num_args(1)
+                .value_name("CHAR")
+                .value_parser(parse_delimiter)
+                .help("Separates CSV fields with CHAR, e.g. `tab` for TSV (default: ,)"),
+        )
+        .arg(
+            Arg::new("no-header")
+                .long("no-header")
+                .action(ArgAction::SetTrue)
+                .help("Leaves the header row out of the CSV output"),
+        )
+        .arg(
+            Arg::new("partition-blocks")
+                .long("partition-blocks")
+                .num_args(1)
+                .value_name("N")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .help("Writes one output file per window of N blocks, e.g. order_events.0-999.csv"),
+        )
+        .arg(
+            Arg::new("partition-by")
+                .long("partition-by")
+                .num_args(1)
+                .value_name("UNIT")
+                .value_parser(["day"])
+                .conflicts_with("partition-blocks")
+                .help("Writes one output file per UTC day of the block timestamps, e.g. order_events.2024-01-15.csv"),
+        )
+        .arg(
+            Arg::new("postgres")
+                .long("postgres")
+                .num_args(1)
+                .value_name("URL")
+                .conflicts_with_all([
+                    "format",
+                    "partition-blocks",
+                    "partition-by",
+                    "output-dir",
+                    "upload",
+                    "aggregate-by-block",
+                ])
+                .help("Inserts the events into the order_events table of a Postgres database"),
+        )
+        .arg(
+            Arg::new("webhook")
+                .long("webhook")
+                .num_args(1)
+                .value_name("URL")
+                .conflicts_with("aggregate-by-block")
+                .help("Also POSTs the collected events as JSON to URL, for real-time notifications"),
+        )
+        .arg(
+            Arg::new("webhook-batch")
+                .long("webhook-batch")
+                .num_args(1)
+                .value_name("N")
+                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..))
+                .requires("webhook")
+                .help("Events per webhook request (default: 100)"),
+        )
+        .arg(
+            Arg::new("webhook-interval")
+                .long("webhook-interval")
+                .num_args(1)
+                .value_name("SECS")
+                .value_parser(clap::value_parser!(u64))
+                .requires("webhook")
+                .help("Delivers a partial batch once SECS have passed since the last one (default: 5)"),
+        )
+        .arg(
+            Arg::new("webhook-retries")
+                .long("webhook-retries")
+                .num_args(1)
+                .value_name("N")
+                .value_parser(clap::value_parser!(u32))
+                .requires("webhook")
+                .help("Retries a failed webhook delivery up to N times with backoff (default: 3)"),
+        )
+        .arg(
+            Arg::new("chunk-size")
+                .long("chunk-size")
+                .num_args(1)
+                .value_name("BLOCKS")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .help("Blocks fetched per get_logs call (default: tuned for the network)"),
+        )
+        .arg(
+            Arg::new("max-block-span")
+                .long("max-block-span")
+                .num_args(1)
+                .value_name("BLOCKS")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .help("Blocks a single get_logs call may span at most, for restrictive providers"),
+        )
+        .arg(
+            Arg::new("delay-ms")
+                .long("delay-ms")
+                .num_args(1)
+                .value_name("MS")
+                .value_parser(clap::value_parser!(u64))
+                .help("Pause between chunks in milliseconds (default: tuned for the network)"),
+        )
+        .arg(
+            Arg::new("rps-limit")
+                .long("rps-limit")
+                .num_args(1)
+                .value_name("N")
+                .value_parser(clap::value_parser!(u32))
+                .help("Caps RPC requests per second, 0 for no limit (default: tuned for the network)"),
+        )
+        .arg(
+            Arg::new("confirmations")
+                .long("confirmations")
+                .num_args(1)
+                .value_name("N")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("0")
+                .help("Leaves the latest N blocks, which may still be reorged, out of the scan (12 is a safe choice)"),
+        )
+        .arg(
+            Arg::new("enrich-concurrency")
+                .long("enrich-concurrency")
+                .num_args(1)
+                .value_name("N")
+                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..))
+                .default_value("8")
+                .help("Fetches the blocks and transactions of up to N logs of a chunk at a time"),
+        )
+        .arg(
+            Arg::new("checkpoint")
+                .long("checkpoint")
+                .num_args(1)
+                .value_name("FILE")
+                .help("Records completed chunk ranges to FILE and skips them when restarting"),
+        )
+        .arg(
+            Arg::new("resume-from-checkpoint")
+                .long("resume-from-checkpoint")
+                .action(ArgAction::SetTrue)
+                .requires("checkpoint")
+                .conflicts_with_all([
+                    "partition-blocks",
+                    "partition-by",
+                    "postgres",
+                    "aggregate-by-block",
+                ])
+                .help("Reconciles the checkpoint with the output file before resuming"),
+        )
+        .arg(
+            Arg::new("output-dir")
+                .long("output-dir")
+                .num_args(1)
+                .value_name("DIR")
+                .help("Writes each contract to a file named after the network, contract and block range in DIR"),
+        )
+        .arg(
+            Arg::new("retry-failed")
+                .long("retry-failed")
+                .num_args(1)
+                .value_name("ERRORS_FILE")
+                .conflicts_with_all([
+                    "contracts-file",
+                    "from-block",
+                    "to-block",
+                    "from-date",
+                    "to-date",
+                    "from-tx",
+                    "output-dir",
+                ])
+                .help("Collects again only the failed chunks recorded in ERRORS_FILE"),
+        )
+        .arg(
+            Arg::new("ranges-file")
+                .long("ranges-file")
+                .num_args(1)
+                .value_name("FILE")
+                .conflicts_with_all([
+                    "retry-failed",
+                    "from-block",
+                    "to-block",
+                    "from-date",
+                    "to-date",
+                    "from-tx",
+                    "block-hash",
+                ])
+                .help("Collects only the `from,to` block ranges listed in FILE"),
+        )
+        .arg(
+            Arg::new("catch-up-then-follow")
+                .long("catch-up-then-follow")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "contracts-file",
+                    "to-block",
+                    "to-date",
+                    "block-hash",
+                    "retry-failed",
+                    "ranges-file",
+                    "output-dir",
+                    "upload",
+                ])
+                .help("Backfills up to the latest block, then keeps collecting new blocks"),
+        )
+        .arg(
+            Arg::new("lookback-overlap")
+                .long("lookback-overlap")
+                .value_name("BLOCKS")
+                .value_parser(clap::value_parser!(u64))
+                .requires("catch-up-then-follow")
+                .help("Re-queries BLOCKS blocks before each new head for logs indexed late"),
+        )
+        .arg(
+            Arg::new("raw")
+                .long("raw")
+                .action(ArgAction::SetTrue)
+                .help("Appends the hex-encoded log data and topics to each row"),
+        )
+        .arg(
+            Arg::new("flatten-args")
+                .long("flatten-args")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["topic0", "postgres"])
+                .help("Appends a column per decoded event parameter, e.g. arg_sender"),
+        )
+        .arg(
+            Arg::new("indexed-addresses")
+                .long("indexed-addresses")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["topic0", "postgres"])
+                .help("Appends a column per indexed address parameter, e.g. topic_sender"),
+        )
+        .arg(
+            Arg::new("include-internal")
+                .long("include-internal")
+                .action(ArgAction::SetTrue)
+                .help("Traces each transaction to record the contract's immediate caller"),
+        )
+        .arg(
+            Arg::new("no-enrich")
+                .long("no-enrich")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["include-internal", "postgres"])
+                .help("Writes log data only, skipping the block and transaction of each log"),
+        )
+        .arg(
+            Arg::new("min-value")
+                .long("min-value")
+                .num_args(1)
+                .value_name("AMOUNT")
+                .value_parser(parse_wei)
+                .conflicts_with("no-enrich")
+                .help("Keeps only events whose transaction value is at least AMOUNT, e.g. 1.5eth"),
+        )
+        .arg(
+            Arg::new("max-value")
+                .long("max-value")
+                .num_args(1)
+                .value_name("AMOUNT")
+                .value_parser(parse_wei)
+                .conflicts_with("no-enrich")
+                .help("Keeps only events whose transaction value is at most AMOUNT, e.g. 20gwei"),
+        )
+        .arg(
+            Arg::new("gas-price")
+                .long("gas-price")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["no-enrich", "postgres"])
+                .help("Adds the gas price paid by each transaction, in wei"),
+        )
+        .arg(
+            Arg::new("with-receipts")
+                .long("with-receipts")
+                .action(ArgAction::SetTrue)
+                .requires("gas-price")
+                .help("Fetches each transaction receipt for its effective gas price"),
+        )
+        .arg(
+            Arg::new("with-calldata")
+                .long("with-calldata")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["no-enrich", "postgres"])
+                .help("Adds the hex input data of each transaction in an input column"),
+        )
+        .arg(
+            Arg::new("calldata")
+                .long("calldata")
+                .num_args(1)
+                .value_name("MODE")
+                .value_parser(["full", "selector"])
+                .requires("with-calldata")
+                .help("Writes the whole calldata (full, the default) or its 4-byte selector"),
+        )
+        .arg(
+            Arg::new("strict")
+                .long("strict")
+                .action(ArgAction::SetTrue)
+                .help("Aborts instead of skipping any event, for datasets that must be complete"),
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .action(ArgAction::SetTrue)
+                .help("Appends to an output even if its metadata records another ABI"),
+        )
+        .arg(
+            Arg::new("aggregate-by-block")
+                .long("aggregate-by-block")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "topic0",
+                    "format",
+                    "raw",
+                    "include-internal",
+                    "contracts-file",
+                    "chain-id-column",
+                    "delimiter",
+                    "no-header",
+                    "flatten-args",
+                    "no-enrich",
+                    "indexed-addresses",
+                    "gas-price",
+                    "with-calldata",
+                    "min-value",
+                    "max-value",
+                ])
+                .help("Writes the TakeOrder and Clear counts of each block instead of events"),
+        )
+        .arg(
+            Arg::new("fsync")
+                .long("fsync")
+                .action(ArgAction::SetTrue)
+                .help("Fsyncs the output after every chunk (slower, but crash safe)"),
+        )
+        .arg(
+            Arg::new("timing")
+                .long("timing")
+                .action(ArgAction::SetTrue)
+                .help("Logs how long each chunk took, to tune --chunk-size"),
+        )
+        .arg(
+            Arg::new("dump-filter")
+                .long("dump-filter")
+                .action(ArgAction::SetTrue)
+                .help("Logs the eth_getLogs filter as JSON before the first query"),
+        )
+        .arg(
+            Arg::new("metrics-port")
+                .long("metrics-port")
+                .num_args(1)
+                .value_name("PORT")
+                .value_parser(clap::value_parser!(u16))
+                .help("Serves Prometheus metrics over HTTP on PORT while collecting"),
+        )
+        .arg(
+            Arg::new("upload")
+                .long("upload")
+                .num_args(1)
+                .value_name("S3_URL")
+                .value_parser(|s: &str| s.parse::<UploadTarget>())
+                .help("Uploads the output to s3://bucket/prefix after a successful run"),
+        )
+        .arg(
+            Arg::new("s3-endpoint")
+                .long("s3-endpoint")
+                .num_args(1)
+                .value_name("URL")
+                .requires("upload")
+                .help("S3-compatible endpoint (e.g. MinIO) to upload to instead of AWS"),
+        )
+        .arg(
+            Arg::new("pretty")
+                .long("pretty")
+                .action(ArgAction::SetTrue)
+                .overrides_with("no-pretty")
+                .help("Formats numbers with thousands separators in logs (default)"),
+        )
+        .arg(
+            Arg::new("no-pretty")
+                .long("no-pretty")
+                .action(ArgAction::SetTrue)
+                .overrides_with("pretty")
+                .help("Prints plain numbers for machine parsing"),
+        )
+        .arg(
+            Arg::new("no-cache")
+                .long("no-cache")
+                .action(ArgAction::SetTrue)
+                .help("Looks contract creation blocks up again instead of using the local cache"),
+        )
+        .arg(
+            Arg::new("max-retries")
+                .long("max-retries")
+                .num_args(1)
+                .value_name("N")
+                .value_parser(clap::value_parser!(u32))
+                .default_value("3")
+                .help("Retries Etherscan requests failing transiently N times, with backoff"),
+        )
+        .arg(
+            Arg::new("no-color")
+                .long("no-color")
+                .action(ArgAction::SetTrue)
+                .help("Disables colors in the run summary (also honors NO_COLOR)"),
+        )
+        .arg(
+            Arg::new("log-format")
+                .long("log-format")
+                .num_args(1)
+                .value_name("FORMAT")
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .help("Emits human-readable (text) or structured JSON (json) log lines"),
+        )
+        .subcommand(
+            Command::new("doctor")
+                .about("Checks environment variables, API keys, RPC connectivity and the ABI"),
+        )
+        .subcommand(
+            Command::new("analyze")
+                .about("Prints per-type, per-origin and per-day counts of an exported CSV")
+                .arg(
+                    Arg::new("input")
+                        .short('i')
+                        .long("input")
+                        .num_args(1)
+                        .value_name("FILE")
+                        .default_value(constants::OUTPUT_FILE_PATH)
+                        .help("The CSV file to analyze"),
+                ),
         )
+        .subcommand(Command::new("json-schema").about(
+            "Prints the JSON Schema of the events of the JSON formats, with the optional \
+             columns enabled by the other flags",
+        ))
         .get_matches();
 
     // Extract and return CLI arguments
     CliArgs {
+        command: match matches.subcommand() {
+            Some(("doctor", _)) => CliCommand::Doctor,
+            Some(("analyze", sub_matches)) => CliCommand::Analyze {
+                input: sub_matches.get_one::<String>("input").unwrap().clone(),
+            },
+            Some(("json-schema", _)) => CliCommand::JsonSchema,
+            _ if matches.get_flag("validate-abi") => CliCommand::ValidateAbi,
+            _ => CliCommand::Collect,
+        },
         network: matches.get_one::<String>("network").unwrap().clone(),
+        chain_id: matches.get_one::<u64>("chain-id").copied(),
+        chain_id_column: matches.get_flag("chain-id-column"),
         contract_address: matches.get_one::<String>("contract").unwrap().clone(),
+        contracts_file: matches.get_one::<String>("contracts-file").cloned(),
+        abi_path: matches.get_one::<String>("abi").cloned(),
+        resolve_proxy: matches.get_flag("resolve-proxy"),
         event_type: matches.get_one::<String>("event").unwrap().clone(),
+        topic0: matches
+            .get_many::<H256>("topic0")
+            .map(|signatures| signatures.copied().collect())
+            .unwrap_or_default(),
+        sampling: match (
+            matches.get_one::<f64>("sample-rate"),
+            matches.get_one::<u64>("stride"),
+        ) {
+            (Some(&rate), _) => Sampling::Rate(rate),
+            (None, Some(&stride)) => Sampling::Stride(stride),
+            (None, None) => Sampling::All,
+        },
+        seed: matches.get_one::<u64>("seed").copied(),
+        head: matches.get_one::<u64>("head").copied(),
+        tail: matches.get_one::<u64>("tail").copied(),
+        dedup_key: matches
+            .get_one::<String>("dedup-key")
+            .unwrap()
+            .parse()
+            .unwrap(),
+        one_per_tx: matches
+            .get_one::<String>("one-per-tx")
+            .map(|mode| mode.parse().unwrap()),
+        from_block: matches.get_one::<BlockNumber>("from-block").copied(),
+        to_block: matches.get_one::<BlockNumber>("to-block").copied(),
+        from_date: matches.get_one::<String>("from-date").cloned(),
+        to_date: matches.get_one::<String>("to-date").cloned(),
+        from_tx: matches.get_one::<H256>("from-tx").copied(),
+        contract_creation_block: matches.get_one::<u64>("contract-creation-block").copied(),
+        block_hash: matches.get_one::<H256>("block-hash").copied(),
+        rpc_urls: matches
+            .get_many::<String>("rpc-url")
+            .map(|urls| urls.cloned().collect())
+            .unwrap_or_default(),
+        rpc_headers: matches
+            .get_many::<RpcHeader>("rpc-header")
+            .map(|headers| headers.cloned().collect())
+            .unwrap_or_default(),
+        infura_key: matches.get_one::<String>("infura-key").cloned(),
+        etherscan_key: matches.get_one::<String>("etherscan-key").cloned(),
+        order: matches.get_one::<String>("order").unwrap().parse().unwrap(),
+        format: matches
+            .get_one::<String>("format")
+            .unwrap()
+            .parse()
+            .unwrap(),
+        array_streaming: matches.get_flag("array-streaming"),
+        compact_addresses: matches.get_flag("compact-addresses"),
+        order_by: matches.get_one::<OrderBy>("order-by").cloned(),
+        max_in_flight_bytes: matches.get_one::<u64>("max-in-flight-bytes").copied(),
+        partition: match matches.get_one::<u64>("partition-blocks") {
+            Some(&window_blocks) => Some(Partitioning::Blocks(window_blocks)),
+            None => matches
+                .contains_id("partition-by")
+                .then_some(Partitioning::Day),
+        },
+        postgres: matches.get_one::<String>("postgres").cloned(),
+        webhook: matches
+            .get_one::<String>("webhook")
+            .map(|url| WebhookConfig {
+                url: url.clone(),
+                batch_size: matches
+                    .get_one::<usize>("webhook-batch")
+                    .copied()
+                    .unwrap_or(constants::WEBHOOK_BATCH_SIZE),
+                flush_interval: Duration::from_secs(
+                    matches
+                        .get_one::<u64>("webhook-interval")
+                        .copied()
+                        .unwrap_or(constants::WEBHOOK_FLUSH_INTERVAL_SECS),
+                ),
+                retry: RetryPolicy {
+                    max_retries: matches
+                        .get_one::<u32>("webhook-retries")
+                        .copied()
+                        .unwrap_or(constants::DEFAULT_MAX_RETRIES),
+                    ..Default::default()
+                },
+            }),
+        csv_dialect: CsvDialect {
+            delimiter: matches.get_one::<u8>("delimiter").copied().unwrap_or(b','),
+            has_header: !matches.get_flag("no-header"),
+        },
+        chunk_size: matches.get_one::<u64>("chunk-size").copied(),
+        max_block_span: matches.get_one::<u64>("max-block-span").copied(),
+        delay_ms: matches.get_one::<u64>("delay-ms").copied(),
+        rps_limit: matches.get_one::<u32>("rps-limit").copied(),
+        confirmations: *matches.get_one::<u64>("confirmations").unwrap(),
+        enrich_concurrency: *matches.get_one::<usize>("enrich-concurrency").unwrap(),
+        output_dir: matches.get_one::<String>("output-dir").cloned(),
+        retry_failed: matches.get_one::<String>("retry-failed").cloned(),
+        ranges_file: matches.get_one::<String>("ranges-file").cloned(),
+        catch_up_then_follow: matches.get_flag("catch-up-then-follow"),
+        lookback_overlap: matches
+            .get_one::<u64>("lookback-overlap")
+            .copied()
+            .unwrap_or(0),
+        checkpoint: matches.get_one::<String>("checkpoint").cloned(),
+        resume_from_checkpoint: matches.get_flag("resume-from-checkpoint"),
+        raw: matches.get_flag("raw"),
+        flatten_args: matches.get_flag("flatten-args"),
+        indexed_addresses: matches.get_flag("indexed-addresses"),
+        include_internal: matches.get_flag("include-internal"),
+        no_enrich: matches.get_flag("no-enrich"),
+        min_value: matches.get_one::<U256>("min-value").copied(),
+        max_value: matches.get_one::<U256>("max-value").copied(),
+        gas_price: matches.get_flag("gas-price"),
+        with_receipts: matches.get_flag("with-receipts"),
+        calldata: matches.get_flag("with-calldata").then(|| {
+            matches
+                .get_one::<String>("calldata")
+                .map(|mode| mode.parse().unwrap())
+                .unwrap_or_default()
+        }),
+        aggregate_by_block: matches.get_flag("aggregate-by-block"),
+        strict: matches.get_flag("strict"),
+        force: matches.get_flag("force"),
+        fsync: matches.get_flag("fsync"),
+        timing: matches.get_flag("timing"),
+        dump_filter: matches.get_flag("dump-filter"),
+        metrics_port: matches.get_one::<u16>("metrics-port").copied(),
+        upload: matches.get_one::<UploadTarget>("upload").cloned(),
+        s3_endpoint: matches.get_one::<String>("s3-endpoint").cloned(),
+        print_config: matches.get_flag("print-config"),
+        pretty: !matches.get_flag("no-pretty"),
+        no_color: matches.get_flag("no-color"),
+        no_cache: matches.get_flag("no-cache"),
+        max_retries: *matches.get_one::<u32>("max-retries").unwrap(),
+        log_format: matches
+            .get_one::<String>("log-format")
+            .unwrap()
+            .parse()
+            .unwrap(),
     }
 }
@@ -0,0 +1,343 @@
+use csv::StringRecord;
+use serde_json::Value;
+
+use crate::constants;
+use crate::error::CollectorError;
+use crate::utils::OrderEvent;
+
+/// A column of the CSV output, declared in output order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Column {
+    TxOrigin,
+    EventType,
+    TxnHash,
+    Timestamp,
+    /// Number of the block the event was emitted in.
+    BlockNumber,
+    /// Index of the log within its block.
+    LogIndex,
+    /// Hex-encoded `log.data`.
+    LogData,
+    /// Hex-encoded log topics joined with `;`.
+    LogTopics,
+    /// Immediate caller of the contract, from the transaction traces.
+    DirectCaller,
+    /// Label of the contract the event came from.
+    Label,
+    /// Chain ID of the network the event came from.
+    ChainId,
+    /// Gas price paid by the transaction, in wei.
+    GasPrice,
+    /// Effective gas price from the transaction receipt, in wei.
+    EffectiveGasPrice,
+    /// Hex-encoded transaction input, or its 4-byte selector.
+    Input,
+}
+
+impl Column {
+    /// Every column, in output order.
+    pub const ALL: [Column; 14] = [
+        Column::TxOrigin,
+        Column::EventType,
+        Column::TxnHash,
+        Column::Timestamp,
+        Column::BlockNumber,
+        Column::LogIndex,
+        Column::LogData,
+        Column::LogTopics,
+        Column::DirectCaller,
+        Column::Label,
+        Column::ChainId,
+        Column::GasPrice,
+        Column::EffectiveGasPrice,
+        Column::Input,
+    ];
+
+    /// Returns the header name of the column.
+    pub fn name(self) -> &'static str {
+        match self {
+            Column::TxOrigin => constants::CSV_HEADER[0],
+            Column::EventType => constants::CSV_HEADER[1],
+            Column::TxnHash => constants::CSV_HEADER[2],
+            Column::Timestamp => constants::CSV_HEADER[3],
+            Column::BlockNumber => constants::LOG_CSV_HEADER[0],
+            Column::LogIndex => constants::LOG_CSV_HEADER[1],
+            Column::LogData => constants::RAW_CSV_HEADER[0],
+            Column::LogTopics => constants::RAW_CSV_HEADER[1],
+            Column::DirectCaller => constants::DIRECT_CALLER_CSV_HEADER,
+            Column::Label => constants::LABEL_CSV_HEADER,
+            Column::ChainId => constants::CHAIN_ID_CSV_HEADER,
+            Column::GasPrice => constants::GAS_PRICE_CSV_HEADER[0],
+            Column::EffectiveGasPrice => constants::GAS_PRICE_CSV_HEADER[1],
+            Column::Input => constants::INPUT_CSV_HEADER,
+        }
+    }
+
+    /// Returns the field of the JSON formats holding the column, for the optional
+    /// columns; the others are always written.
+    fn json_field(self) -> Option<&'static str> {
+        match self {
+            Column::TxOrigin
+            | Column::EventType
+            | Column::TxnHash
+            | Column::Timestamp
+            | Column::BlockNumber
+            | Column::LogIndex => None,
+            Column::LogData => Some("raw_data"),
+            Column::LogTopics => Some("raw_topics"),
+            Column::DirectCaller => Some("direct_caller"),
+            Column::Label => Some("label"),
+            Column::ChainId => Some("chain_id"),
+            Column::GasPrice => Some("gas_price"),
+            Column::EffectiveGasPrice => Some("effective_gas_price"),
+            Column::Input => Some("input"),
+        }
+    }
+
+    /// Returns the value of the column for `event`; absent optional fields are empty.
+    pub fn value(self, event: &OrderEvent) -> String {
+        match self {
+            Column::TxOrigin => format!("{:?}", event.tx_origin),
+            Column::EventType => event.event_type.clone(),
+            Column::TxnHash => format!("{:?}", event.txn_hash),
+            Column::Timestamp => event.timestamp.to_string(),
+            Column::BlockNumber => event.block_number.to_string(),
+            Column::LogIndex => event.log_index.to_string(),
+            Column::LogData => event.raw_data.clone().unwrap_or_default(),
+            Column::LogTopics => event.raw_topics.clone().unwrap_or_default(),
+            Column::DirectCaller => event.direct_caller.clone().unwrap_or_default(),
+            Column::Label => event.label.clone().unwrap_or_default(),
+            Column::ChainId => event.chain_id.map(|id| id.to_string()).unwrap_or_default(),
+            Column::GasPrice => event.gas_price.map(|p| p.to_string()).unwrap_or_default(),
+            Column::EffectiveGasPrice => event
+                .effective_gas_price
+                .map(|p| p.to_string())
+                .unwrap_or_default(),
+            Column::Input => event.input.clone().unwrap_or_default(),
+        }
+    }
+}
+
+/// Returns the indexed address `name` of `event` as a cell, empty for events
+/// without it.
+pub fn topic_value(event: &OrderEvent, name: &str) -> String {
+    event
+        .indexed_addresses
+        .iter()
+        .find(|(param, _)| param == name)
+        .map(|(_, address)| format!("{:?}", address))
+        .unwrap_or_default()
+}
+
+/// Returns the value of the decoded event argument `name` of `event` as a cell.
+///
+/// Strings are written as is and other values as JSON; arguments the event does
+/// not have, e.g. those of another event type, are empty.
+pub fn arg_value(event: &OrderEvent, name: &str) -> String {
+    match event.args.as_ref().and_then(|args| args.get(name)) {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(value)) => value.clone(),
+        Some(value) => value.to_string(),
+    }
+}
+
+/// The enabled columns of a CSV output, from which both its header and its rows
+/// are derived so that they always match.
+///
+/// The four order event columns are enabled by default, and the event type and
+/// transaction hash always are; columns are kept in the order of [`Column::ALL`]
+/// whatever order they are inserted in. With
+/// `--indexed-addresses`, one column per indexed address parameter follows them,
+/// named with [`constants::TOPIC_COLUMN_PREFIX`], and with `--flatten-args`, one
+/// column per event parameter comes last, named with
+/// [`constants::ARG_COLUMN_PREFIX`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnSet {
+    columns: Vec<Column>,
+    /// Header names of the indexed address columns, in output order.
+    topics: Vec<String>,
+    /// Header names of the event argument columns, in output order.
+    args: Vec<String>,
+}
+
+impl Default for ColumnSet {
+    fn default() -> Self {
+        ColumnSet {
+            columns: Column::ALL[..constants::CSV_HEADER.len()].to_vec(),
+            topics: Vec::new(),
+            args: Vec::new(),
+        }
+    }
+}
+
+impl ColumnSet {
+    /// Enables `column`, keeping the columns in output order.
+    pub fn insert(&mut self, column: Column) {
+        if let Err(idx) = self.columns.binary_search(&column) {
+            self.columns.insert(idx, column);
+        }
+    }
+
+    /// Enables `column` when `enabled` is set.
+    pub fn with(mut self, column: Column, enabled: bool) -> Self {
+        if enabled {
+            self.insert(column);
+        }
+        self
+    }
+
+    /// Disables `column` when `disabled` is set. The event type and transaction
+    /// hash, which identify each row, stay enabled.
+    pub fn without(mut self, column: Column, disabled: bool) -> Self {
+        if disabled && !matches!(column, Column::EventType | Column::TxnHash) {
+            self.columns.retain(|&enabled| enabled != column);
+        }
+        self
+    }
+
+    /// Adds a column for each indexed address parameter of `names`, before the
+    /// event argument columns.
+    pub fn with_topics<'a>(mut self, names: impl IntoIterator<Item = &'a str>) -> Self {
+        for name in names {
+            let header = format!("{}{}", constants::TOPIC_COLUMN_PREFIX, name);
+            if !self.topics.contains(&header) {
+                self.topics.push(header);
+            }
+        }
+        self
+    }
+
+    /// Adds a column for each event parameter of `names`, after the other columns.
+    pub fn with_args<'a>(mut self, names: impl IntoIterator<Item = &'a str>) -> Self {
+        for name in names {
+            let header = format!("{}{}", constants::ARG_COLUMN_PREFIX, name);
+            if !self.args.contains(&header) {
+                self.args.push(header);
+            }
+        }
+        self
+    }
+
+    /// Returns whether `column` is enabled.
+    pub fn contains(&self, column: Column) -> bool {
+        self.columns.contains(&column)
+    }
+
+    /// Returns the enabled columns, in output order.
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// Returns the header row.
+    pub fn header(&self) -> Vec<&str> {
+        self.columns
+            .iter()
+            .map(|column| column.name())
+            .chain(self.topics.iter().map(String::as_str))
+            .chain(self.args.iter().map(String::as_str))
+            .collect()
+    }
+
+    /// Serializes `event` into a row matching [`Self::header`].
+    pub fn record(&self, event: &OrderEvent) -> Vec<String> {
+        let topics = self
+            .topics
+            .iter()
+            .map(|header| topic_value(event, &header[constants::TOPIC_COLUMN_PREFIX.len()..]));
+        let args = self
+            .args
+            .iter()
+            .map(|header| arg_value(event, &header[constants::ARG_COLUMN_PREFIX.len()..]));
+        self.columns
+            .iter()
+            .map(|column| column.value(event))
+            .chain(topics)
+            .chain(args)
+            .collect()
+    }
+
+    /// Recovers the column set of an existing output from its header row.
+    pub fn from_header(header: &StringRecord) -> Result<Self, CollectorError> {
+        let mut columns = Vec::with_capacity(header.len());
+        let mut topics = Vec::new();
+        let mut args = Vec::new();
+        for name in header {
+            // Argument columns come last, so any column after one must be another
+            if let Some(param) = name.strip_prefix(constants::ARG_COLUMN_PREFIX) {
+                args.push(param);
+                continue;
+            }
+            // Indexed address columns come next to last, before any argument column
+            if let Some(param) = name.strip_prefix(constants::TOPIC_COLUMN_PREFIX) {
+                if args.is_empty() {
+                    topics.push(param);
+                    continue;
+                }
+            }
+            if !args.is_empty() || !topics.is_empty() {
+                return Err(CollectorError::InvalidArgs(format!(
+                    "Unexpected CSV header: {}",
+                    header.iter().collect::<Vec<_>>().join(",")
+                )));
+            }
+            let column = Column::ALL
+                .into_iter()
+                .find(|column| column.name() == name)
+                .ok_or_else(|| {
+                    CollectorError::InvalidArgs(format!("Unknown CSV column: {}", name))
+                })?;
+            columns.push(column);
+        }
+
+        // The header must hold the identifying columns and the others in output order
+        let mut set = ColumnSet {
+            columns: Vec::new(),
+            topics: Vec::new(),
+            args: Vec::new(),
+        };
+        for &column in &columns {
+            set.insert(column);
+        }
+        if set.columns != columns
+            || !set.contains(Column::EventType)
+            || !set.contains(Column::TxnHash)
+        {
+            return Err(CollectorError::InvalidArgs(format!(
+                "Unexpected CSV header: {}",
+                header.iter().collect::<Vec<_>>().join(",")
+            )));
+        }
+        Ok(set.with_topics(topics).with_args(args))
+    }
+}
+
+/// Returns the fields of the events written by the JSON formats with the optional
+/// columns of `columns`, as listed by [`json_schema`].
+pub fn json_fields(columns: &ColumnSet) -> Vec<String> {
+    match json_schema(columns).get("properties") {
+        Some(Value::Object(properties)) => properties.keys().cloned().collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Returns the JSON Schema of the events written by the JSON formats with the
+/// optional columns of `columns`, generated from [`OrderEvent`].
+///
+/// The fields of disabled optional columns are left out and no other field is
+/// allowed, so consumers can validate the output and generate code from it. The
+/// indexed addresses and event arguments are held in `args` rather than in fields.
+pub fn json_schema(columns: &ColumnSet) -> Value {
+    let mut schema = serde_json::to_value(schemars::schema_for!(OrderEvent))
+        .expect("the OrderEvent JSON schema serializes");
+    if let Some(Value::Object(properties)) = schema.get_mut("properties") {
+        for column in Column::ALL {
+            if let Some(field) = column.json_field() {
+                if !columns.contains(column) {
+                    properties.remove(field);
+                }
+            }
+        }
+    }
+    schema["additionalProperties"] = Value::Bool(false);
+    schema
+}
@@ -2,9 +2,36 @@ pub const DEFAULT_CONTRACT_ADDRESS: &str = "0x0ea6d458488d1cf51695e1d6e4744e6fb7
 pub const TAKEORDER_EVENT_NAME: &str = "TakeOrderV2";
 pub const CLEAR_EVENT_NAME: &str = "ClearV2";
 pub const DEFAULT: &str = "DEFAULT";
+pub const ALL_EVENTS: &str = "all";
+pub const COLLECTED_EVENTS: [&str; 2] = [TAKEORDER_EVENT_NAME, CLEAR_EVENT_NAME];
 pub const ABI_FILE_PATH: &str = "./IOrderBookV4.json";
 pub const OUTPUT_FILE_PATH: &str = "order_events.csv";
+pub const CREATION_BLOCK_CACHE_PATH: &str = ".creation_blocks.json";
+pub const PROVIDER_COOLDOWN_CHUNKS: u64 = 10;
+pub const EMPTY_RANGE_WARNING_BLOCKS: u64 = 100_000;
+pub const TABLE_MAX_ROWS: usize = 100;
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+pub const RETRY_BASE_DELAY_MS: u64 = 1_000;
+pub const RETRY_MAX_DELAY_MS: u64 = 30_000;
+pub const POSTGRES_TABLE: &str = "order_events";
+pub const WEBHOOK_BATCH_SIZE: usize = 100;
+pub const WEBHOOK_FLUSH_INTERVAL_SECS: u64 = 5;
 pub const CSV_HEADER: [&str; 4] = ["tx.origin", "event type", "txn hash", "timestamp"];
+pub const LOG_CSV_HEADER: [&str; 2] = ["block_number", "log_index"];
+pub const RAW_CSV_HEADER: [&str; 2] = ["log data", "log topics"];
+pub const DIRECT_CALLER_CSV_HEADER: &str = "direct_caller";
+pub const LABEL_CSV_HEADER: &str = "label";
+pub const ARG_COLUMN_PREFIX: &str = "arg_";
+pub const TOPIC_COLUMN_PREFIX: &str = "topic_";
+pub const CHAIN_ID_CSV_HEADER: &str = "chain_id";
+pub const GAS_PRICE_CSV_HEADER: [&str; 2] = ["gas_price", "effective_gas_price"];
+pub const INPUT_CSV_HEADER: &str = "input";
+pub const AGGREGATE_CSV_HEADER: [&str; 4] = [
+    "block_number",
+    "timestamp",
+    "take_order_count",
+    "clear_count",
+];
 
 pub const ETHERSCAN_BASIC_URL: &str = "https://api.etherscan.io";
 
@@ -13,5 +40,67 @@ pub const BASE_WS_RPC_BASE_URL: &str = "wss://base-mainnet.infura.io/ws/v3/";
 pub const ARBITRUM_WS_RPC_BASE_URL: &str = "wss://arbitrum-mainnet.infura.io/ws/v3/";
 pub const OPTIMISM_WS_RPC_BASE_URL: &str = "wss://optimism-mainnet.infura.io/ws/v3/";
 pub const LINEA_WS_RPC_BASE_URL: &str = "wss://linea-mainnet.infura.io/ws/v3/";
+pub const SUPPORTED_NETWORKS: [&str; 5] = ["Mainnet", "Base", "Arbitrum", "Optimism", "Linear"];
+
+/// Chain ID served by the RPC endpoints of `network`, if it is a known network.
+pub fn expected_chain_id(network: &str) -> Option<u64> {
+    match network {
+        "Mainnet" => Some(1),
+        "Base" => Some(8453),
+        "Arbitrum" => Some(42161),
+        "Optimism" => Some(10),
+        "Linear" => Some(59144),
+        _ => None,
+    }
+}
+
+/// Default number of blocks per `get_logs` call on `network`. L2 blocks come
+/// faster, so a window of the same duration spans many more of them.
+pub fn default_chunk_size(network: &str) -> u64 {
+    match network {
+        "Arbitrum" => 100_000,
+        "Base" | "Optimism" | "Linear" => 250_000,
+        _ => 1_000_000,
+    }
+}
+
+/// Default pause between chunks on `network`, in milliseconds. The smaller L2
+/// chunks need more requests, each paused for less time.
+pub fn default_delay_ms(network: &str) -> u64 {
+    match network {
+        "Arbitrum" | "Base" | "Optimism" | "Linear" => 200,
+        _ => 500,
+    }
+}
+
+/// Default cap on the RPC requests per second on `network`. The L2 networks are
+/// mostly served by the same providers with higher limits.
+pub fn default_rps_limit(network: &str) -> u32 {
+    match network {
+        "Arbitrum" | "Base" | "Optimism" | "Linear" => 50,
+        _ => 25,
+    }
+}
+
+/// Networks whose default endpoints reject `get_logs` calls spanning more blocks
+/// than a documented cap, whatever the number of logs returned. The Infura
+/// endpoints of the supported networks limit the number of results and the run
+/// time of a call, not its span, so none is listed; `--max-block-span` sets the
+/// cap of providers enforcing one.
+const MAX_BLOCK_SPANS: &[(&str, u64)] = &[];
+
+/// Maximum number of blocks a single `get_logs` call may span on `network`, if
+/// its endpoints cap it.
+pub fn max_block_span(network: &str) -> Option<u64> {
+    MAX_BLOCK_SPANS
+        .iter()
+        .find(|(name, _)| *name == network)
+        .map(|&(_, span)| span)
+}
+
+/// Environment variables holding the API keys, overridden by `--infura-key` and
+/// `--etherscan-key`.
+pub const INFURA_API_KEY_VAR: &str = "INFURA_API_KEY";
+pub const ETHERSCAN_API_KEY_VAR: &str = "ETHERSCAN_API_KEY";
 
 pub const TEST_INFURA_API_KEY: &str = "afee43fb439a4e1794d9acad3e4a95b8";
@@ -0,0 +1,79 @@
+use std::fs;
+
+use ethers::types::H160;
+
+use crate::error::CollectorError;
+
+/// A contract to collect events from, with an optional label tagging its rows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractEntry {
+    /// Contract address in `0x`-prefixed lowercase hex.
+    pub address: String,
+    /// Label written to the `label` column of every row of this contract.
+    pub label: Option<String>,
+}
+
+/// Parses a contracts list of `address,label` lines.
+///
+/// Blank lines, `#` comments and an optional `address,label` header on the first
+/// other line are skipped.
+/// A missing label defaults to the address itself. Every malformed line is
+/// reported with its line number in a single error.
+pub fn parse_contracts(content: &str) -> Result<Vec<ContractEntry>, CollectorError> {
+    let mut entries = Vec::new();
+    let mut malformed = Vec::new();
+    let mut first_entry = true;
+
+    for (idx, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        // The header may follow comments and blank lines, but no entry
+        if std::mem::take(&mut first_entry) && line.eq_ignore_ascii_case("address,label") {
+            continue;
+        }
+
+        let (address, label) = match line.split_once(',') {
+            Some((address, label)) => (address.trim(), label.trim()),
+            None => (line, ""),
+        };
+
+        match address.parse::<H160>() {
+            Ok(parsed) => {
+                let address = format!("{:?}", parsed);
+                let label = if label.is_empty() {
+                    address.clone()
+                } else {
+                    label.to_string()
+                };
+                entries.push(ContractEntry {
+                    address,
+                    label: Some(label),
+                });
+            }
+            Err(_) => malformed.push(format!("line {}: invalid address '{}'", idx + 1, address)),
+        }
+    }
+
+    if !malformed.is_empty() {
+        return Err(CollectorError::InvalidArgs(format!(
+            "Malformed contracts file entries: {}",
+            malformed.join("; ")
+        )));
+    }
+    if entries.is_empty() {
+        return Err(CollectorError::InvalidArgs(
+            "Contracts file does not list any contract".to_string(),
+        ));
+    }
+    Ok(entries)
+}
+
+/// Reads and parses the contracts list at `path`, see [`parse_contracts`].
+pub fn load_contracts_file(path: &str) -> Result<Vec<ContractEntry>, CollectorError> {
+    let content = fs::read_to_string(path).map_err(|e| {
+        CollectorError::InvalidArgs(format!("Failed to read contracts file {}: {}", path, e))
+    })?;
+    parse_contracts(&content)
+}
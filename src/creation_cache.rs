@@ -0,0 +1,47 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+
+/// Cached creation blocks, keyed by `<network>:<lowercase address>`.
+type CreationBlocks = BTreeMap<String, u64>;
+
+fn cache_key(network: &str, address: &str) -> String {
+    format!("{}:{}", network, address.to_ascii_lowercase())
+}
+
+/// Reads the cache at `path`; a missing file is an empty cache.
+fn read_cache(path: &str) -> Result<CreationBlocks, Box<dyn Error>> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid creation block cache {}: {}", path, e))?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(CreationBlocks::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Returns the cached creation block of `address` on `network`, if any.
+pub fn creation_block_cache_get(
+    path: &str,
+    network: &str,
+    address: &str,
+) -> Result<Option<u64>, Box<dyn Error>> {
+    Ok(read_cache(path)?.get(&cache_key(network, address)).copied())
+}
+
+/// Records the creation block of `address` on `network` in the cache at `path`,
+/// creating the file if needed.
+pub fn creation_block_cache_put(
+    path: &str,
+    network: &str,
+    address: &str,
+    block: u64,
+) -> Result<(), Box<dyn Error>> {
+    let mut cache = read_cache(path)?;
+    cache.insert(cache_key(network, address), block);
+
+    // Replace the file atomically so an interrupted write cannot corrupt it
+    let tmp = format!("{}.tmp", path);
+    fs::write(&tmp, serde_json::to_string_pretty(&cache)?)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
@@ -2,45 +2,228 @@ use std::error::Error;
 use std::fs::File;
 
 use crate::utils::OrderEvent;
-use csv::{ReaderBuilder, Writer};
+use csv::{ByteRecord, ReaderBuilder, Writer, WriterBuilder};
 
+use crate::columns::ColumnSet;
 use crate::constants;
+use crate::error::CollectorError;
+use crate::sink::EventSink;
 
-/// Initializes a CSV file with headers
-pub fn initialize_csv(filename: &str) -> Result<(), Box<dyn Error>> {
-    let mut writer = Writer::from_writer(
-        File::options()
-            .write(true) // Open file for writing
-            .create(true) // Create the file if it doesn't exist
-            .truncate(true) // Truncate the file to zero length if it exists
-            .open(filename)?, // Open the file
-    );
+/// Field delimiter and header presence of a CSV file, shared by its writers and
+/// readers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CsvDialect {
+    /// Byte separating the fields of a row.
+    pub delimiter: u8,
+    /// Whether the first row names the columns.
+    pub has_header: bool,
+}
+
+impl Default for CsvDialect {
+    fn default() -> Self {
+        CsvDialect {
+            delimiter: b',',
+            has_header: true,
+        }
+    }
+}
 
-    // Write headers
-    writer.write_record(constants::CSV_HEADER)?;
-    writer.flush()?;
+impl CsvDialect {
+    fn writer(&self, file: File) -> Writer<File> {
+        WriterBuilder::new()
+            .delimiter(self.delimiter)
+            .has_headers(false)
+            .from_writer(file)
+    }
 
-    Ok(())
+    pub(crate) fn reader(&self) -> ReaderBuilder {
+        let mut builder = ReaderBuilder::new();
+        builder
+            .delimiter(self.delimiter)
+            .has_headers(self.has_header);
+        builder
+    }
+}
+
+/// Parses a `--delimiter` value: a single ASCII character, or `\t` / `tab` for
+/// tab-separated output.
+pub fn parse_delimiter(s: &str) -> Result<u8, String> {
+    let delimiter = match s {
+        "\\t" | "tab" => b'\t',
+        _ if s.len() == 1 && s.is_ascii() => s.as_bytes()[0],
+        _ => return Err(format!("Delimiter must be a single ASCII character: {}", s)),
+    };
+    if matches!(delimiter, b'"' | b'\n' | b'\r') {
+        return Err(format!("Unsupported delimiter: {:?}", delimiter as char));
+    }
+    Ok(delimiter)
 }
 
-/// Writes order events to a CSV file.
-pub fn write_to_csv(filename: &str, events: &[OrderEvent]) -> Result<(), Box<dyn Error>> {
-    let mut writer = Writer::from_writer(File::options().append(true).open(filename)?);
+/// Long-lived CSV output that keeps its file handle open across chunks.
+pub struct CsvSink {
+    writer: Writer<File>,
+    columns: ColumnSet,
+    fsync: bool,
+}
+
+impl CsvSink {
+    /// Creates (or truncates) `filename` and writes the header of `columns`, unless
+    /// the dialect has none.
+    pub fn create(
+        filename: &str,
+        columns: ColumnSet,
+        dialect: CsvDialect,
+        fsync: bool,
+    ) -> Result<Self, Box<dyn Error>> {
+        let writer = dialect.writer(
+            File::options()
+                .write(true) // Open file for writing
+                .create(true) // Create the file if it doesn't exist
+                .truncate(true) // Truncate the file to zero length if it exists
+                .open(filename)?, // Open the file
+        );
+        let mut sink = CsvSink {
+            writer,
+            columns,
+            fsync,
+        };
+
+        // Write headers
+        if dialect.has_header {
+            sink.writer.write_record(sink.columns.header())?;
+        }
+        sink.sync()?;
+
+        Ok(sink)
+    }
+
+    /// Opens an existing `filename` for appending rows after its current content,
+    /// with the columns named by its header.
+    pub fn append(
+        filename: &str,
+        dialect: CsvDialect,
+        fsync: bool,
+    ) -> Result<Self, Box<dyn Error>> {
+        if !dialect.has_header {
+            return Err(CollectorError::InvalidArgs(format!(
+                "{} has no header to read its columns from",
+                filename
+            ))
+            .into());
+        }
+        let columns = ColumnSet::from_header(dialect.reader().from_path(filename)?.headers()?)?;
+        CsvSink::append_with_columns(filename, columns, dialect, fsync)
+    }
+
+    /// Opens an existing `filename` for appending rows of `columns` after its
+    /// current content, without reading its header; for headerless files.
+    pub fn append_with_columns(
+        filename: &str,
+        columns: ColumnSet,
+        dialect: CsvDialect,
+        fsync: bool,
+    ) -> Result<Self, Box<dyn Error>> {
+        let writer = dialect.writer(File::options().append(true).open(filename)?);
+        Ok(CsvSink {
+            writer,
+            columns,
+            fsync,
+        })
+    }
+
+    /// Returns the columns written for each event.
+    pub fn columns(&self) -> &ColumnSet {
+        &self.columns
+    }
 
-    for event in events {
-        writer.write_record(&[
-            format!("{:?}", event.tx_origin),
-            event.event_type.clone(),
-            format!("{:?}", event.txn_hash),
-            event.timestamp.to_string(),
-        ])?;
+    /// Writes order events as rows and flushes them to the file.
+    pub fn write_events(&mut self, events: &[OrderEvent]) -> Result<(), Box<dyn Error>> {
+        for event in events {
+            self.writer.write_record(self.columns.record(event))?;
+        }
+        self.sync()
     }
 
-    writer.flush()?;
+    /// Flushes buffered rows and, when fsync is enabled, forces them to disk so a
+    /// crash or power loss cannot drop a chunk that was reported as written.
+    fn sync(&mut self) -> Result<(), Box<dyn Error>> {
+        self.writer.flush()?;
+        if self.fsync {
+            self.writer.get_ref().sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+impl EventSink for CsvSink {
+    fn write_events(&mut self, events: &[OrderEvent]) -> Result<(), Box<dyn Error>> {
+        CsvSink::write_events(self, events)
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), Box<dyn Error>> {
+        self.sync()
+    }
+}
+
+/// Initializes a CSV file with the header of `columns`.
+pub fn initialize_csv(
+    filename: &str,
+    columns: &ColumnSet,
+    dialect: CsvDialect,
+) -> Result<(), Box<dyn Error>> {
+    CsvSink::create(filename, columns.clone(), dialect, false)?;
     Ok(())
 }
 
-pub fn verify_csv(filename: &str, expected_row_count: usize) -> bool {
+/// Writes order events to a CSV file with a header.
+pub fn write_to_csv(
+    filename: &str,
+    dialect: CsvDialect,
+    events: &[OrderEvent],
+) -> Result<(), Box<dyn Error>> {
+    CsvSink::append(filename, dialect, false)?.write_events(events)
+}
+
+/// Truncates an incomplete final row left behind by an interrupted write and
+/// returns the number of bytes removed.
+///
+/// The final row is incomplete when it lacks its line terminator or when its
+/// number of fields differs from the header's, or from the first row's in a
+/// headerless file.
+pub fn repair_csv(filename: &str, dialect: CsvDialect) -> Result<u64, Box<dyn Error>> {
+    let content = std::fs::read(filename)?;
+    let mut reader = dialect
+        .reader()
+        .flexible(true)
+        .from_reader(content.as_slice());
+    let mut columns = dialect
+        .has_header
+        .then(|| reader.headers().map(|header| header.len()))
+        .transpose()?;
+
+    // Find the start offset and field count of the final row
+    let mut last_row = None;
+    let mut record = ByteRecord::new();
+    while reader.read_byte_record(&mut record)? {
+        if let Some(position) = record.position() {
+            last_row = Some((position.byte(), record.len()));
+        }
+        columns.get_or_insert(record.len());
+    }
+    let columns = columns.unwrap_or_default();
+
+    let Some((start, fields)) = last_row else {
+        return Ok(0);
+    };
+    if fields == columns && content.ends_with(b"\n") {
+        return Ok(0);
+    }
+
+    File::options().write(true).open(filename)?.set_len(start)?;
+    Ok(content.len() as u64 - start)
+}
+
+pub fn verify_csv(filename: &str, dialect: CsvDialect, expected_row_count: usize) -> bool {
     // Open the CSV file
     let file = match File::open(filename) {
         Ok(file) => file,
@@ -48,20 +231,22 @@ pub fn verify_csv(filename: &str, expected_row_count: usize) -> bool {
     };
 
     // Create a CSV reader
-    let mut rdr = ReaderBuilder::new().has_headers(true).from_reader(file);
+    let mut rdr = dialect.reader().from_reader(file);
 
-    // Read the header
-    let headers = match rdr.headers() {
-        Ok(headers) => headers,
-        Err(_) => return false,
-    };
+    if dialect.has_header {
+        // Read the header
+        let headers = match rdr.headers() {
+            Ok(headers) => headers,
+            Err(_) => return false,
+        };
 
-    // Convert CSV_HEADER to a comma-separated string and compare with the file header
-    let expected_header = constants::CSV_HEADER.join(",");
-    let file_header = headers.iter().collect::<Vec<&str>>().join(",");
+        // Convert CSV_HEADER to a comma-separated string and compare with the file header
+        let expected_header = constants::CSV_HEADER.join(",");
+        let file_header = headers.iter().collect::<Vec<&str>>().join(",");
 
-    if file_header != expected_header {
-        return false;
+        if file_header != expected_header {
+            return false;
+        }
     }
 
     // Count the number of non-empty data rows using an iterator chain
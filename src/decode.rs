@@ -0,0 +1,112 @@
+use ethers::abi::{Event, ParamType, RawLog, Token};
+use ethers::types::{Log, H160, I256};
+use ethers::utils::to_checksum;
+use serde_json::{Map, Value};
+
+/// Converts a decoded ABI token into JSON that JavaScript and Python consume as is.
+///
+/// Integers become decimal strings, since they may not fit a double; addresses
+/// are checksummed; bytes are `0x`-prefixed hex; arrays and tuples are arrays.
+pub fn token_to_json(token: &Token) -> Value {
+    match token {
+        Token::Address(address) => Value::String(to_checksum(address, None)),
+        Token::Uint(value) => Value::String(value.to_string()),
+        Token::Int(value) => Value::String(I256::from_raw(*value).to_string()),
+        Token::Bool(value) => Value::Bool(*value),
+        Token::String(value) => Value::String(value.clone()),
+        Token::Bytes(bytes) | Token::FixedBytes(bytes) => {
+            Value::String(format!("0x{}", hex::encode(bytes)))
+        }
+        Token::Array(tokens) | Token::FixedArray(tokens) | Token::Tuple(tokens) => {
+            Value::Array(tokens.iter().map(token_to_json).collect())
+        }
+    }
+}
+
+/// Decodes the parameters of `log` as an instance of `event` into a JSON object
+/// keyed by parameter name.
+pub fn decode_log_args(event: &Event, log: &Log) -> Result<Value, ethers::abi::Error> {
+    let parsed = event.parse_log(RawLog {
+        topics: log.topics.clone(),
+        data: log.data.to_vec(),
+    })?;
+
+    Ok(Value::Object(
+        parsed
+            .params
+            .into_iter()
+            .map(|param| (param.name, token_to_json(&param.value)))
+            .collect::<Map<String, Value>>(),
+    ))
+}
+
+/// Returns the parameter names of `events`, each once, in the order they are
+/// first declared, e.g. to give mixed event types a common set of columns.
+///
+/// Unnamed parameters are left out, as they cannot be told apart by name.
+pub fn param_names(events: &[&Event]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for input in events.iter().flat_map(|event| &event.inputs) {
+        if !input.name.is_empty() && !names.contains(&input.name) {
+            names.push(input.name.clone());
+        }
+    }
+    names
+}
+
+/// Returns the indexed address parameters of `event` with the position of their
+/// topic in its logs, after the signature topic unless the event is anonymous.
+///
+/// Parameters are named as in the ABI, or `addr_<position>` when unnamed.
+pub fn indexed_address_params(event: &Event) -> Vec<(String, usize)> {
+    let first = if event.anonymous { 0 } else { 1 };
+    event
+        .inputs
+        .iter()
+        .filter(|input| input.indexed)
+        .enumerate()
+        .filter(|(_, input)| input.kind == ParamType::Address)
+        .map(|(idx, input)| {
+            let position = first + idx;
+            let name = if input.name.is_empty() {
+                format!("addr_{}", position)
+            } else {
+                input.name.clone()
+            };
+            (name, position)
+        })
+        .collect()
+}
+
+/// Returns the names of the indexed address parameters of `events`, each once, in
+/// the order they are first declared.
+pub fn indexed_address_names(events: &[&Event]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for (name, _) in events
+        .iter()
+        .flat_map(|event| indexed_address_params(event))
+    {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// Decodes the indexed address parameters of `log` as an instance of `event` from
+/// the topics they are mapped to, keyed by the names of [`indexed_address_params`].
+pub fn decode_indexed_addresses(event: &Event, log: &Log) -> Result<Vec<(String, H160)>, String> {
+    indexed_address_params(event)
+        .into_iter()
+        .map(|(name, position)| match log.topics.get(position) {
+            // Addresses are left-padded to 32 bytes in their topic
+            Some(topic) => Ok((name, H160::from(*topic))),
+            None => Err(format!(
+                "{} is indexed in topic {}, but the log has {} topics",
+                name,
+                position,
+                log.topics.len()
+            )),
+        })
+        .collect()
+}
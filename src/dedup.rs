@@ -0,0 +1,172 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::str::FromStr;
+
+use ethers::types::H256;
+
+use crate::utils::OrderEvent;
+
+/// Fields identifying duplicate events, of which only the first is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DedupKey {
+    /// Every event is kept.
+    #[default]
+    None,
+    /// At most one event per transaction.
+    Tx,
+    /// At most one event per log, identified by its transaction and log index.
+    TxLog,
+    /// At most one event of each type per transaction.
+    TxEvent,
+}
+
+impl FromStr for DedupKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(DedupKey::None),
+            "tx" => Ok(DedupKey::Tx),
+            "tx-log" => Ok(DedupKey::TxLog),
+            "tx-event" => Ok(DedupKey::TxEvent),
+            _ => Err(format!("Unsupported dedup key: {}", s)),
+        }
+    }
+}
+
+/// Value of the fields of a [`DedupKey`] for one event.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum EventKey {
+    Tx(H256),
+    TxLog(H256, u64),
+    TxEvent(H256, String),
+}
+
+impl DedupKey {
+    /// Returns the key of `event`, or `None` when events are not deduplicated.
+    pub fn key(self, event: &OrderEvent) -> Option<EventKey> {
+        match self {
+            DedupKey::None => None,
+            DedupKey::Tx => Some(EventKey::Tx(event.txn_hash)),
+            DedupKey::TxLog => Some(EventKey::TxLog(event.txn_hash, event.log_index)),
+            DedupKey::TxEvent => Some(EventKey::TxEvent(event.txn_hash, event.event_type.clone())),
+        }
+    }
+}
+
+/// Drops the events whose [`DedupKey`] was already seen during the run.
+///
+/// A single deduplicator filters the events of a run before the sink routes them,
+/// so its set covers every file of a partitioned output: a duplicate is dropped
+/// even when it would land in another partition than the first event.
+#[derive(Debug, Default)]
+pub struct Deduplicator {
+    key: DedupKey,
+    seen: HashSet<EventKey>,
+}
+
+impl Deduplicator {
+    /// Creates a deduplicator keyed on `key`.
+    pub fn new(key: DedupKey) -> Self {
+        Deduplicator {
+            key,
+            seen: HashSet::new(),
+        }
+    }
+
+    /// Returns whether `event` is kept, i.e. is the first event with its key.
+    pub fn keep(&mut self, event: &OrderEvent) -> bool {
+        match self.key.key(event) {
+            Some(key) => self.seen.insert(key),
+            None => true,
+        }
+    }
+}
+
+/// The logs written for the most recent blocks, shared by the collections of a
+/// followed chain so that the blocks re-queried by `--lookback-overlap` do not
+/// write their logs twice.
+///
+/// Only the logs of blocks from [`Self::since`] on are tracked, so the set stays
+/// bounded however long the chain is followed.
+#[derive(Debug, Default)]
+pub struct RecentLogs {
+    since: u64,
+    seen: BTreeMap<u64, HashSet<(H256, u64)>>,
+}
+
+impl RecentLogs {
+    /// Tracks the logs of blocks `since` and later.
+    pub fn new(since: u64) -> Self {
+        RecentLogs {
+            since,
+            seen: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the first tracked block.
+    pub fn since(&self) -> u64 {
+        self.since
+    }
+
+    /// Returns whether `event` is kept, i.e. is not a log already written. Logs
+    /// before the tracked blocks are always kept.
+    pub fn keep(&mut self, event: &OrderEvent) -> bool {
+        if event.block_number < self.since {
+            return true;
+        }
+        self.seen
+            .entry(event.block_number)
+            .or_default()
+            .insert((event.txn_hash, event.log_index))
+    }
+
+    /// Stops tracking the logs of the blocks before `since`.
+    pub fn forget_before(&mut self, since: u64) {
+        self.since = self.since.max(since);
+        self.seen = self.seen.split_off(&self.since);
+    }
+}
+
+/// Event kept for each transaction by `--one-per-tx`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnePerTx {
+    /// The event with the lowest log index.
+    First,
+    /// The event with the highest log index.
+    Last,
+}
+
+impl FromStr for OnePerTx {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "first" => Ok(OnePerTx::First),
+            "last" => Ok(OnePerTx::Last),
+            _ => Err(format!("Unsupported one-per-tx mode: {}", s)),
+        }
+    }
+}
+
+impl OnePerTx {
+    /// Keeps a single event per transaction hash in `events`, the first or last by log
+    /// index. The survivors stay in their original order.
+    ///
+    /// All the logs of a transaction are in its block, so reducing each chunk on its own
+    /// gives the same result as reducing the whole run.
+    pub fn reduce(self, events: &mut Vec<OrderEvent>) {
+        let mut survivors: HashMap<H256, u64> = HashMap::new();
+        for event in events.iter() {
+            survivors
+                .entry(event.txn_hash)
+                .and_modify(|log_index| {
+                    *log_index = match self {
+                        OnePerTx::First => (*log_index).min(event.log_index),
+                        OnePerTx::Last => (*log_index).max(event.log_index),
+                    }
+                })
+                .or_insert(event.log_index);
+        }
+        events.retain(|event| survivors.get(&event.txn_hash) == Some(&event.log_index));
+    }
+}
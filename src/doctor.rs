@@ -0,0 +1,339 @@
+use std::fmt;
+
+use ethers::abi::Abi;
+use ethers::types::H256;
+
+use crate::constants;
+use crate::event_collector::{load_abi, AbiSource};
+use crate::keys::Keys;
+use crate::retry::RetryPolicy;
+use crate::utils::{get_contract_creation_block, get_latest_block_number, get_ws_rpc_url};
+
+/// Outcome of a single preflight check.
+#[derive(Debug)]
+pub struct Check {
+    /// Short description of what was checked.
+    pub name: String,
+    /// `Ok` with a detail message on success, `Err` with the reason on failure.
+    pub result: Result<String, String>,
+    /// Actionable advice shown when the check fails.
+    pub hint: &'static str,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, result: Result<String, String>, hint: &'static str) -> Self {
+        Check {
+            name: name.into(),
+            result,
+            hint,
+        }
+    }
+
+    /// Returns whether the check passed.
+    pub fn passed(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+/// Runs every preflight check: API keys, Etherscan key validity, the
+/// WebSocket RPC of each supported network and the ABI file.
+pub async fn run_checks(keys: &Keys) -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    // Required API keys, from the flags or the environment
+    for (name, key, hint) in [
+        (
+            "Infura",
+            &keys.infura,
+            "Pass --infura-key, or add INFURA_API_KEY to your environment or .env file",
+        ),
+        (
+            "Etherscan",
+            &keys.etherscan,
+            "Pass --etherscan-key, or add ETHERSCAN_API_KEY to your environment or .env file",
+        ),
+    ] {
+        checks.push(Check::new(
+            format!("{} API key is set", name),
+            key.as_ref()
+                .map(|_| "present".to_string())
+                .ok_or_else(|| "missing".to_string()),
+            hint,
+        ));
+    }
+
+    // Etherscan key validity, using a cheap contract creation lookup
+    let etherscan = match keys.etherscan() {
+        Ok(api_key) => get_contract_creation_block(
+            constants::ETHERSCAN_BASIC_URL,
+            api_key,
+            constants::DEFAULT_CONTRACT_ADDRESS,
+            &RetryPolicy::none(),
+        )
+        .map(|block| format!("lookup returned block {}", block))
+        .map_err(|e| e.to_string()),
+        Err(_) => Err("skipped, the Etherscan API key is not set".to_string()),
+    };
+    checks.push(Check::new(
+        "Etherscan API key is valid",
+        etherscan,
+        "Verify the key at https://etherscan.io/myapikey and check your rate limit",
+    ));
+
+    // Reachability of every configured WebSocket RPC
+    for network in constants::SUPPORTED_NETWORKS {
+        let result = match keys.infura() {
+            Err(_) => Err("skipped, the Infura API key is not set".to_string()),
+            Ok(infura_key) => match get_ws_rpc_url(network, Some(infura_key)) {
+                Ok(ws_rpc_url) => get_latest_block_number(&ws_rpc_url, &[])
+                    .await
+                    .map(|block| format!("latest block {}", block))
+                    .map_err(|e| e.to_string()),
+                Err(e) => Err(e),
+            },
+        };
+        checks.push(Check::new(
+            format!("{} RPC is reachable", network),
+            result,
+            "Check the Infura key and that the network is enabled for your project",
+        ));
+    }
+
+    // ABI file parses and defines the collected events
+    let abi = load_abi(&AbiSource::default())
+        .map_err(|e| e.to_string())
+        .and_then(|abi| {
+            for name in [constants::TAKEORDER_EVENT_NAME, constants::CLEAR_EVENT_NAME] {
+                abi.event(name)
+                    .map_err(|_| format!("event {} not found", name))?;
+            }
+            Ok(format!("{} events defined", abi.events().count()))
+        });
+    checks.push(Check::new(
+        format!("ABI file {} is valid", constants::ABI_FILE_PATH),
+        abi,
+        "Run from the project root or restore IOrderBookV4.json",
+    ));
+
+    checks
+}
+
+/// Formats a check as a checklist line, followed by its hint when it failed.
+pub fn format_check(check: &Check) -> String {
+    match &check.result {
+        Ok(detail) => format!("[PASS] {} ({})", check.name, detail),
+        Err(reason) => format!(
+            "[FAIL] {} ({})\n       hint: {}",
+            check.name, reason, check.hint
+        ),
+    }
+}
+
+/// Prints the checklist and returns whether every check passed.
+pub fn print_report(checks: &[Check]) -> bool {
+    for check in checks {
+        println!("{}", format_check(check));
+    }
+
+    let failed = checks.iter().filter(|check| !check.passed()).count();
+    if failed == 0 {
+        println!("All {} checks passed.", checks.len());
+    } else {
+        println!("{} of {} checks failed.", failed, checks.len());
+    }
+    failed == 0
+}
+
+/// An event declared in an ABI, as listed by `--validate-abi`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AbiEvent {
+    /// Name of the event.
+    pub name: String,
+    /// Canonical signature, e.g. `Deposit(address,address,uint256,uint256)`.
+    pub signature: String,
+    /// Keccak hash of the signature, i.e. the `topic0` of its logs.
+    pub topic0: H256,
+    /// Name, type and whether it is indexed, of each parameter.
+    pub params: Vec<(String, String, bool)>,
+}
+
+/// Lists the events of `abi` by name, overloads included.
+pub fn abi_events(abi: &Abi) -> Vec<AbiEvent> {
+    abi.events()
+        .map(|event| {
+            let types: Vec<String> = event
+                .inputs
+                .iter()
+                .map(|input| input.kind.to_string())
+                .collect();
+            AbiEvent {
+                name: event.name.clone(),
+                signature: format!("{}({})", event.name, types.join(",")),
+                topic0: event.signature(),
+                params: event
+                    .inputs
+                    .iter()
+                    .zip(types)
+                    .map(|(input, kind)| (input.name.clone(), kind, input.indexed))
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// Returns the collected events (see [`constants::COLLECTED_EVENTS`]) that `abi`
+/// does not declare under their exact name.
+pub fn missing_collected_events(abi: &Abi) -> Vec<&'static str> {
+    constants::COLLECTED_EVENTS
+        .into_iter()
+        .filter(|name| abi.event(name).is_err())
+        .collect()
+}
+
+/// An ambiguity among the collected events of an ABI, which would make their logs
+/// mislabelled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventAmbiguity {
+    /// The name is declared with several signatures, of which only the first one
+    /// is collected.
+    Overloaded {
+        name: String,
+        signatures: Vec<String>,
+    },
+    /// The signature is declared with different indexed parameters, so its logs
+    /// are decoded with the first declaration only, e.g. an ERC-20 and an ERC-721
+    /// `Transfer`.
+    IndexedMismatch {
+        signature: String,
+        declarations: Vec<String>,
+    },
+}
+
+impl fmt::Display for EventAmbiguity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EventAmbiguity::Overloaded { name, signatures } => write!(
+                f,
+                "event {} is overloaded as {}; only {} is collected",
+                name,
+                signatures.join(", "),
+                signatures[0]
+            ),
+            EventAmbiguity::IndexedMismatch {
+                signature,
+                declarations,
+            } => write!(
+                f,
+                "event {} is declared as {}; its logs are decoded as {}",
+                signature,
+                declarations.join(", "),
+                declarations[0]
+            ),
+        }
+    }
+}
+
+/// Returns the signature of `event` with its indexed parameters marked, e.g.
+/// `Transfer(address indexed,address indexed,uint256)`.
+fn indexed_signature(event: &AbiEvent) -> String {
+    let params: Vec<String> = event
+        .params
+        .iter()
+        .map(|(_, kind, indexed)| {
+            if *indexed {
+                format!("{} indexed", kind)
+            } else {
+                kind.clone()
+            }
+        })
+        .collect();
+    format!("{}({})", event.name, params.join(","))
+}
+
+/// Returns the ambiguities among the events of `abi` named `names`: names declared
+/// with several signatures, and signatures declared with different indexed
+/// parameters. Repeated declarations of the same event are not ambiguous.
+///
+/// Distinct signatures always have distinct topic0s, short of a keccak collision,
+/// so the topic0 alone never mixes up two events.
+pub fn event_ambiguities(abi: &Abi, names: &[&str]) -> Vec<EventAmbiguity> {
+    let mut events: Vec<AbiEvent> = Vec::new();
+    for event in abi_events(abi) {
+        let repeated = events
+            .iter()
+            .any(|other| indexed_signature(other) == indexed_signature(&event));
+        if names.contains(&event.name.as_str()) && !repeated {
+            events.push(event);
+        }
+    }
+
+    let mut ambiguities = Vec::new();
+    for name in names {
+        let mut signatures: Vec<String> = Vec::new();
+        for event in events.iter().filter(|event| event.name == *name) {
+            if !signatures.contains(&event.signature) {
+                signatures.push(event.signature.clone());
+            }
+        }
+        if signatures.len() > 1 {
+            ambiguities.push(EventAmbiguity::Overloaded {
+                name: name.to_string(),
+                signatures: signatures.clone(),
+            });
+        }
+        for signature in signatures {
+            let declarations: Vec<String> = events
+                .iter()
+                .filter(|event| event.signature == signature)
+                .map(indexed_signature)
+                .collect();
+            if declarations.len() > 1 {
+                ambiguities.push(EventAmbiguity::IndexedMismatch {
+                    signature,
+                    declarations,
+                });
+            }
+        }
+    }
+    ambiguities
+}
+
+/// Formats `events` as an aligned list: one line per event with its name, topic0
+/// and signature, followed by one line per parameter with its name, whether it is
+/// indexed, and its type.
+pub fn format_abi_events(events: &[AbiEvent]) -> String {
+    let name_width = events
+        .iter()
+        .map(|event| event.name.len())
+        .max()
+        .unwrap_or(0);
+    let param_width = events
+        .iter()
+        .flat_map(|event| &event.params)
+        .map(|(name, _, _)| name.len())
+        .max()
+        .unwrap_or(0);
+
+    let mut lines = Vec::new();
+    for event in events {
+        lines.push(format!(
+            "{:<width$}  {:?}  {}",
+            event.name,
+            event.topic0,
+            event.signature,
+            width = name_width
+        ));
+        for (name, kind, indexed) in &event.params {
+            lines.push(format!(
+                "{:indent$}  {:<width$}  {:<7}  {}",
+                "",
+                name,
+                if *indexed { "indexed" } else { "data" },
+                kind,
+                indent = name_width,
+                width = param_width
+            ));
+        }
+    }
+    lines.join("\n")
+}
@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+
+use ethers::types::H160;
+
+use crate::error::CollectorError;
+use crate::source::ChainSource;
+
+/// Returns whether `input` has the shape of an ENS name, i.e. dot-separated
+/// non-empty labels such as `orderbook.eth`, rather than of an address.
+pub fn is_ens_name(input: &str) -> bool {
+    !input.starts_with("0x")
+        && input.contains('.')
+        && input
+            .split('.')
+            .all(|label| !label.is_empty() && !label.chars().any(char::is_whitespace))
+}
+
+/// Resolves addresses given either in hex or as ENS names, remembering the
+/// names resolved during the run.
+#[derive(Debug, Default)]
+pub struct EnsResolver {
+    cache: HashMap<String, H160>,
+}
+
+impl EnsResolver {
+    /// Returns the address `input` stands for: itself when it is a hex address,
+    /// or the address its ENS name resolves to through `source`.
+    ///
+    /// Input that is neither, and names without an address, are invalid
+    /// arguments; a failed lookup is an RPC error.
+    pub async fn resolve(
+        &mut self,
+        source: &dyn ChainSource,
+        input: &str,
+    ) -> Result<H160, CollectorError> {
+        if let Ok(address) = input.parse::<H160>() {
+            return Ok(address);
+        }
+        if !is_ens_name(input) {
+            return Err(CollectorError::InvalidArgs(format!(
+                "'{}' is neither a hex address nor an ENS name",
+                input
+            )));
+        }
+
+        let name = input.to_ascii_lowercase();
+        if let Some(address) = self.cache.get(&name) {
+            return Ok(*address);
+        }
+        let address = source
+            .ens_address(&name)
+            .await
+            .map_err(|e| CollectorError::Rpc(format!("Failed to resolve {}: {}", name, e)))?
+            .ok_or_else(|| CollectorError::InvalidArgs(format!("ENS name {} not found", name)))?;
+        self.cache.insert(name, address);
+        Ok(address)
+    }
+}
@@ -0,0 +1,142 @@
+use std::error::Error;
+use std::fmt;
+
+use ethers::providers::{ProviderError, WsClientError};
+
+use crate::event_collector::CollectionStats;
+
+/// Errors raised by the collector that callers may want to tell apart.
+#[derive(Debug)]
+pub enum CollectorError {
+    /// The user supplied invalid arguments or configuration.
+    InvalidArgs(String),
+    /// The RPC provider failed or could not be reached.
+    Rpc(String),
+    /// The provider returned a pending block, which has no number yet.
+    PendingBlock,
+    /// An event would have been left out of the output of a `--strict` run.
+    Incomplete(String),
+    /// Reading or writing a local file failed.
+    Io(String),
+    /// Data such as a JSON response or an ABI could not be parsed or decoded.
+    Decode(String),
+    /// The run was stopped before the chunk in progress completed.
+    Interrupted,
+}
+
+impl fmt::Display for CollectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CollectorError::InvalidArgs(msg) => write!(f, "Invalid arguments: {}", msg),
+            CollectorError::Rpc(msg) => write!(f, "RPC error: {}", msg),
+            CollectorError::PendingBlock => {
+                write!(
+                    f,
+                    "RPC error: the latest block is pending and has no number"
+                )
+            }
+            CollectorError::Incomplete(msg) => {
+                write!(f, "Incomplete output (strict mode): {}", msg)
+            }
+            CollectorError::Io(msg) => write!(f, "I/O error: {}", msg),
+            CollectorError::Decode(msg) => write!(f, "Decoding error: {}", msg),
+            CollectorError::Interrupted => {
+                write!(f, "Interrupted before the current chunk completed")
+            }
+        }
+    }
+}
+
+impl Error for CollectorError {}
+
+// Conversions keeping `?` usable in functions returning a `CollectorError`
+
+impl From<ureq::Error> for CollectorError {
+    fn from(err: ureq::Error) -> Self {
+        CollectorError::Rpc(err.to_string())
+    }
+}
+
+impl From<ProviderError> for CollectorError {
+    fn from(err: ProviderError) -> Self {
+        CollectorError::Rpc(err.to_string())
+    }
+}
+
+impl From<std::io::Error> for CollectorError {
+    fn from(err: std::io::Error) -> Self {
+        CollectorError::Io(err.to_string())
+    }
+}
+
+impl From<csv::Error> for CollectorError {
+    fn from(err: csv::Error) -> Self {
+        CollectorError::Io(err.to_string())
+    }
+}
+
+impl From<serde_json::Error> for CollectorError {
+    fn from(err: serde_json::Error) -> Self {
+        CollectorError::Decode(err.to_string())
+    }
+}
+
+impl From<ethers::abi::Error> for CollectorError {
+    fn from(err: ethers::abi::Error) -> Self {
+        CollectorError::Decode(err.to_string())
+    }
+}
+
+/// Process exit codes reported by the command-line tool.
+///
+/// | Code | Meaning                                               |
+/// |------|-------------------------------------------------------|
+/// | 0    | Events were collected and written                     |
+/// | 1    | Unclassified hard error                               |
+/// | 2    | Invalid arguments or configuration                    |
+/// | 3    | RPC / network failure                                 |
+/// | 4    | The run succeeded but collected zero events           |
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    Success = 0,
+    Failure = 1,
+    InvalidArgs = 2,
+    Rpc = 3,
+    NoEvents = 4,
+}
+
+impl ExitCode {
+    /// Maps the statistics of a successful run to an exit code.
+    pub fn from_stats(stats: &CollectionStats) -> Self {
+        if stats.total_events == 0 {
+            ExitCode::NoEvents
+        } else {
+            ExitCode::Success
+        }
+    }
+
+    /// Maps a hard error to an exit code by inspecting its concrete type.
+    pub fn from_error(err: &(dyn Error + 'static)) -> Self {
+        if let Some(err) = err.downcast_ref::<CollectorError>() {
+            return match err {
+                CollectorError::InvalidArgs(_) => ExitCode::InvalidArgs,
+                CollectorError::Rpc(_) | CollectorError::PendingBlock => ExitCode::Rpc,
+                CollectorError::Incomplete(_)
+                | CollectorError::Io(_)
+                | CollectorError::Decode(_)
+                | CollectorError::Interrupted => ExitCode::Failure,
+            };
+        }
+
+        if err.is::<ProviderError>() || err.is::<WsClientError>() || err.is::<ureq::Error>() {
+            ExitCode::Rpc
+        } else {
+            ExitCode::Failure
+        }
+    }
+
+    /// Returns the numeric code passed to `std::process::exit`.
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+}
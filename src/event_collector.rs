@@ -1,148 +1,1683 @@
-use std::cmp::min;
+use std::collections::{BTreeMap, BTreeSet};
 use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Instant;
 
-use ethers::abi::Abi;
-use ethers::providers::{Middleware, Provider, Ws};
-use ethers::types::{BlockNumber, Filter, Log, H160, H256, U64};
-use log::{error, info};
+use ethers::abi::{Abi, Event};
+use ethers::types::{Action, BlockNumber, Filter, Log, Trace, H160, H256, U64};
+use futures::future::join_all;
+use log::{error, info, warn};
 use serde_json::Value;
+use tokio::sync::Semaphore;
 use tokio::time::{sleep, Duration};
+use tokio_util::sync::CancellationToken;
 
+use crate::aggregate::{aggregate_logs, with_timestamps, BlockAggregateSink};
+use crate::avro::AvroSink;
+use crate::checkpoint::Checkpoint;
+use crate::chunks::{BlockChunks, BlockOrder};
+use crate::columns::{Column, ColumnSet};
 use crate::constants;
-use crate::csv_manager::{initialize_csv, write_to_csv};
-use crate::utils::OrderEvent;
+use crate::csv_manager::{repair_csv, CsvDialect, CsvSink};
+use crate::decode::{
+    decode_indexed_addresses, decode_log_args, indexed_address_names, param_names,
+};
+use crate::dedup::{DedupKey, Deduplicator, OnePerTx, RecentLogs};
+use crate::doctor::event_ambiguities;
+use crate::error::CollectorError;
+use crate::failures::{record_failed_chunk, FailedChunk};
+use crate::metadata::{check_schema, metadata_path, MetadataGuard, OutputMetadata, OutputSchema};
+use crate::metrics::Metrics;
+use crate::postgres::PostgresSink;
+use crate::progress::{ProgressStart, ProgressTracker};
+use crate::provider_pool::{PooledProviders, ProviderPool};
+use crate::rate_limit::RateLimiter;
+use crate::rpc_errors::classify_source_error;
+use crate::rpc_headers::RpcHeader;
+use crate::sampling::{Sampler, Sampling};
+use crate::sink::{
+    check_buffered_bytes, EventSink, JsonArraySink, JsonSink, OutputFormat, PartitionedSink,
+    Partitioning, TailSink, TeeSink,
+};
+use crate::source::{ChainSource, SourceError};
+use crate::table::TableSink;
+use crate::timing::ChunkDurations;
+use crate::utils::{
+    effective_gas_price, fmt_int, safe_block, safe_latest_block, transaction_calldata,
+    transaction_gas_price, Calldata, OrderEvent, SafeLatestBlock,
+};
+use crate::value_filter::ValueRange;
+use crate::webhook::Webhook;
 
-/// Loads the ABI from a JSON file and returns an `Abi` object.
-fn load_abi(file_path: &str) -> Result<Abi, Box<dyn Error>> {
-    let abi_json: Value = serde_json::from_str(&std::fs::read_to_string(file_path)?)?;
-    Ok(Abi::load(abi_json.to_string().as_bytes())?)
+/// Where the contract ABI JSON is read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AbiSource {
+    /// Path of a JSON file.
+    Path(String),
+    /// The JSON itself, for embedders holding the ABI in memory.
+    Inline(String),
 }
 
-/// Retrieves event signatures based on the event type filter.
-fn get_event_signatures(abi: &Abi, event_type: &str) -> Result<Vec<H256>, Box<dyn Error>> {
-    let take_order_event = abi.event(constants::TAKEORDER_EVENT_NAME)?.signature();
-    let clear_event = abi.event(constants::CLEAR_EVENT_NAME)?.signature();
+impl Default for AbiSource {
+    fn default() -> Self {
+        AbiSource::Path(constants::ABI_FILE_PATH.to_string())
+    }
+}
 
-    let signatures = match event_type {
-        constants::TAKEORDER_EVENT_NAME => vec![take_order_event],
-        constants::CLEAR_EVENT_NAME => vec![clear_event],
-        constants::DEFAULT => vec![take_order_event, clear_event], // Default: Both events
-        &_ => vec![take_order_event, clear_event],
+/// Loads the ABI from a JSON file or string and returns an `Abi` object.
+///
+/// Failures name the ABI file and tell a missing file, invalid JSON and JSON that
+/// is not an ABI apart.
+pub fn load_abi(source: &AbiSource) -> Result<Abi, Box<dyn Error>> {
+    let read;
+    let (origin, json) = match source {
+        AbiSource::Path(path) => {
+            read = std::fs::read_to_string(path).map_err(|err| {
+                let reason = match err.kind() {
+                    std::io::ErrorKind::NotFound => "file not found".to_string(),
+                    _ => err.to_string(),
+                };
+                CollectorError::Io(format!("Failed to parse ABI at {}: {}", path, reason))
+            })?;
+            (format!("at {}", path), read.as_str())
+        }
+        AbiSource::Inline(json) => ("given inline".to_string(), json.as_str()),
     };
 
-    Ok(signatures)
+    let abi_json: Value = serde_json::from_str(json).map_err(|err| {
+        CollectorError::Decode(format!(
+            "Failed to parse ABI {}: not valid JSON: {}",
+            origin, err
+        ))
+    })?;
+    Abi::load(abi_json.to_string().as_bytes()).map_err(|err| {
+        CollectorError::Decode(format!(
+            "Failed to parse ABI {}: valid JSON but not a valid ABI: {}",
+            origin, err
+        ))
+        .into()
+    })
 }
 
-/// Fetches order events within a specified block range.
-pub async fn collect_order_events(
-    ws_rpc_url: &str,
-    contract_address: &str,
+/// Resolves the `--event` selection into the names of the events to collect.
+///
+/// An empty value, `all` or the legacy `DEFAULT` sentinel select every collected
+/// event; any other value must name one of them exactly.
+pub(crate) fn resolve_event_names(event_type: &str) -> Result<Vec<&'static str>, CollectorError> {
+    if selects_all_events(event_type) {
+        return Ok(constants::COLLECTED_EVENTS.to_vec());
+    }
+
+    constants::COLLECTED_EVENTS
+        .iter()
+        .find(|name| **name == event_type)
+        .map(|name| vec![*name])
+        .ok_or_else(|| {
+            CollectorError::InvalidArgs(format!(
+                "Unsupported event type: {} (expected one of {} or {})",
+                event_type,
+                constants::COLLECTED_EVENTS.join(", "),
+                constants::ALL_EVENTS
+            ))
+        })
+}
+
+/// Returns whether the `--event` selection is that of every collected event.
+fn selects_all_events(event_type: &str) -> bool {
+    event_type.is_empty()
+        || event_type.eq_ignore_ascii_case(constants::ALL_EVENTS)
+        || event_type == constants::DEFAULT
+}
+
+/// Checks that raw `topic0` signatures agree with an explicit `event_type`, that is
+/// that they are exactly the signatures of the named events in `abi`, rather than
+/// silently preferring one filter. Selecting every event leaves the choice to
+/// `topic0`.
+pub fn check_topic0_consistency(
+    abi: &Abi,
+    event_type: &str,
+    topic0: &[H256],
+) -> Result<(), CollectorError> {
+    if topic0.is_empty() || selects_all_events(event_type) {
+        return Ok(());
+    }
+
+    let mut expected = BTreeSet::new();
+    for name in resolve_event_names(event_type)? {
+        expected.insert(abi.event(name)?.signature());
+    }
+    let given: BTreeSet<H256> = topic0.iter().copied().collect();
+    if given != expected {
+        return Err(CollectorError::InvalidArgs(format!(
+            "--event {} has signature(s) {:?}, but --topic0 gives {:?}; \
+             make them agree or pass only one of them",
+            event_type, expected, given
+        )));
+    }
+    Ok(())
+}
+
+/// Collected event names keyed by their signature (topic0), from which both the
+/// `topic0` filter of the logs and the `event_type` of their events are derived.
+pub type EventNames = BTreeMap<H256, String>;
+
+/// Retrieves the names of the events selected by the event type filter, keyed by
+/// their signature in `abi`.
+fn get_event_names(abi: &Abi, event_type: &str) -> Result<EventNames, Box<dyn Error>> {
+    let names = resolve_event_names(event_type)?;
+    info!("Collecting events: {}", names.join(", "));
+
+    let mut event_names = EventNames::new();
+    for name in names {
+        event_names.insert(abi.event(name)?.signature(), name.to_string());
+    }
+    Ok(event_names)
+}
+
+/// Warns about the overloads and shared signatures among the events selected by
+/// `event_type` in `abi`, which would mislabel their logs, or fails on the first
+/// one when `strict` is set.
+fn check_event_ambiguities(
+    abi: &Abi,
+    event_type: &str,
+    strict: bool,
+) -> Result<(), CollectorError> {
+    for ambiguity in event_ambiguities(abi, &resolve_event_names(event_type)?) {
+        if strict {
+            return Err(CollectorError::InvalidArgs(format!(
+                "Ambiguous ABI: {}",
+                ambiguity
+            )));
+        }
+        warn!("Ambiguous ABI: {}", ambiguity);
+    }
+    Ok(())
+}
+
+/// Returns the events selected by raw `topic0` signatures, each named after its
+/// signature.
+pub fn raw_event_names(topic0: &[H256]) -> EventNames {
+    topic0
+        .iter()
+        .map(|signature| (*signature, format!("{:?}", signature)))
+        .collect()
+}
+
+/// Builds the `eth_getLogs` filter of the events with `signatures` emitted by
+/// `address`, either in the block with hash `block_hash` or in blocks `from_block`
+/// to `to_block`.
+pub fn logs_filter(
+    address: H160,
+    signatures: Vec<H256>,
+    block_hash: Option<H256>,
     from_block: u64,
     to_block: u64,
-    chunk_size: u64,
+) -> Filter {
+    let filter = Filter::new().address(address).topic0(signatures);
+    match block_hash {
+        Some(hash) => filter.at_block_hash(hash),
+        None => filter
+            .from_block(BlockNumber::Number(U64::from(from_block)))
+            .to_block(BlockNumber::Number(U64::from(to_block))),
+    }
+}
+
+/// Serializes `filter` into the JSON params object of `eth_getLogs`, with the
+/// addresses, topics and block numbers in hex, ready to be replayed with curl or
+/// cast.
+pub fn filter_json(filter: &Filter) -> Result<String, CollectorError> {
+    Ok(serde_json::to_string(filter)?)
+}
+
+/// Returns the ABI definitions of the events selected by `event_type`.
+fn collected_abi_events<'a>(
+    abi: &'a Abi,
     event_type: &str,
-    filename: &str, // Add filename parameter
-) -> Result<(), Box<dyn Error>> {
-    let provider = Provider::<Ws>::connect(ws_rpc_url).await?;
-    let contract_addr: H160 = contract_address.parse()?;
-    let abi = load_abi(constants::ABI_FILE_PATH)?;
-    let event_signatures = get_event_signatures(&abi, event_type)?;
+) -> Result<Vec<&'a Event>, Box<dyn Error>> {
+    Ok(resolve_event_names(event_type)?
+        .into_iter()
+        .map(|name| abi.event(name))
+        .collect::<Result<Vec<_>, _>>()?)
+}
+
+/// Rejects an empty signature set, which the `topic0` filter would not treat as
+/// "no events" and which would therefore collect the wrong logs.
+pub(crate) fn require_signatures(signatures: &[H256]) -> Result<(), CollectorError> {
+    if signatures.is_empty() {
+        return Err(CollectorError::InvalidArgs(
+            "no matching event signatures for the requested event type(s)".to_string(),
+        ));
+    }
+    Ok(())
+}
 
-    let take_order_event = abi.event(constants::TAKEORDER_EVENT_NAME)?;
-    let clear_event = abi.event(constants::CLEAR_EVENT_NAME)?;
+/// Transform applied to every event just before it is written, so embedders can
+/// annotate or rewrite events, e.g. with an off-chain label, without forking the
+/// collector.
+///
+/// It runs in the hot path, once per written event, so it should be cheap. Clones
+/// of a config share the same transform and its state.
+#[derive(Clone)]
+pub struct EventTransform(Arc<Mutex<TransformFn>>);
 
-    let mut start_block = from_block;
+/// Closure of an [`EventTransform`].
+type TransformFn = dyn FnMut(&mut OrderEvent) + Send;
 
-    // Initialize CSV file once before appending
-    initialize_csv(filename)?;
+impl EventTransform {
+    /// Wraps `transform` to be set as [`CollectorConfig::on_event`].
+    pub fn new(transform: impl FnMut(&mut OrderEvent) + Send + 'static) -> Self {
+        EventTransform(Arc::new(Mutex::new(transform)))
+    }
 
-    info!(
-        "Collecting Event data from {} to {} with chunk size of {} for {} contract",
-        from_block, to_block, chunk_size, contract_address,
-    );
-    while start_block <= to_block {
-        let end_block = min(start_block + chunk_size - 1, to_block);
+    /// Applies the transform to `event`.
+    pub fn apply(&self, event: &mut OrderEvent) {
+        // A transform that panicked once is still called for the next events
+        let mut transform = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        (*transform)(event)
+    }
+}
+
+impl fmt::Debug for EventTransform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EventTransform")
+    }
+}
+
+/// Parameters controlling a single collection run.
+#[derive(Debug, Clone)]
+pub struct CollectorConfig {
+    /// WebSocket RPC URL of the node to query.
+    pub ws_rpc_url: String,
+    /// Additional WebSocket RPC URLs to fail over to, in order of preference.
+    pub fallback_rpc_urls: Vec<String>,
+    /// Headers sent with the WebSocket upgrade request of every RPC connection.
+    pub rpc_headers: Vec<RpcHeader>,
+    /// Address of the contract emitting the events.
+    pub contract_address: String,
+    /// First block of the range (inclusive).
+    pub from_block: u64,
+    /// Last block of the range (inclusive).
+    pub to_block: u64,
+    /// Hash of the single block whose logs are fetched instead of scanning the range,
+    /// which is immune to reorgs, when set.
+    pub block_hash: Option<H256>,
+    /// Number of most recent blocks left out of the scan, as they may still be reorged.
+    pub confirmations: u64,
+    /// Latest block already known to the caller, e.g. a followed head, used instead
+    /// of asking the providers, which may lag behind it, when set.
+    pub latest_block: Option<u64>,
+    /// Maximum number of logs of a chunk enriched concurrently.
+    pub enrich_concurrency: usize,
+    /// Number of blocks fetched per `get_logs` call.
+    pub chunk_size: u64,
+    /// Maximum number of blocks a single `get_logs` call may span, for providers
+    /// rejecting wider ranges; wider chunks are queried in several calls.
+    pub max_block_span: Option<u64>,
+    /// Pause between chunks, in milliseconds, to stay under provider rate limits.
+    pub request_delay_ms: u64,
+    /// Token bucket every RPC call of the collection waits on, capping the requests
+    /// per second across concurrent enrichments, when set.
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    /// Event type filter (e.g., TakeOrderV2, ClearV2 or `all` for both).
+    pub event_type: String,
+    /// Raw event signature hashes to filter on instead of `event_type`, bypassing the ABI.
+    pub topic0: Vec<H256>,
+    /// ABI of the contract, used to select and label the events.
+    pub abi: AbiSource,
+    /// Subset of the matching events that is kept; dropped events are not enriched.
+    pub sampling: Sampling,
+    /// Seed of the random sampling, for reproducible samples.
+    pub seed: Option<u64>,
+    /// Fields identifying duplicate events, of which only the first is written.
+    pub dedup_key: DedupKey,
+    /// Reduces the events of each transaction to its first or last one, when set.
+    pub one_per_tx: Option<OnePerTx>,
+    /// Bounds on the value of the transaction of each kept event.
+    pub value_range: ValueRange,
+    /// Format of the output file.
+    pub format: OutputFormat,
+    /// Whether a JSON array output is written chunk by chunk instead of at the end.
+    pub array_streaming: bool,
+    /// Whether binary outputs store addresses and hashes as bytes instead of hex.
+    pub compact_addresses: bool,
+    /// Estimated bytes of buffered events above which the run fails, when set.
+    pub max_in_flight_bytes: Option<u64>,
+    /// Delimiter and header presence of the CSV output.
+    pub csv_dialect: CsvDialect,
+    /// Splits the output into one file per window of blocks or per UTC day, when
+    /// set.
+    pub partition: Option<Partitioning>,
+    /// Postgres connection URL the events are inserted into instead of the output
+    /// file, when set.
+    pub postgres_url: Option<String>,
+    /// Webhook the events are also POSTed to as they are written, when set; one
+    /// delivery is shared by every collection of the run.
+    pub webhook: Option<Arc<Webhook>>,
+    /// Number of events after which the collection stops, when set.
+    pub head: Option<u64>,
+    /// Number of last events kept and written when the run finishes, when set.
+    pub tail: Option<u64>,
+    /// Whether each decoded event parameter gets a CSV column of its own.
+    pub flatten_args: bool,
+    /// Whether each indexed address parameter, decoded from the log topics, gets a
+    /// CSV column of its own.
+    pub indexed_addresses: bool,
+    /// Output file path.
+    pub filename: String,
+    /// Direction in which the block range is traversed.
+    pub order: BlockOrder,
+    /// Whether numbers in log lines are grouped with thousands separators.
+    pub pretty: bool,
+    /// Checkpoint file recording completed chunks, used to skip them on restart.
+    pub checkpoint_path: Option<String>,
+    /// Whether the checkpoint is reconciled with the output before resuming, see
+    /// [`Checkpoint::reconcile`].
+    pub reconcile_checkpoint: bool,
+    /// Error log recording the chunks that failed on every provider, when set.
+    pub error_log_path: Option<String>,
+    /// Inclusive block ranges, in ascending order, collected instead of the whole of
+    /// `from_block..=to_block` when non-empty, e.g. the failed chunks of a previous
+    /// run or the ranges of a ranges file.
+    pub ranges: Vec<(u64, u64)>,
+    /// Whether the raw log data and topics are appended to each row.
+    pub raw: bool,
+    /// Whether the output file is fsynced after every chunk for crash safety.
+    pub fsync: bool,
+    /// Whether the timing of every chunk is logged and summarized.
+    pub timing: bool,
+    /// Whether the `eth_getLogs` filter is logged as JSON before the first query.
+    pub dump_filter: bool,
+    /// Whether the immediate caller of the contract is traced into a `direct_caller` column.
+    pub include_internal: bool,
+    /// Whether events are written from their logs alone, without fetching their block
+    /// and transaction, leaving out the origin and timestamp.
+    pub no_enrich: bool,
+    /// Whether the gas price paid by each transaction is added in a `gas_price` column.
+    pub gas_price: bool,
+    /// Whether each transaction receipt is fetched for an `effective_gas_price` column.
+    pub with_receipts: bool,
+    /// How much of each transaction input is added in an `input` column, when set.
+    pub calldata: Option<Calldata>,
+    /// Prometheus metrics updated while collecting, when exposed.
+    pub metrics: Option<Arc<Metrics>>,
+    /// Label tagging every row in an added `label` column.
+    pub label: Option<String>,
+    /// Chain ID tagging every row in an added `chain_id` column.
+    pub chain_id: Option<u64>,
+    /// Whether per-block event counts are written instead of individual events.
+    pub aggregate_by_block: bool,
+    /// Whether any event left out of the output (a failed chunk, or a log whose
+    /// block or transaction is missing) aborts the run instead of being skipped.
+    pub strict: bool,
+    /// Whether rows are appended to an existing output instead of recreating it.
+    pub append_output: bool,
+    /// Metadata of the output before this collection, updated with its chunks and
+    /// written to the output's sidecar however the collection ends, when set.
+    pub metadata: Option<OutputMetadata>,
+    /// Whether appending to an output collected with another ABI, as recorded in its
+    /// metadata, only warns instead of failing.
+    pub force: bool,
+    /// Token checked at every chunk boundary to stop the run early.
+    pub cancel: Option<CancellationToken>,
+    /// Token abandoning the chunk in progress to stop the run at once.
+    pub hard_stop: Option<CancellationToken>,
+    /// Number of blocks before each new head re-queried when following the chain,
+    /// for logs the node indexed late.
+    pub lookback_overlap: u64,
+    /// Logs already written for the recent blocks, which are left out when their
+    /// blocks are queried again, when set.
+    pub recent_logs: Option<Arc<Mutex<RecentLogs>>>,
+    /// Transform applied to every event before it is written, when set.
+    pub on_event: Option<EventTransform>,
+}
+
+impl CollectorConfig {
+    /// Returns the CSV columns written for the enabled options.
+    pub fn columns(&self) -> ColumnSet {
+        ColumnSet::default()
+            .with(Column::BlockNumber, self.no_enrich)
+            .with(Column::LogIndex, self.no_enrich)
+            .without(Column::TxOrigin, self.no_enrich)
+            .without(Column::Timestamp, self.no_enrich)
+            .with(Column::LogData, self.raw)
+            .with(Column::LogTopics, self.raw)
+            .with(Column::DirectCaller, self.include_internal)
+            .with(Column::Label, self.label.is_some())
+            .with(Column::ChainId, self.chain_id.is_some())
+            .with(Column::GasPrice, self.gas_price)
+            .with(Column::EffectiveGasPrice, self.with_receipts)
+            .with(Column::Input, self.calldata.is_some())
+    }
+
+    /// Returns whether events are decoded into their `args`, for the outputs using them.
+    pub fn decodes_args(&self) -> bool {
+        self.format.includes_args() || self.flatten_args || self.postgres_url.is_some()
+    }
+}
+
+impl Default for CollectorConfig {
+    fn default() -> Self {
+        CollectorConfig {
+            ws_rpc_url: String::new(),
+            fallback_rpc_urls: Vec::new(),
+            rpc_headers: Vec::new(),
+            contract_address: constants::DEFAULT_CONTRACT_ADDRESS.to_string(),
+            from_block: 0,
+            to_block: 0,
+            block_hash: None,
+            confirmations: 0,
+            latest_block: None,
+            enrich_concurrency: 8,
+            chunk_size: 1_000_000,
+            max_block_span: None,
+            request_delay_ms: 500,
+            rate_limiter: None,
+            event_type: constants::ALL_EVENTS.to_string(),
+            topic0: Vec::new(),
+            abi: AbiSource::default(),
+            sampling: Sampling::All,
+            seed: None,
+            dedup_key: DedupKey::None,
+            one_per_tx: None,
+            value_range: ValueRange::default(),
+            format: OutputFormat::Csv,
+            array_streaming: false,
+            compact_addresses: false,
+            max_in_flight_bytes: None,
+            csv_dialect: CsvDialect::default(),
+            partition: None,
+            postgres_url: None,
+            webhook: None,
+            head: None,
+            tail: None,
+            flatten_args: false,
+            indexed_addresses: false,
+            filename: constants::OUTPUT_FILE_PATH.to_string(),
+            order: BlockOrder::Asc,
+            pretty: true,
+            checkpoint_path: None,
+            reconcile_checkpoint: false,
+            error_log_path: None,
+            ranges: Vec::new(),
+            raw: false,
+            fsync: false,
+            timing: false,
+            dump_filter: false,
+            include_internal: false,
+            no_enrich: false,
+            gas_price: false,
+            with_receipts: false,
+            calldata: None,
+            metrics: None,
+            label: None,
+            chain_id: None,
+            aggregate_by_block: false,
+            strict: false,
+            append_output: false,
+            metadata: None,
+            force: false,
+            cancel: None,
+            hard_stop: None,
+            lookback_overlap: 0,
+            recent_logs: None,
+            on_event: None,
+        }
+    }
+}
+
+/// Summary of a completed collection run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CollectionStats {
+    /// Number of events written to the output.
+    pub total_events: u64,
+    /// Number of chunks whose logs were fetched successfully.
+    pub chunks_processed: u64,
+    /// Number of chunks whose `get_logs` call failed.
+    pub failed_chunks: u64,
+    /// Number of logs left out because fetching their block or transaction failed.
+    pub failed_enrichments: u64,
+    /// Number of logs left out because they were not mined yet, so had no block
+    /// number or transaction hash.
+    pub pending_logs: u64,
+    /// Number of events left out because the value of their transaction is outside
+    /// `--min-value` and `--max-value`.
+    pub value_filtered: u64,
+    /// Number of events written to the output by the earlier runs resumed from a
+    /// checkpoint, not counted in `total_events`.
+    pub resumed_events: u64,
+    /// Whether the run was stopped early through its cancellation token.
+    pub cancelled: bool,
+    /// Durations of the chunks, gathered when timing is enabled.
+    pub chunk_durations: ChunkDurations,
+}
+
+impl CollectionStats {
+    /// Adds the counts of another run, e.g. of the next contract in a list.
+    pub fn merge(&mut self, other: &CollectionStats) {
+        self.total_events += other.total_events;
+        self.chunks_processed += other.chunks_processed;
+        self.failed_chunks += other.failed_chunks;
+        self.failed_enrichments += other.failed_enrichments;
+        self.pending_logs += other.pending_logs;
+        self.value_filtered += other.value_filtered;
+        self.resumed_events += other.resumed_events;
+        self.cancelled |= other.cancelled;
+        self.chunk_durations.merge(&other.chunk_durations);
+    }
+
+    /// Returns whether a completed scan of `scanned_blocks` blocks found so few
+    /// events that a misconfiguration is more likely than a quiet contract.
+    pub fn is_implausibly_empty(&self, scanned_blocks: u64) -> bool {
+        self.total_events == 0
+            && !self.cancelled
+            && self.chunks_processed > 0
+            && scanned_blocks >= constants::EMPTY_RANGE_WARNING_BLOCKS
+    }
+}
+
+/// Fetches order events within a specified block range.
+pub async fn collect_order_events(
+    config: &CollectorConfig,
+) -> Result<CollectionStats, Box<dyn Error>> {
+    collect_order_events_from(config, pooled_providers(config)?).await
+}
+
+/// Builds the pool of providers for the primary and fallback RPC URLs of `config`.
+pub fn pooled_providers(config: &CollectorConfig) -> Result<PooledProviders, CollectorError> {
+    let rpc_urls = std::iter::once(config.ws_rpc_url.clone())
+        .chain(config.fallback_rpc_urls.iter().cloned())
+        .collect();
+    Ok(PooledProviders::new(ProviderPool::new(
+        rpc_urls,
+        constants::PROVIDER_COOLDOWN_CHUNKS,
+    )?)
+    .with_headers(config.rpc_headers.clone()))
+}
+
+/// Fetches order events within a specified block range from an existing pool of
+/// sources, ignoring the RPC URLs of `config`.
+///
+/// When `config.cancel` is triggered, the chunk in progress is completed and
+/// written, and the statistics gathered so far are returned without error. When
+/// `config.hard_stop` is triggered, the chunk in progress is abandoned and the
+/// collection fails with [`CollectorError::Interrupted`], after the output and its
+/// metadata are closed as for any other error.
+pub async fn collect_order_events_from(
+    config: &CollectorConfig,
+    providers: PooledProviders,
+) -> Result<CollectionStats, Box<dyn Error>> {
+    let collection = collect_chunks(config, providers);
+    match &config.hard_stop {
+        // Dropping the collection closes the sink and writes the metadata
+        Some(hard_stop) => tokio::select! {
+            stats = collection => stats,
+            _ = hard_stop.cancelled() => Err(CollectorError::Interrupted.into()),
+        },
+        None => collection.await,
+    }
+}
+
+/// Runs the collection of [`collect_order_events_from`], chunk by chunk.
+async fn collect_chunks(
+    config: &CollectorConfig,
+    mut providers: PooledProviders,
+) -> Result<CollectionStats, Box<dyn Error>> {
+    let contract_addr: H160 = config.contract_address.parse().map_err(|_| {
+        CollectorError::InvalidArgs(format!(
+            "Invalid contract address: {}",
+            config.contract_address
+        ))
+    })?;
+
+    // A reversed range would scan nothing and leave an empty output behind
+    if config.block_hash.is_none() && config.from_block > config.to_block {
+        return Err(CollectorError::InvalidArgs(format!(
+            "Start block {} is after end block {}; did you swap --from-block and --to-block?",
+            config.from_block, config.to_block
+        ))
+        .into());
+    }
+    let idx = providers.connect().await?;
+
+    // Keep the range within the chain, so no chunk queries blocks that do not exist yet
+    let SafeLatestBlock {
+        latest: latest_block,
+        safe,
+    } = match config.latest_block {
+        Some(latest) => SafeLatestBlock {
+            latest,
+            safe: safe_block(latest, config.confirmations, config.from_block),
+        },
+        None => {
+            safe_latest_block(
+                providers.provider(idx),
+                config.confirmations,
+                config.from_block,
+            )
+            .await?
+        }
+    };
+    if config.from_block > latest_block {
+        return Err(CollectorError::InvalidArgs(format!(
+            "Start block {} is beyond the latest block {}",
+            config.from_block, latest_block
+        ))
+        .into());
+    }
+    if config.to_block > latest_block {
+        warn!(
+            "End block {} is beyond the latest block {}, clamping to it",
+            config.to_block, latest_block
+        );
+    }
+
+    // Leave the most recent blocks, which may still be reorged, out of the scan
+    let Some(confirmed_block) = safe else {
+        return Err(CollectorError::InvalidArgs(format!(
+            "Start block {} is within {} confirmations of the latest block {}",
+            config.from_block, config.confirmations, latest_block
+        ))
+        .into());
+    };
+    let to_block = config.to_block.min(confirmed_block);
+    if config.confirmations > 0 && to_block < config.to_block.min(latest_block) {
+        info!(
+            "End block adjusted to {} to keep {} confirmations",
+            to_block, config.confirmations
+        );
+    }
+
+    // Raw signatures need no ABI; their events are labelled by the signature itself
+    let (event_names, abi) = if config.topic0.is_empty() {
+        let abi = load_abi(&config.abi)?;
+        check_event_ambiguities(&abi, &config.event_type, config.strict)?;
+        (get_event_names(&abi, &config.event_type)?, Some(abi))
+    } else {
+        if !selects_all_events(&config.event_type) {
+            check_topic0_consistency(&load_abi(&config.abi)?, &config.event_type, &config.topic0)?;
+        }
+        info!("Collecting events with topic0: {:?}", config.topic0);
+        (raw_event_names(&config.topic0), None)
+    };
+    let event_signatures: Vec<H256> = event_names.keys().copied().collect();
+    require_signatures(&event_signatures)?;
+
+    // Aggregation tells the events apart by signature, which needs the ABI
+    let aggregate_signatures = match (config.aggregate_by_block, &abi) {
+        (false, _) => None,
+        (true, Some(abi)) => Some((
+            abi.event(constants::TAKEORDER_EVENT_NAME)?.signature(),
+            abi.event(constants::CLEAR_EVENT_NAME)?.signature(),
+        )),
+        (true, None) => {
+            return Err(CollectorError::InvalidArgs(
+                "--aggregate-by-block cannot be combined with --topic0".to_string(),
+            )
+            .into())
+        }
+    };
+
+    let filename = config.filename.as_str();
+    let num = |value: u64| fmt_int(value, config.pretty);
+    let mut stats = CollectionStats::default();
+
+    let mut checkpoint = match &config.checkpoint_path {
+        Some(path) => Some(Checkpoint::load(path)?),
+        None => None,
+    };
+
+    // A crash between writing a chunk and recording it leaves the two disagreeing
+    if let Some(checkpoint) = checkpoint.as_mut().filter(|_| config.reconcile_checkpoint) {
+        let reconciliation = checkpoint.reconcile(
+            std::path::Path::new(filename),
+            config.format,
+            config.csv_dialect,
+        )?;
+        if reconciliation.is_consistent() {
+            info!(
+                "Checkpoint matches the {} rows of {}",
+                num(reconciliation.output_rows),
+                filename
+            );
+        } else {
+            warn!(
+                "Checkpoint disagreed with the {} rows of {}: rewound {} recorded chunks, cut {} unrecorded rows and {} bytes in total",
+                num(reconciliation.output_rows),
+                filename,
+                num(reconciliation.rewound_ranges as u64),
+                num(reconciliation.dropped_rows),
+                num(reconciliation.truncated_bytes),
+            );
+        }
+    }
+
+    // The chunks of a checkpoint are in its output file; without it they would be
+    // skipped and missing from the new output, so they are collected again
+    let output_missing = config.partition.is_none()
+        && config.postgres_url.is_none()
+        && !std::path::Path::new(filename).exists();
+    if let Some(checkpoint) = checkpoint
+        .as_mut()
+        .filter(|cp| !cp.is_empty() && output_missing)
+    {
+        warn!(
+            "Output {} of the checkpoint is missing; ignoring its {} recorded chunks",
+            filename,
+            num(checkpoint.ranges().len() as u64),
+        );
+        checkpoint.clear()?;
+    }
+
+    // Initialize the output once before appending, unless resuming from a checkpoint
+    let resuming = checkpoint.as_ref().is_some_and(|cp| !cp.is_empty())
+        && (config.partition.is_some() || std::path::Path::new(filename).exists());
+    if resuming {
+        info!("Resuming into {} from checkpoint", filename);
+    }
+    let append = resuming || config.append_output;
+
+    // Appending with another ABI would mix rows of different shapes in one output
+    let schema = OutputSchema::new(abi.as_ref(), &event_signatures)?;
+    if append && config.metadata.is_some() {
+        check_schema(&metadata_path(filename), &schema, config.force)?;
+    }
+
+    // Indexed addresses and flattened arguments get a column for every matching
+    // parameter of the collected events
+    let mut columns = config.columns();
+    if let Some(abi) = &abi {
+        let events = collected_abi_events(abi, &config.event_type)?;
+        if config.indexed_addresses {
+            let names = indexed_address_names(&events);
+            columns = columns.with_topics(names.iter().map(String::as_str));
+        }
+        if config.flatten_args {
+            let names = param_names(&events);
+            columns = columns.with_args(names.iter().map(String::as_str));
+        }
+    }
+    let mut sink: Box<dyn EventSink + '_> = match config.partition {
+        Some(partitioning) => Box::new(PartitionedSink::new(
+            filename,
+            partitioning,
+            append,
+            Box::new(|path: &str, append: bool| open_sink(config, &columns, path, append)),
+        )),
+        None => open_sink(config, &columns, filename, append)?,
+    };
+    if let Some(tail) = config.tail {
+        sink = Box::new(TailSink::new(sink, tail as usize));
+    }
+    if let Some(webhook) = &config.webhook {
+        sink = Box::new(TeeSink::new(sink, Box::new(webhook.sink())));
+    }
+
+    // Describe the output even if the collection is cancelled or aborted from here on
+    let mut metadata = config.metadata.clone().map(|mut metadata| {
+        metadata.schema = Some(schema);
+        MetadataGuard::new(metadata_path(filename), metadata)
+    });
+
+    // Sampling and trace support carry over from one chunk to the next
+    let mut run = RunState::new(config);
+    let mut dedup = Deduplicator::new(config.dedup_key);
+
+    match config.block_hash {
+        Some(hash) => info!(
+            "Collecting Event data from block {:?} for {} contract",
+            hash, config.contract_address,
+        ),
+        None => info!(
+            "Collecting Event data from {} to {} with chunk size of {} for {} contract",
+            num(config.from_block),
+            num(to_block),
+            num(config.chunk_size),
+            config.contract_address,
+        ),
+    }
+    if let Some(span) = config.max_block_span {
+        if config.block_hash.is_none() && config.chunk_size > span {
+            info!(
+                "Chunk size of {} exceeds the provider's get_logs span of {} blocks; each chunk \
+                 is queried in spans of {} blocks",
+                num(config.chunk_size),
+                num(span),
+                num(span),
+            );
+        }
+    }
+    let chunks: Vec<(u64, u64)> = if config.block_hash.is_some() {
+        // A single query by hash, reported under the whole range
+        vec![(config.from_block, to_block)]
+    } else if config.ranges.is_empty() {
+        BlockChunks::new(config.from_block, to_block, config.chunk_size, config.order).collect()
+    } else {
+        // Each range is chunked on its own, within the scanned range
+        let mut ranges: Vec<(u64, u64)> = config
+            .ranges
+            .iter()
+            .filter(|(from, _)| *from <= to_block)
+            .map(|&(from, to)| (from, to.min(to_block)))
+            .collect();
+        if config.order == BlockOrder::Desc {
+            ranges.reverse();
+        }
+        ranges
+            .into_iter()
+            .flat_map(|(from, to)| BlockChunks::new(from, to, config.chunk_size, config.order))
+            .collect()
+    };
+
+    // A resumed run reports its progress through the whole range, not just the chunks left
+    let chunk_blocks = |&(from, to): &(u64, u64)| to - from + 1;
+    let total_blocks: u64 = chunks.iter().map(chunk_blocks).sum();
+    let start = match &checkpoint {
+        Some(cp) if resuming => ProgressStart {
+            blocks: chunks
+                .iter()
+                .filter(|&&(from, to)| cp.contains(from, to))
+                .map(chunk_blocks)
+                .sum(),
+            events: cp.events_within(config.from_block, to_block),
+        },
+        _ => ProgressStart::default(),
+    };
+    if start.blocks > 0 {
+        info!(
+            "Resuming with {} of {} blocks and {} events already collected",
+            num(start.blocks),
+            num(total_blocks),
+            num(start.events),
+        );
+    }
+    stats.resumed_events = start.events;
+    let mut progress = ProgressTracker::new(total_blocks, start);
+
+    // Logged once: later chunks only differ in their block range
+    let mut filter_dumped = false;
+    // Set once --head events are written, leaving the rest of the range unscanned
+    let mut head_reached = false;
+    for (start_block, end_block) in chunks {
+        if config
+            .cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.is_cancelled())
+        {
+            info!(
+                "Collection cancelled before blocks {} to {}",
+                num(start_block),
+                num(end_block),
+            );
+            stats.cancelled = true;
+            break;
+        }
+        if config.head.is_some_and(|head| stats.total_events >= head) {
+            info!(
+                "Stopping before blocks {} to {}: the first {} events are written",
+                num(start_block),
+                num(end_block),
+                num(stats.total_events),
+            );
+            head_reached = true;
+            break;
+        }
+
+        if checkpoint
+            .as_ref()
+            .is_some_and(|cp| cp.contains(start_block, end_block))
+        {
+            info!(
+                "    Skipping completed chunk {} to {}",
+                num(start_block),
+                num(end_block),
+            );
+            continue;
+        }
 
         info!(
             "    Collecting Event data from {} to {}",
-            start_block, end_block,
+            num(start_block),
+            num(end_block),
+        );
+        let filter = logs_filter(
+            contract_addr,
+            event_signatures.clone(),
+            config.block_hash,
+            start_block,
+            end_block,
         );
-        let filter = Filter::new()
-            .address(contract_addr)
-            .topic0(event_signatures.clone())
-            .from_block(BlockNumber::Number(U64::from(start_block)))
-            .to_block(BlockNumber::Number(U64::from(end_block)));
+        if config.dump_filter && !filter_dumped {
+            info!("eth_getLogs filter: {}", filter_json(&filter)?);
+            filter_dumped = true;
+        }
 
         let mut events = Vec::new(); // Clear events per chunk
+        let mut chunk_events = 0;
+        let chunk_started = Instant::now();
+        let mut logs_returned = 0;
+        let mut get_logs_duration = Duration::ZERO;
+        let mut enrichment_duration = Duration::ZERO;
+
+        // Try each provider of the pool at most once for this chunk
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let idx = providers.connect().await?;
+            let provider = providers.provider(idx);
+
+            throttle(config).await;
+            let started = Instant::now();
+            let logs = span_capped_logs(provider, &filter, start_block, end_block, config).await;
+
+            match logs {
+                Ok(mut logs) => {
+                    get_logs_duration = started.elapsed();
+                    logs_returned = logs.len();
+
+                    // Logs of pending blocks cannot be enriched, so they are reported and left out
+                    let pending = take_pending_logs(&mut logs);
+                    if pending > 0 {
+                        if config.strict {
+                            return Err(CollectorError::Incomplete(format!(
+                                "{} logs between blocks {} and {} are pending, without a block \
+                                 number or transaction hash",
+                                pending, start_block, end_block
+                            ))
+                            .into());
+                        }
+                        warn!(
+                            "    Skipping {} pending logs without a block number or transaction hash",
+                            num(pending)
+                        );
+                        stats.pending_logs += pending;
+                    }
+                    let enrichment_started = Instant::now();
+                    chunk_events = match aggregate_signatures {
+                        Some((take_order, clear)) => {
+                            // Only blocks are fetched; no transaction is needed for counts
+                            let blocks = aggregate_logs(&logs, take_order, clear);
+                            let expected = blocks.len();
+                            let (aggregates, failed) = with_timestamps(
+                                provider,
+                                blocks,
+                                config.enrich_concurrency,
+                                config.rate_limiter.as_deref(),
+                            )
+                            .await;
+                            enrichment_duration = enrichment_started.elapsed();
+                            if config.strict && aggregates.len() < expected {
+                                return Err(CollectorError::Incomplete(format!(
+                                    "{} blocks between {} and {} could not be fetched",
+                                    expected - aggregates.len(),
+                                    start_block,
+                                    end_block
+                                ))
+                                .into());
+                            }
+                            stats.failed_enrichments += failed;
+                            if !aggregates.is_empty() {
+                                sink.write_block_aggregates(&aggregates)?;
+                            }
+
+                            let take_orders = aggregates.iter().map(|a| a.take_order_count).sum();
+                            let clears = aggregates.iter().map(|a| a.clear_count).sum();
+                            if let Some(metrics) = &config.metrics {
+                                metrics
+                                    .events_collected
+                                    .with_label_values(&[constants::TAKEORDER_EVENT_NAME])
+                                    .inc_by(take_orders);
+                                metrics
+                                    .events_collected
+                                    .with_label_values(&[constants::CLEAR_EVENT_NAME])
+                                    .inc_by(clears);
+                            }
+                            take_orders + clears
+                        }
+                        None => {
+                            stats.failed_enrichments += process_logs(
+                                provider,
+                                logs,
+                                &event_names,
+                                abi.as_ref(),
+                                config,
+                                &mut run,
+                                &mut events,
+                            )
+                            .await?;
+                            stats.value_filtered = run.value_filtered;
+                            enrichment_duration = enrichment_started.elapsed();
+                            if let Some(one_per_tx) = config.one_per_tx {
+                                one_per_tx.reduce(&mut events);
+                            }
+                            // Deduplicate before the sink routes events to their partition
+                            events.retain(|event| dedup.keep(event));
+                            // Leave out the logs of re-queried blocks written before
+                            if let Some(recent_logs) = &config.recent_logs {
+                                let mut recent_logs =
+                                    recent_logs.lock().unwrap_or_else(PoisonError::into_inner);
+                                events.retain(|event| recent_logs.keep(event));
+                            }
+                            // Write no more events than --head has left
+                            if let Some(head) = config.head {
+                                let left = head.saturating_sub(stats.total_events);
+                                if events.len() as u64 > left {
+                                    events.truncate(left as usize);
+                                    head_reached = true;
+                                }
+                            }
 
-        match provider.get_logs(&filter).await {
-            Ok(logs) => {
-                process_logs(&provider, logs, take_order_event, clear_event, &mut events).await;
+                            // Append chunk data to CSV
+                            if !events.is_empty() {
+                                sink.write_events(&events)?;
+                            }
+                            if let Some(metrics) = &config.metrics {
+                                for event in &events {
+                                    metrics
+                                        .events_collected
+                                        .with_label_values(&[&event.event_type])
+                                        .inc();
+                                }
+                            }
+                            events.len() as u64
+                        }
+                    };
+                    providers.record_success(idx);
 
-                // Append chunk data to CSV
-                if !events.is_empty() {
-                    write_to_csv(filename, &events)?;
+                    if let Some(checkpoint) = checkpoint.as_mut() {
+                        checkpoint.save(start_block, end_block, chunk_events)?;
+                    }
+                    stats.chunks_processed += 1;
+                    stats.total_events += chunk_events;
+                    if let Some(metrics) = &config.metrics {
+                        metrics.chunks_processed.inc();
+                        metrics.current_block.set(end_block as i64);
+                    }
+                    break;
+                }
+                Err(e) => {
+                    providers.record_failure(idx);
+                    let e = classify_source_error(&e);
+                    error!(
+                        "Error fetching logs for blocks {} to {} from RPC provider #{}: {}",
+                        num(start_block),
+                        num(end_block),
+                        idx,
+                        e
+                    );
+                    if attempts >= providers.len() {
+                        if config.strict {
+                            return Err(CollectorError::Incomplete(format!(
+                                "the logs of blocks {} to {} could not be fetched from any \
+                                 provider: {}",
+                                start_block, end_block, e
+                            ))
+                            .into());
+                        }
+                        stats.failed_chunks += 1;
+                        // A failed query by block hash has no range to retry
+                        if let (Some(path), None) = (&config.error_log_path, config.block_hash) {
+                            record_failed_chunk(
+                                path,
+                                &FailedChunk::new(
+                                    &config.contract_address,
+                                    config.label.clone(),
+                                    start_block,
+                                    end_block,
+                                    e.to_string(),
+                                ),
+                            )?;
+                        }
+                        break;
+                    }
+                    warn!("    Failing over to the next RPC provider");
                 }
-            }
-            Err(e) => {
-                error!(
-                    "Error fetching logs for blocks {} to {}: {:?}",
-                    start_block, end_block, e
-                );
             }
         }
 
-        info!(
-            "    Ending Event data from {} to {}",
-            start_block, end_block,
+        // Emitted through `tracing` so JSON logs carry the chunk range and count as fields
+        tracing::info!(
+            from_block = start_block,
+            to_block = end_block,
+            events = chunk_events,
+            "    Ending Event data from {} to {} ({} events)",
+            num(start_block),
+            num(end_block),
+            num(chunk_events),
         );
-        start_block = end_block + 1;
-        sleep(Duration::from_millis(500)).await; // Avoid rate limits
+        if let Some(metadata) = metadata.as_mut() {
+            metadata.record_chunk(end_block, chunk_events);
+        }
+        // Buffered outputs grow with every chunk until the run finishes
+        if let Some(max_bytes) = config.max_in_flight_bytes {
+            check_buffered_bytes(sink.as_ref(), max_bytes)?;
+        }
+        progress.record(end_block - start_block + 1, chunk_events);
+        if config.block_hash.is_none() {
+            let report = progress.report();
+            info!(
+                "    Progress: {:.1}% ({} of {} blocks), {} events, ETA {}",
+                report.percent,
+                num(report.blocks),
+                num(report.total_blocks),
+                num(report.events),
+                report
+                    .eta
+                    .map_or_else(|| "unknown".to_string(), |eta| format!("{:.0?}", eta)),
+            );
+        }
+        if config.timing {
+            let chunk_duration = chunk_started.elapsed();
+            stats.chunk_durations.record(chunk_duration);
+            tracing::info!(
+                blocks = end_block - start_block + 1,
+                logs = logs_returned,
+                get_logs_ms = get_logs_duration.as_millis() as u64,
+                enrichment_ms = enrichment_duration.as_millis() as u64,
+                rows = chunk_events,
+                duration_ms = chunk_duration.as_millis() as u64,
+                "    Timing: {} blocks, {} logs, get_logs {:.2?}, enrichment {:.2?}, {} rows \
+                 written, {:.2?} in total",
+                num(end_block - start_block + 1),
+                num(logs_returned as u64),
+                get_logs_duration,
+                enrichment_duration,
+                num(chunk_events),
+                chunk_duration,
+            );
+        }
+
+        // Avoid rate limits, waking up early on cancellation
+        match &config.cancel {
+            Some(cancel) => tokio::select! {
+                _ = cancel.cancelled() => {}
+                _ = sleep(Duration::from_millis(config.request_delay_ms)) => {}
+            },
+            None => sleep(Duration::from_millis(config.request_delay_ms)).await,
+        }
     }
 
     info!(
         "Ending Event data from {} to {} with chunk size of {} for {} contract",
-        from_block, to_block, chunk_size, contract_address,
+        num(config.from_block),
+        num(to_block),
+        num(config.chunk_size),
+        config.contract_address,
     );
+    sink.finish()?;
     info!("✅ Data exported successfully!");
-    Ok(())
+    if !stats.cancelled && !head_reached {
+        if let Some(metadata) = metadata.as_mut() {
+            metadata.complete();
+        }
+    }
+
+    let scanned_blocks = (to_block + 1).saturating_sub(config.from_block);
+    if config.block_hash.is_none() && stats.is_implausibly_empty(scanned_blocks) {
+        warn!(
+            "⚠️  No events found in {} blocks for contract {}. This usually means a wrong \
+             network (--network / --rpc-url), a wrong contract address, or an ABI / --event \
+             selection that does not match the events the contract emits.",
+            num(scanned_blocks),
+            config.contract_address,
+        );
+    }
+    Ok(stats)
+}
+
+/// Opens the output file `filename` in the configured format, appending to it
+/// when resuming or adding to a shared output.
+fn open_sink(
+    config: &CollectorConfig,
+    columns: &ColumnSet,
+    filename: &str,
+    append: bool,
+) -> Result<Box<dyn EventSink>, Box<dyn Error>> {
+    if let Some(url) = &config.postgres_url {
+        return Ok(Box::new(PostgresSink::open(
+            url,
+            constants::POSTGRES_TABLE,
+        )?));
+    }
+    if config.aggregate_by_block {
+        let sink = if append {
+            BlockAggregateSink::append(filename, config.fsync)?
+        } else {
+            BlockAggregateSink::create(filename, config.fsync)?
+        };
+        return Ok(Box::new(sink));
+    }
+
+    Ok(match config.format {
+        OutputFormat::Csv => Box::new(open_csv_sink(config, columns, filename, append)?),
+        OutputFormat::Avro if append => Box::new(AvroSink::append(
+            filename,
+            config.fsync,
+            config.compact_addresses,
+        )?),
+        OutputFormat::Avro => Box::new(AvroSink::create(
+            filename,
+            config.fsync,
+            config.compact_addresses,
+        )?),
+        OutputFormat::Table => Box::new(TableSink::new(columns.clone(), constants::TABLE_MAX_ROWS)),
+        OutputFormat::Json if config.array_streaming && append => {
+            Box::new(JsonArraySink::append(filename, config.fsync)?)
+        }
+        OutputFormat::Json if config.array_streaming => {
+            Box::new(JsonArraySink::create(filename, config.fsync)?)
+        }
+        format if append => Box::new(JsonSink::append(filename, format, config.fsync)?),
+        format => Box::new(JsonSink::create(filename, format, config.fsync)?),
+    })
+}
+
+/// Opens the CSV output `filename` with `columns`, checking the header of existing
+/// files.
+fn open_csv_sink(
+    config: &CollectorConfig,
+    columns: &ColumnSet,
+    filename: &str,
+    append: bool,
+) -> Result<CsvSink, Box<dyn Error>> {
+    let dialect = config.csv_dialect;
+    if !append {
+        return CsvSink::create(filename, columns.clone(), dialect, config.fsync);
+    }
+
+    // Drop a row cut short by an interrupted run before appending after it
+    let removed = repair_csv(filename, dialect)?;
+    if removed > 0 {
+        warn!(
+            "Removed an incomplete final row ({} bytes) from {}",
+            removed, filename
+        );
+    }
+    if !dialect.has_header {
+        // Without a header, the rows are assumed to hold the columns of this run
+        return CsvSink::append_with_columns(filename, columns.clone(), dialect, config.fsync);
+    }
+    let sink = CsvSink::append(filename, dialect, config.fsync)?;
+    if sink.columns() != columns {
+        return Err(CollectorError::InvalidArgs(format!(
+            "{} has the columns {:?}, but this run writes {:?}",
+            filename,
+            sink.columns().header(),
+            columns.header()
+        ))
+        .into());
+    }
+    Ok(sink)
+}
+
+/// Fetches the logs of `filter`, which spans `from_block` to `to_block`, in calls
+/// of at most `config.max_block_span` blocks when the range is wider. Every call
+/// but the first waits on the rate limit, like separate chunks would, and each call
+/// is recorded in the metrics.
+async fn span_capped_logs(
+    provider: &dyn ChainSource,
+    filter: &Filter,
+    from_block: u64,
+    to_block: u64,
+    config: &CollectorConfig,
+) -> Result<Vec<Log>, SourceError> {
+    let span = match config.max_block_span {
+        Some(span) if filter.get_block_hash().is_none() && to_block - from_block >= span => span,
+        _ => return observed_logs(provider, filter, config).await,
+    };
+    let mut logs = Vec::new();
+    for (idx, (from, to)) in
+        BlockChunks::new(from_block, to_block, span, BlockOrder::Asc).enumerate()
+    {
+        if idx > 0 {
+            throttle(config).await;
+        }
+        let filter = filter
+            .clone()
+            .from_block(BlockNumber::Number(U64::from(from)))
+            .to_block(BlockNumber::Number(U64::from(to)));
+        logs.extend(observed_logs(provider, &filter, config).await?);
+    }
+    Ok(logs)
+}
+
+/// Fetches the logs of `filter` in a single call, recorded in the metrics.
+async fn observed_logs(
+    provider: &dyn ChainSource,
+    filter: &Filter,
+    config: &CollectorConfig,
+) -> Result<Vec<Log>, SourceError> {
+    let started = Instant::now();
+    let logs = provider.logs(filter).await;
+    observe_rpc(config, "get_logs", started, logs.is_ok());
+    logs
 }
 
-/// Processes logs and extracts order event data.
-async fn process_logs(
-    provider: &Provider<Ws>,
+/// Waits for a token of the configured rate limit, if any, before an RPC call.
+async fn throttle(config: &CollectorConfig) {
+    if let Some(rate_limiter) = &config.rate_limiter {
+        rate_limiter.acquire().await;
+    }
+}
+
+/// Records an RPC request in the metrics, when they are enabled.
+fn observe_rpc(config: &CollectorConfig, method: &str, started: Instant, success: bool) {
+    if let Some(metrics) = &config.metrics {
+        metrics.observe_rpc(method, started.elapsed(), success);
+    }
+}
+
+/// Returns the sender of the first call into `contract` among `traces`, which is
+/// the immediate caller of the contract when it was reached through other contracts.
+pub(crate) fn direct_caller(traces: &[Trace], contract: H160) -> Option<H160> {
+    traces.iter().find_map(|trace| match &trace.action {
+        Action::Call(call) if call.to == contract => Some(call.from),
+        _ => None,
+    })
+}
+
+/// Collects and enriches the events selected by `event_type` that `contract`
+/// emitted in block `block`, in log order, decoded with `abi`, e.g. loaded with
+/// [`load_abi`].
+///
+/// The logs go through the same enrichment as those of a scan, with the default
+/// options, except that any log left out fails the call, as with `--strict`.
+pub async fn collect_single_block(
+    provider: &dyn ChainSource,
+    abi: &Abi,
+    contract: H160,
+    block: u64,
+    event_type: &str,
+) -> Result<Vec<OrderEvent>, Box<dyn Error>> {
+    let event_names = get_event_names(abi, event_type)?;
+    let signatures: Vec<H256> = event_names.keys().copied().collect();
+    require_signatures(&signatures)?;
+
+    let logs = provider
+        .logs(&logs_filter(contract, signatures, None, block, block))
+        .await
+        .map_err(|e| {
+            CollectorError::Rpc(format!(
+                "Failed to fetch the logs of block {}: {}",
+                block, e
+            ))
+        })?;
+    let config = CollectorConfig {
+        strict: true,
+        ..Default::default()
+    };
+    let mut events = Vec::new();
+    process_logs(
+        provider,
+        logs,
+        &event_names,
+        Some(abi),
+        &config,
+        &mut RunState::new(&config),
+        &mut events,
+    )
+    .await?;
+    Ok(events)
+}
+
+/// State of a run carried from chunk to chunk by [`process_logs`].
+pub(crate) struct RunState {
+    /// Sampler deciding which logs are kept, continuing across chunks.
+    pub(crate) sampler: Sampler,
+    /// Cleared after the first failed trace, leaving `direct_caller` empty from then on.
+    pub(crate) traces_supported: AtomicBool,
+    /// Number of events left out for their transaction value.
+    pub(crate) value_filtered: u64,
+}
+
+impl RunState {
+    /// Starts the run of `config`, with its sampling and trace lookups.
+    pub(crate) fn new(config: &CollectorConfig) -> Self {
+        RunState {
+            sampler: Sampler::new(config.sampling, config.seed),
+            traces_supported: AtomicBool::new(config.include_internal),
+            value_filtered: 0,
+        }
+    }
+}
+
+/// Processes logs and extracts order event data, enriching up to
+/// `config.enrich_concurrency` logs at a time while keeping the log order.
+///
+/// Returns the number of logs whose enrichment failed; they are left out of
+/// `events` without failing the chunk, unless `config.strict` is set, in which
+/// case the first log left out is returned as an error. Events whose transaction
+/// value is outside `config.value_range` are left out and counted in
+/// `run.value_filtered`.
+pub(crate) async fn process_logs(
+    provider: &dyn ChainSource,
     logs: Vec<Log>,
-    take_order_event: &ethers::abi::Event,
-    _clear_event: &ethers::abi::Event,
+    event_names: &EventNames,
+    abi: Option<&Abi>,
+    config: &CollectorConfig,
+    run: &mut RunState,
     events: &mut Vec<OrderEvent>,
-) {
-    for log in logs {
-        let detected_event = if log.topics[0] == take_order_event.signature() {
-            constants::TAKEORDER_EVENT_NAME
-        } else {
-            constants::CLEAR_EVENT_NAME
+) -> Result<u64, CollectorError> {
+    // Order the logs as on chain, whatever order the provider returned them in, so
+    // that both the sample and the output are identical across runs
+    let mut logs = logs;
+    logs.sort_by_key(|log| (log.block_number, log.log_index));
+
+    // Skip dropped events before spending any request on them
+    let logs: Vec<Log> = logs.into_iter().filter(|_| run.sampler.keep()).collect();
+
+    let semaphore = Semaphore::new(config.enrich_concurrency.max(1));
+    let traces_supported = &run.traces_supported;
+    let results = join_all(logs.iter().map(|log| async {
+        let _permit = semaphore.acquire().await;
+        enrich_log(provider, log, event_names, abi, config, traces_supported).await
+    }))
+    .await;
+
+    let mut failed = 0;
+    for (log, result) in logs.iter().zip(results) {
+        match result {
+            Ok(Some(mut event)) => {
+                // The value is only known once the transaction is fetched
+                if !config.value_range.is_unbounded()
+                    && !event
+                        .value
+                        .is_some_and(|value| config.value_range.contains(value))
+                {
+                    run.value_filtered += 1;
+                    continue;
+                }
+                if let Some(transform) = &config.on_event {
+                    transform.apply(&mut event);
+                }
+                info!("        {}", event);
+                events.push(event);
+            }
+            Ok(None) if config.strict => {
+                return Err(CollectorError::Incomplete(format!(
+                    "log {:?} of block {:?} in transaction {:?} is not of a collected event, or \
+                     its block or transaction is missing",
+                    log.log_index, log.block_number, log.transaction_hash
+                )));
+            }
+            Ok(None) => {}
+            Err(e) if config.strict => {
+                return Err(CollectorError::Incomplete(format!(
+                    "failed to enrich log {:?} of block {:?} in transaction {:?}: {}",
+                    log.log_index, log.block_number, log.transaction_hash, e
+                )));
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to enrich the log of transaction {:?}: {}",
+                    log.transaction_hash, e
+                );
+                failed += 1;
+            }
+        }
+    }
+    Ok(failed)
+}
+
+/// Removes the logs of pending blocks, which have no block number or transaction
+/// hash yet, from `logs` and returns how many there were.
+pub(crate) fn take_pending_logs(logs: &mut Vec<Log>) -> u64 {
+    let before = logs.len();
+    logs.retain(|log| log.block_number.is_some() && log.transaction_hash.is_some());
+    (before - logs.len()) as u64
+}
+
+/// Builds the order event of `log`, named after its topic0 in `event_names`,
+/// fetching its block timestamp, transaction and, when requested, traces. Returns
+/// `None` for logs that are not of a collected event or miss their block or
+/// transaction.
+async fn enrich_log(
+    provider: &dyn ChainSource,
+    log: &Log,
+    event_names: &EventNames,
+    abi: Option<&Abi>,
+    config: &CollectorConfig,
+    traces_supported: &AtomicBool,
+) -> Result<Option<OrderEvent>, SourceError> {
+    // Label strictly by topic0, so a log the filter should not have let through is
+    // left out rather than mislabelled
+    let Some(&topic0) = log.topics.first() else {
+        return Ok(None);
+    };
+    let Some(detected_event) = event_names.get(&topic0).cloned() else {
+        warn!(
+            "Skipping the log of transaction {:?} with unexpected topic0 {:?}",
+            log.transaction_hash, topic0
+        );
+        return Ok(None);
+    };
+
+    let (Some(block_number), Some(txn_hash)) = (log.block_number, log.transaction_hash) else {
+        return Ok(None);
+    };
+
+    // The origin and timestamp cost two requests per log, which dominate the run
+    let (tx_origin, timestamp, gas_price, value, input) = if config.no_enrich {
+        (H160::zero(), 0, None, None, None)
+    } else {
+        throttle(config).await;
+        let started = Instant::now();
+        let timestamp = provider.block_timestamp(block_number.as_u64()).await;
+        observe_rpc(config, "get_block", started, timestamp.is_ok());
+        let Some(timestamp) = timestamp? else {
+            return Ok(None);
         };
 
-        if let Some(block_number) = log.block_number {
-            if let Ok(Some(block)) = provider.get_block(block_number).await {
-                if let Some(txn_hash) = log.transaction_hash {
-                    if let Ok(Some(txn)) = provider.get_transaction(txn_hash).await {
-                        let event = OrderEvent {
-                            tx_origin: txn.from,
-                            event_type: detected_event.to_string(),
-                            txn_hash,
-                            timestamp: block.timestamp.as_u64(),
-                        };
-
-                        info!(
-                            "        Tx Hash: {}  Event Type: {}",
-                            event.txn_hash, event.event_type
-                        );
+        throttle(config).await;
+        let started = Instant::now();
+        let txn = provider.transaction(txn_hash).await;
+        observe_rpc(config, "get_transaction", started, txn.is_ok());
+        let Some(txn) = txn? else {
+            return Ok(None);
+        };
+        let gas_price = config
+            .gas_price
+            .then(|| transaction_gas_price(&txn))
+            .flatten();
+        let input = config.calldata.map(|mode| transaction_calldata(&txn, mode));
+        (txn.from, timestamp, gas_price, Some(txn.value), input)
+    };
+
+    let mut event = OrderEvent {
+        tx_origin,
+        event_type: detected_event,
+        txn_hash,
+        timestamp,
+        block_number: block_number.as_u64(),
+        log_index: log.log_index.unwrap_or_default().low_u64(),
+        label: config.label.clone(),
+        chain_id: config.chain_id,
+        gas_price,
+        value: value.filter(|_| !config.value_range.is_unbounded()),
+        input,
+        ..Default::default()
+    };
 
-                        events.push(event);
+    // Only the receipt tells what an EIP-1559 transaction actually paid
+    if config.with_receipts {
+        throttle(config).await;
+        let started = Instant::now();
+        let receipt = provider.transaction_receipt(txn_hash).await;
+        observe_rpc(config, "get_transaction_receipt", started, receipt.is_ok());
+        let Some(receipt) = receipt? else {
+            return Ok(None);
+        };
+        event.effective_gas_price = effective_gas_price(&receipt, event.gas_price);
+    }
+
+    // Keep the undecoded payload, hex-encoded like the other columns
+    if config.raw {
+        event.raw_data = Some(format!("0x{}", hex::encode(&log.data)));
+        event.raw_topics = Some(
+            log.topics
+                .iter()
+                .map(|topic| format!("{:?}", topic))
+                .collect::<Vec<_>>()
+                .join(";"),
+        );
+    }
+
+    // Keep the column present even when traces are unavailable
+    if config.include_internal {
+        let mut caller = None;
+        if traces_supported.load(Ordering::Relaxed) {
+            throttle(config).await;
+            let started = Instant::now();
+            let traces = provider.transaction_traces(txn_hash).await;
+            observe_rpc(config, "trace_transaction", started, traces.is_ok());
+            match traces {
+                Ok(traces) => caller = direct_caller(&traces, log.address),
+                Err(e) => {
+                    // Only the first failure is reported, as later requests are skipped
+                    if traces_supported.swap(false, Ordering::Relaxed) {
+                        warn!(
+                            "Transaction traces unavailable, leaving direct_caller empty: {}",
+                            e
+                        );
                     }
                 }
             }
         }
+        event.direct_caller = Some(
+            caller
+                .map(|caller| format!("{:?}", caller))
+                .unwrap_or_default(),
+        );
     }
+
+    // Decode the parameters of events known to the ABI, for the outputs using them
+    let abi_event = abi
+        .filter(|_| config.indexed_addresses || config.decodes_args())
+        .and_then(|abi| {
+            abi.events()
+                .find(|candidate| candidate.signature() == topic0)
+        });
+    if let (true, Some(abi_event)) = (config.indexed_addresses, abi_event) {
+        match decode_indexed_addresses(abi_event, log) {
+            Ok(addresses) => event.indexed_addresses = addresses,
+            Err(e) => warn!(
+                "Failed to decode the indexed addresses of {:?}: {}",
+                txn_hash, e
+            ),
+        }
+    }
+    if config.decodes_args() {
+        if let Some(abi_event) = abi_event {
+            match decode_log_args(abi_event, log) {
+                Ok(args) => event.args = Some(args),
+                Err(e) => warn!(
+                    "Failed to decode {} arguments of {:?}: {}",
+                    abi_event.name, txn_hash, e
+                ),
+            }
+        }
+    }
+
+    Ok(Some(event))
 }
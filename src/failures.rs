@@ -0,0 +1,100 @@
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::contracts::ContractEntry;
+
+/// A chunk whose logs could not be fetched from any provider, recorded so a
+/// later `--retry-failed` run can collect exactly its range.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailedChunk {
+    /// Address of the contract the chunk was collected for.
+    pub contract: String,
+    /// Label of the contract, when collected from a contracts file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub from: u64,
+    pub to: u64,
+    /// Error returned by the last provider tried.
+    pub error: String,
+    /// Unix time of the failure, in seconds.
+    pub timestamp: u64,
+}
+
+impl FailedChunk {
+    /// Describes the failure of `[from, to]` for `contract`, timestamped now.
+    pub fn new(contract: &str, label: Option<String>, from: u64, to: u64, error: String) -> Self {
+        FailedChunk {
+            contract: contract.to_string(),
+            label,
+            from,
+            to,
+            error,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Returns the path of the error log kept next to the output `filename`, e.g.
+/// `order_events.errors.jsonl` for `order_events.csv`.
+pub fn error_log_path(filename: &str) -> String {
+    Path::new(filename)
+        .with_extension("errors.jsonl")
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Returns the path failures are recorded at while the error log at `path` is being
+/// retried, so the log is only replaced once the retry ends.
+pub fn pending_error_log_path(path: &str) -> String {
+    format!("{}.tmp", path)
+}
+
+/// Appends `failure` durably to the error log at `path` as one JSON line.
+pub fn record_failed_chunk(path: &str, failure: &FailedChunk) -> Result<(), Box<dyn Error>> {
+    let mut file = File::options().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(failure)?)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// Reads the failures recorded in the error log at `path`, in recording order.
+pub fn read_failed_chunks(path: &str) -> Result<Vec<FailedChunk>, Box<dyn Error>> {
+    let failures = fs::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(serde_json::from_str)
+        .collect::<Result<Vec<FailedChunk>, _>>()
+        .map_err(|e| format!("Invalid error log {}: {}", path, e))?;
+    Ok(failures)
+}
+
+/// Returns the contracts with failed chunks, in order of their first failure.
+pub fn failed_contracts(failures: &[FailedChunk]) -> Vec<ContractEntry> {
+    let mut contracts: Vec<ContractEntry> = Vec::new();
+    for failure in failures {
+        if !contracts.iter().any(|c| c.address == failure.contract) {
+            contracts.push(ContractEntry {
+                address: failure.contract.clone(),
+                label: failure.label.clone(),
+            });
+        }
+    }
+    contracts
+}
+
+/// Returns the failed ranges of `contract`, in recording order.
+pub fn failed_ranges(failures: &[FailedChunk], contract: &str) -> Vec<(u64, u64)> {
+    failures
+        .iter()
+        .filter(|failure| failure.contract == contract)
+        .map(|failure| (failure.from, failure.to))
+        .collect()
+}
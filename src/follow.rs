@@ -0,0 +1,207 @@
+use std::error::Error;
+use std::future::Future;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use futures::channel::{mpsc, oneshot};
+use futures::{Stream, StreamExt};
+use log::{info, warn};
+use tokio_util::sync::CancellationToken;
+
+use crate::dedup::RecentLogs;
+use crate::error::CollectorError;
+use crate::event_collector::{collect_order_events_from, CollectionStats, CollectorConfig};
+use crate::metadata::{metadata_path, read_metadata};
+use crate::provider_pool::PooledProviders;
+use crate::rpc_headers::{connect_ws, RpcHeader};
+use crate::utils::{safe_block, safe_latest_block};
+
+/// Next block to collect while following the chain, turning each announced head
+/// into the blocks not collected yet.
+///
+/// Ranges are derived from block numbers rather than from when heads arrive, so
+/// blocks mined between the end of the backfill and the first subscribed head
+/// are collected with that head instead of being lost in the handoff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FollowCursor {
+    next_block: u64,
+}
+
+impl FollowCursor {
+    /// Starts following after the blocks before `next_block`, e.g. those backfilled.
+    pub fn new(next_block: u64) -> Self {
+        FollowCursor { next_block }
+    }
+
+    /// Returns the next block to collect.
+    pub fn next_block(&self) -> u64 {
+        self.next_block
+    }
+
+    /// Returns the blocks up to `head` not collected yet, if any, and marks them
+    /// collected. Heads already covered, e.g. repeated or after a reorg to a
+    /// shorter chain, give none.
+    pub fn advance(&mut self, head: u64) -> Option<(u64, u64)> {
+        if head < self.next_block {
+            return None;
+        }
+        let range = (self.next_block, head);
+        self.next_block = head + 1;
+        Some(range)
+    }
+}
+
+/// Subscribes to the new heads of the WebSocket RPC at `url` and streams their
+/// block numbers, until the connection ends or the stream is dropped.
+pub async fn subscribe_heads(
+    url: &str,
+    headers: &[RpcHeader],
+) -> Result<mpsc::UnboundedReceiver<u64>, Box<dyn Error>> {
+    let provider = connect_ws(url, headers).await?;
+    let (sender, receiver) = mpsc::unbounded();
+    let (subscribed, ready) = oneshot::channel::<Result<(), String>>();
+
+    // The subscription borrows its provider, so both live in the forwarding task
+    tokio::spawn(async move {
+        use ethers::providers::Middleware;
+
+        let mut blocks = match provider.subscribe_blocks().await {
+            Ok(blocks) => {
+                let _ = subscribed.send(Ok(()));
+                blocks
+            }
+            Err(e) => {
+                let _ = subscribed.send(Err(e.to_string()));
+                return;
+            }
+        };
+        while let Some(block) = blocks.next().await {
+            if let Some(number) = block.number {
+                if sender.unbounded_send(number.as_u64()).is_err() {
+                    break;
+                }
+            }
+        }
+        warn!("The new heads subscription ended");
+    });
+
+    ready
+        .await
+        .map_err(|_| "The new heads subscription task stopped")?
+        .map_err(|e| CollectorError::Rpc(format!("Failed to subscribe to new heads: {}", e)))?;
+    Ok(receiver)
+}
+
+/// Backfills the range of `config` up to the latest block, then subscribes to new
+/// heads and keeps collecting the blocks they announce, appending to the output.
+///
+/// The latest block is recorded before the backfill, which ends there; the first
+/// subscribed head then also collects every block mined since, so none is missed
+/// during the handoff. Each collection gets a fresh pool from `providers`, and
+/// `subscribe` is only called once the backfill is written. The blocks of a head
+/// are collected up to that head as announced, even from a provider lagging behind
+/// it.
+///
+/// With `config.lookback_overlap`, each head also re-queries that many blocks
+/// before the new ones, catching logs the node had not indexed yet when their
+/// block was first collected; the logs already written are left out.
+///
+/// Following stops when the heads stream ends or `config.cancel` is triggered.
+pub async fn catch_up_then_follow<S, F>(
+    config: &CollectorConfig,
+    mut providers: impl FnMut() -> Result<PooledProviders, CollectorError>,
+    subscribe: impl FnOnce() -> F,
+) -> Result<CollectionStats, Box<dyn Error>>
+where
+    S: Stream<Item = u64> + Unpin,
+    F: Future<Output = Result<S, Box<dyn Error>>>,
+{
+    // Record the end of the backfill before it starts
+    let mut pool = providers()?;
+    let idx = pool.connect().await?;
+    let safe = safe_latest_block(pool.provider(idx), config.confirmations, config.from_block)
+        .await?
+        .safe
+        .ok_or_else(|| {
+            CollectorError::InvalidArgs(format!(
+                "Start block {} is not {} blocks deep yet",
+                config.from_block, config.confirmations
+            ))
+        })?;
+    let backfill_end = config.to_block.min(safe);
+    drop(pool);
+
+    let start_block = config.from_block;
+    let mut config = config.clone();
+    config.to_block = backfill_end;
+    // Track the logs written for the blocks the first head will query again
+    let recent_logs = (config.lookback_overlap > 0).then(|| {
+        Arc::new(Mutex::new(RecentLogs::new(
+            (backfill_end + 1).saturating_sub(config.lookback_overlap),
+        )))
+    });
+    config.recent_logs = recent_logs.clone();
+    info!(
+        "Backfilling blocks {} to {} before following new heads",
+        config.from_block, backfill_end
+    );
+    let mut stats = collect_order_events_from(&config, providers()?).await?;
+    if stats.cancelled {
+        return Ok(stats);
+    }
+
+    let mut heads = subscribe().await?;
+    let mut cursor = FollowCursor::new(backfill_end + 1);
+    let cancel = config.cancel.clone().unwrap_or_default();
+    config.append_output = true;
+    while let Some(head) = next_head(&mut heads, &cancel).await {
+        // Only blocks with enough confirmations are collected, like in the backfill
+        let Some((from_block, to_block)) = safe_block(head, config.confirmations, start_block)
+            .and_then(|safe| cursor.advance(safe))
+        else {
+            continue;
+        };
+        if from_block == backfill_end + 1 && to_block > from_block {
+            info!(
+                "Filling the handoff gap: blocks {} to {}",
+                from_block, to_block
+            );
+        }
+
+        // Carry the sidecar over, extended to the new blocks, so it describes the output as a whole
+        if config.metadata.is_some() {
+            let mut metadata = read_metadata(&metadata_path(&config.filename))?;
+            metadata.to_block = to_block;
+            config.metadata = Some(metadata);
+        }
+        // Query the overlapped blocks again, forgetting the logs of older ones
+        let from_block = from_block
+            .saturating_sub(config.lookback_overlap)
+            .max(start_block);
+        if let Some(recent_logs) = &recent_logs {
+            recent_logs
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .forget_before(from_block);
+        }
+        config.from_block = from_block;
+        config.to_block = to_block;
+        config.latest_block = Some(head);
+        let range_stats = collect_order_events_from(&config, providers()?).await?;
+        stats.merge(&range_stats);
+        if range_stats.cancelled {
+            break;
+        }
+    }
+    Ok(stats)
+}
+
+/// Waits for the next head, or `None` once the stream ends or `cancel` fires.
+async fn next_head<S: Stream<Item = u64> + Unpin>(
+    heads: &mut S,
+    cancel: &CancellationToken,
+) -> Option<u64> {
+    tokio::select! {
+        head = heads.next() => head,
+        _ = cancel.cancelled() => None,
+    }
+}
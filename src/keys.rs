@@ -0,0 +1,73 @@
+use crate::cli::CliArgs;
+use crate::constants;
+use crate::error::CollectorError;
+
+/// API keys of the services the collector queries, resolved once per run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Keys {
+    /// Infura key, completing the WebSocket RPC URL of a network.
+    pub infura: Option<String>,
+    /// Etherscan key, used to look up contract creation blocks and ABIs.
+    pub etherscan: Option<String>,
+}
+
+impl Keys {
+    /// Returns the Infura key, or an error naming how to provide it.
+    pub fn infura(&self) -> Result<&str, CollectorError> {
+        require_key(
+            &self.infura,
+            "Infura",
+            "--infura-key",
+            constants::INFURA_API_KEY_VAR,
+        )
+    }
+
+    /// Returns the Etherscan key, or an error naming how to provide it.
+    pub fn etherscan(&self) -> Result<&str, CollectorError> {
+        require_key(
+            &self.etherscan,
+            "Etherscan",
+            "--etherscan-key",
+            constants::ETHERSCAN_API_KEY_VAR,
+        )
+    }
+}
+
+fn require_key<'a>(
+    key: &'a Option<String>,
+    service: &str,
+    flag: &str,
+    var: &str,
+) -> Result<&'a str, CollectorError> {
+    key.as_deref().ok_or_else(|| {
+        CollectorError::InvalidArgs(format!(
+            "no {} API key: pass {} or set {}",
+            service, flag, var
+        ))
+    })
+}
+
+/// Returns the key given on the command line, or else the one from the
+/// environment. Empty values count as absent.
+pub fn pick_key(flag: Option<&str>, env: Option<&str>) -> Option<String> {
+    flag.into_iter()
+        .chain(env)
+        .find(|key| !key.is_empty())
+        .map(str::to_string)
+}
+
+/// Resolves the API keys of the run, preferring `--infura-key` and
+/// `--etherscan-key` to the `INFURA_API_KEY` and `ETHERSCAN_API_KEY` variables.
+pub fn resolve_keys(args: &CliArgs) -> Keys {
+    let env = |var: &str| std::env::var(var).ok();
+    Keys {
+        infura: pick_key(
+            args.infura_key.as_deref(),
+            env(constants::INFURA_API_KEY_VAR).as_deref(),
+        ),
+        etherscan: pick_key(
+            args.etherscan_key.as_deref(),
+            env(constants::ETHERSCAN_API_KEY_VAR).as_deref(),
+        ),
+    }
+}
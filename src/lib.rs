@@ -1,7 +1,49 @@
+pub mod aggregate;
+pub mod analyze;
+pub mod avro;
+pub mod block_tags;
+pub mod checkpoint;
+pub mod chunks;
 pub mod cli;
+pub mod columns;
+pub mod contracts;
+pub mod creation_cache;
 pub mod csv_manager;
+pub mod decode;
+pub mod dedup;
+pub mod doctor;
+pub mod effective_config;
+pub mod ens;
+pub mod error;
 pub mod event_collector;
+pub mod failures;
+pub mod follow;
+pub mod keys;
+pub mod logging;
+pub mod metadata;
+pub mod metrics;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+pub mod order_by;
+pub mod postgres;
+pub mod progress;
+pub mod provider_pool;
+pub mod proxy;
+pub mod ranges;
+pub mod rate_limit;
+pub mod retry;
+pub mod rpc_errors;
+pub mod rpc_headers;
+pub mod sampling;
+pub mod sink;
+pub mod source;
+pub mod summary;
+pub mod table;
+pub mod timing;
+pub mod upload;
 pub mod utils;
+pub mod value_filter;
+pub mod webhook;
 
 pub mod constants;
 
@@ -0,0 +1,53 @@
+use std::error::Error;
+use std::str::FromStr;
+
+use env_logger::Env;
+
+/// Format of the lines emitted by the logger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    /// Human-readable lines (env_logger).
+    #[default]
+    Text,
+    /// One JSON object per line, suitable for log aggregators.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("Unsupported log format: {}", s)),
+        }
+    }
+}
+
+/// Initializes the global logger for the requested format.
+///
+/// Both formats default to the `info` level and honour `RUST_LOG`. In JSON mode
+/// the existing `log` macros are bridged into `tracing` so every line, including
+/// those from dependencies, is emitted as structured JSON with its fields.
+pub fn init_logger(format: LogFormat) -> Result<(), Box<dyn Error>> {
+    match format {
+        LogFormat::Text => {
+            env_logger::Builder::from_env(Env::default().default_filter_or("info")).try_init()?
+        }
+        LogFormat::Json => {
+            let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"));
+
+            // `try_init` also installs the `log` -> `tracing` bridge
+            tracing_subscriber::fmt()
+                .json()
+                .flatten_event(true)
+                .with_current_span(false)
+                .with_env_filter(filter)
+                .try_init()
+                .map_err(|e| e.to_string())?
+        }
+    }
+    Ok(())
+}
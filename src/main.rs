@@ -1,58 +1,826 @@
 use dotenv::dotenv;
-use env_logger::Env;
-use log::info;
+use ethers::types::H160;
+use log::{error, info, warn};
+use std::collections::HashMap;
 use std::error::Error;
+use std::io::IsTerminal;
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
 use trade_data_collector::{
-    cli::parse_cli_args,
+    analyze::{analyze_csv, print_analysis},
+    block_tags::resolve_block_bound,
+    cli::{parse_cli_args, CliArgs, CliCommand},
+    columns::{json_fields, json_schema, Column},
     constants,
-    event_collector::collect_order_events,
-    utils::get_ws_rpc_url,
-    utils::{get_contract_creation_block, get_latest_block_number},
+    contracts::{load_contracts_file, ContractEntry},
+    creation_cache::{creation_block_cache_get, creation_block_cache_put},
+    doctor,
+    effective_config::effective_config,
+    ens::EnsResolver,
+    error::{CollectorError, ExitCode},
+    event_collector::{
+        collect_order_events, load_abi, pooled_providers, AbiSource, CollectionStats,
+        CollectorConfig,
+    },
+    failures::{
+        error_log_path, failed_contracts, failed_ranges, pending_error_log_path, read_failed_chunks,
+    },
+    follow::{catch_up_then_follow, subscribe_heads},
+    keys::{resolve_keys, Keys},
+    logging::init_logger,
+    metadata::{metadata_path, read_metadata, OutputMetadata},
+    metrics::{serve_metrics, Metrics},
+    order_by::sort_output,
+    proxy::resolve_implementation,
+    ranges::load_ranges_file,
+    rate_limit::RateLimiter,
+    retry::RetryPolicy,
+    rpc_headers::connect_ws,
+    sink::{OutputFormat, Partitioning},
+    summary::{render_summary, should_use_color},
+    upload::{output_files, upload_output},
+    utils::{
+        block_at_timestamp, block_of_tx, get_chain_id, get_contract_abi,
+        get_contract_creation_block, get_latest_block_number, last_block_at_or_before, parse_date,
+    },
+    utils::{fmt_int, get_ws_rpc_url, output_filename, OutputNameParams},
+    value_filter::ValueRange,
+    webhook::Webhook,
 };
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
+async fn main() {
     // Load environment variables from `.env` file
     dotenv().ok();
 
-    // Initialize the logger and enable backtrace
-    env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
-    std::env::set_var("RUST_BACKTRACE", "1");
-
     // Parse command-line arguments to determine network and contract details
     let args = parse_cli_args();
 
-    // Retrieve WebSocket RPC URL based on the specified network
-    let ws_rpc_url = get_ws_rpc_url(&args.network)?;
+    // Initialize the logger in the requested format and enable backtrace
+    init_logger(args.log_format).expect("Failed to initialize logger");
+    std::env::set_var("RUST_BACKTRACE", "1");
+
+    let pretty = args.pretty;
+    let color = should_use_color(
+        args.no_color,
+        std::env::var("NO_COLOR").ok().as_deref(),
+        std::io::stdout().is_terminal(),
+    );
+
+    // The flags take precedence over the environment, resolved once for every command
+    let keys = resolve_keys(&args);
+
+    // Translate the run outcome into a documented process exit code
+    let exit_code = match args.command.clone() {
+        CliCommand::Doctor => {
+            if doctor::print_report(&doctor::run_checks(&keys).await) {
+                ExitCode::Success
+            } else {
+                ExitCode::Failure
+            }
+        }
+        CliCommand::ValidateAbi => {
+            let source = args
+                .abi_path
+                .clone()
+                .map(AbiSource::Path)
+                .unwrap_or_default();
+            match load_abi(&source) {
+                Ok(abi) => {
+                    println!("{}", doctor::format_abi_events(&doctor::abi_events(&abi)));
+                    for ambiguity in doctor::event_ambiguities(&abi, &constants::COLLECTED_EVENTS) {
+                        warn!("Ambiguous ABI: {}", ambiguity);
+                    }
+                    let missing = doctor::missing_collected_events(&abi);
+                    if missing.is_empty() {
+                        ExitCode::Success
+                    } else {
+                        error!(
+                            "The ABI does not declare the collected events: {}",
+                            missing.join(", ")
+                        );
+                        ExitCode::Failure
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to load the ABI: {}", e);
+                    ExitCode::from_error(e.as_ref())
+                }
+            }
+        }
+        CliCommand::Analyze { input } => match analyze_csv(&input) {
+            Ok(analysis) => {
+                print_analysis(&analysis, 10);
+                ExitCode::Success
+            }
+            Err(e) => {
+                error!("{}", e);
+                ExitCode::from_error(e.as_ref())
+            }
+        },
+        CliCommand::JsonSchema => {
+            // Only whether a label and a chain ID are written matters to the columns
+            let config = CollectorConfig {
+                raw: args.raw,
+                include_internal: args.include_internal,
+                gas_price: args.gas_price,
+                with_receipts: args.with_receipts,
+                calldata: args.calldata,
+                label: args.contracts_file.as_ref().map(|_| String::new()),
+                chain_id: args
+                    .chain_id_column
+                    .then(|| args.chain_id.unwrap_or_default()),
+                ..Default::default()
+            };
+            match serde_json::to_string_pretty(&json_schema(&config.columns())) {
+                Ok(schema) => {
+                    println!("{}", schema);
+                    ExitCode::Success
+                }
+                Err(e) => {
+                    error!("Failed to print the JSON schema: {}", e);
+                    ExitCode::Failure
+                }
+            }
+        }
+        CliCommand::Collect => match run(args, keys).await {
+            // Only the configuration was printed
+            Ok(None) => ExitCode::Success,
+            Ok(Some(stats)) => {
+                info!(
+                    "Collected {} events ({} chunks, {} failed, {} logs skipped)",
+                    fmt_int(stats.total_events, pretty),
+                    fmt_int(stats.chunks_processed, pretty),
+                    fmt_int(stats.failed_chunks, pretty),
+                    fmt_int(stats.failed_enrichments, pretty),
+                );
+                println!("{}", render_summary(&stats, pretty, color));
+                ExitCode::from_stats(&stats)
+            }
+            Err(e) => {
+                error!("{}", e);
+                ExitCode::from_error(e.as_ref())
+            }
+        },
+    };
+
+    std::process::exit(exit_code.code());
+}
+
+/// Runs a full collection and returns its statistics, or `None` when only its
+/// configuration is printed.
+async fn run(args: CliArgs, keys: Keys) -> Result<Option<CollectionStats>, Box<dyn Error>> {
+    // A table is printed rather than written, so there is no file to split, place or upload
+    let printed = args.format == OutputFormat::Table;
+    if printed && (args.partition.is_some() || args.output_dir.is_some() || args.upload.is_some()) {
+        return Err(CollectorError::InvalidArgs(
+            "--format table cannot be combined with --partition-blocks, --partition-by, --output-dir or --upload"
+                .to_string(),
+        )
+        .into());
+    }
+
+    // The other formats already hold the decoded arguments as a whole
+    if args.flatten_args && !matches!(args.format, OutputFormat::Csv | OutputFormat::Table) {
+        return Err(CollectorError::InvalidArgs(
+            "--flatten-args is only supported with --format csv or table".to_string(),
+        )
+        .into());
+    }
+
+    // The other formats already hold the indexed addresses among the decoded arguments
+    if args.indexed_addresses && !matches!(args.format, OutputFormat::Csv | OutputFormat::Table) {
+        return Err(CollectorError::InvalidArgs(
+            "--indexed-addresses is only supported with --format csv or table".to_string(),
+        )
+        .into());
+    }
+
+    // Only the CSV columns can leave out the origin and timestamp
+    if args.no_enrich && !matches!(args.format, OutputFormat::Csv | OutputFormat::Table) {
+        return Err(CollectorError::InvalidArgs(
+            "--no-enrich is only supported with --format csv or table".to_string(),
+        )
+        .into());
+    }
+
+    // Day partitions are named after the block timestamps, which --no-enrich leaves out
+    if args.no_enrich && args.partition == Some(Partitioning::Day) {
+        return Err(CollectorError::InvalidArgs(
+            "--partition-by day needs the block timestamps, which --no-enrich does not fetch"
+                .to_string(),
+        )
+        .into());
+    }
+
+    // Only line-based outputs have rows the checkpoint can be reconciled with
+    if args.resume_from_checkpoint
+        && !matches!(args.format, OutputFormat::Csv | OutputFormat::Jsonl)
+    {
+        return Err(CollectorError::InvalidArgs(
+            "--resume-from-checkpoint is only supported with --format csv or jsonl".to_string(),
+        )
+        .into());
+    }
 
-    // Fetch the contract creation block using Etherscan API
-    let api_key =
-        std::env::var("ETHERSCAN_API_KEY").expect("ETHERSCAN_API_KEY environment variable not set");
+    // Only line-based outputs are sorted once complete
+    if args.order_by.is_some() && !matches!(args.format, OutputFormat::Csv | OutputFormat::Jsonl) {
+        return Err(CollectorError::InvalidArgs(
+            "--order-by is only supported with --format csv or jsonl".to_string(),
+        )
+        .into());
+    }
 
-    let creation_block = get_contract_creation_block(
-        constants::ETHERSCAN_BASIC_URL,
-        &api_key,
-        &args.contract_address,
-    )?;
+    if args.array_streaming && args.format != OutputFormat::Json {
+        return Err(CollectorError::InvalidArgs(
+            "--array-streaming is only supported with --format json".to_string(),
+        )
+        .into());
+    }
+
+    // A buffered array is only written when the run finishes, so a checkpoint would
+    // record chunks whose events a crash loses
+    if args.checkpoint.is_some() && args.format == OutputFormat::Json && !args.array_streaming {
+        return Err(CollectorError::InvalidArgs(
+            "--checkpoint with --format json needs --array-streaming".to_string(),
+        )
+        .into());
+    }
+
+    // Neither a printed table nor rows inserted into Postgres leave a file to describe
+    let fileless = printed || args.postgres.is_some();
+
+    // Use the explicit RPC URLs when given, otherwise derive one from the network
+    let mut rpc_urls = args.rpc_urls.into_iter();
+    let ws_rpc_url = match rpc_urls.next() {
+        Some(url) => url,
+        None => get_ws_rpc_url(&args.network, keys.infura.as_deref())
+            .map_err(CollectorError::InvalidArgs)?,
+    };
+    let fallback_rpc_urls: Vec<String> = rpc_urls.collect();
+
+    // Re-collect only the chunks recorded in the error log of a previous run when retrying
+    let retries = match &args.retry_failed {
+        Some(path) => Some(read_failed_chunks(path)?),
+        None => None,
+    };
+
+    // Scattered windows of blocks are collected instead of everything since creation
+    let file_ranges = match &args.ranges_file {
+        Some(path) => load_ranges_file(path)?,
+        None => Vec::new(),
+    };
+
+    // Collect from the single contract, or from every entry of the contracts file
+    let contracts = match (&retries, &args.contracts_file) {
+        (Some(failures), _) => failed_contracts(failures),
+        (None, Some(path)) => load_contracts_file(path)?,
+        (None, None) => vec![ContractEntry {
+            address: args.contract_address.clone(),
+            label: None,
+        }],
+    };
+
+    // Contracts given by ENS name are resolved to their address before any scan
+    let mut contracts = contracts;
+    if contracts
+        .iter()
+        .any(|contract| contract.address.parse::<H160>().is_err())
+    {
+        let provider = connect_ws(&ws_rpc_url, &args.rpc_headers).await?;
+        let mut resolver = EnsResolver::default();
+        for contract in &mut contracts {
+            let address = format!(
+                "{:?}",
+                resolver.resolve(&provider, &contract.address).await?
+            );
+            if address != contract.address {
+                info!("Resolved {} to {}", contract.address, address);
+                contract.address = address;
+            }
+        }
+    }
+
+    // The Etherscan API is used to find each contract creation block and proxy ABI
+    let etherscan_retry = RetryPolicy {
+        max_retries: args.max_retries,
+        ..Default::default()
+    };
 
     // Get the latest block number from the Ethereum network
-    let end_block = get_latest_block_number(&ws_rpc_url).await?;
+    let end_block = get_latest_block_number(&ws_rpc_url, &args.rpc_headers).await?;
+    info!("Latest block: {}", fmt_int(end_block, args.pretty));
+
+    // Record which chain the data comes from, and catch an RPC URL serving another network
+    let rpc_chain_id = get_chain_id(&ws_rpc_url, &args.rpc_headers).await?;
+    if let Some(expected) = constants::expected_chain_id(&args.network) {
+        if expected != rpc_chain_id {
+            warn!(
+                "The RPC node serves chain ID {}, but --network {} is chain ID {}",
+                rpc_chain_id, args.network, expected
+            );
+        }
+    }
+    let chain_id = args.chain_id.unwrap_or(rpc_chain_id);
+    if chain_id != rpc_chain_id {
+        info!(
+            "Recording chain ID {} given by --chain-id instead of {}",
+            chain_id, rpc_chain_id
+        );
+    }
+
+    // Convert the optional date bounds into block numbers by bisecting block timestamps
+    let (from_date_block, to_date_block) = if args.from_date.is_some() || args.to_date.is_some() {
+        let provider = connect_ws(&ws_rpc_url, &args.rpc_headers).await?;
+        let from_block = match &args.from_date {
+            Some(date) => Some(
+                block_at_timestamp(&provider, parse_date(date)?)
+                    .await?
+                    .ok_or_else(|| {
+                        CollectorError::InvalidArgs(format!(
+                            "--from-date {} is after the latest block",
+                            date
+                        ))
+                    })?,
+            ),
+            None => None,
+        };
+        let to_block = match &args.to_date {
+            Some(date) => Some(last_block_at_or_before(&provider, parse_date(date)?).await?),
+            None => None,
+        };
+        info!(
+            "Date range resolved to blocks {:?} - {:?}",
+            from_block, to_block
+        );
+        (from_block, to_block)
+    } else {
+        (None, None)
+    };
+
+    // Resolve block tags such as `finalized` once, as chunking needs concrete numbers
+    let (from_tag_block, to_tag_block) = if args.from_block.is_some() || args.to_block.is_some() {
+        let provider = connect_ws(&ws_rpc_url, &args.rpc_headers).await?;
+        let from_block = match args.from_block {
+            Some(bound) => Some(resolve_block_bound(&provider, bound).await?),
+            None => None,
+        };
+        let to_block = match args.to_block {
+            Some(bound) => Some(resolve_block_bound(&provider, bound).await?),
+            None => None,
+        };
+        info!(
+            "Block bounds resolved to blocks {:?} - {:?}",
+            from_block, to_block
+        );
+        (from_block, to_block)
+    } else {
+        (None, None)
+    };
+
+    // Look up the block of the anchor transaction, which must be mined
+    let from_tx_block = match args.from_tx {
+        Some(hash) => {
+            let provider = connect_ws(&ws_rpc_url, &args.rpc_headers).await?;
+            let block = block_of_tx(&provider, hash).await?;
+            info!("Transaction {:?} resolved to block {}", hash, block);
+            Some(block)
+        }
+        None => None,
+    };
+
+    // A date, a block and a transaction bound exclude each other, so at most one of each is
+    // set, and any takes precedence over a known creation block, which leaves the Etherscan
+    // key unneeded
+    let from_bound_block = from_date_block
+        .or(from_tag_block)
+        .or(from_tx_block)
+        .or(args.contract_creation_block);
+    let to_bound_block = to_date_block.or(to_tag_block);
 
-    // Display contract creation and latest block information
-    info!("Contract created at block: {}", creation_block);
-    info!("Latest block: {}", end_block);
+    // The output file takes the extension of the chosen format
+    let filename = std::path::Path::new(constants::OUTPUT_FILE_PATH)
+        .with_extension(args.format.extension())
+        .to_string_lossy()
+        .into_owned();
+
+    // Chunks failing again are recorded afresh next to the retried log, which they only
+    // replace once the retry ends, so its failures survive a run dying before then
+    let mut error_log = error_log_path(&filename);
+    let retrying_own_log = args
+        .retry_failed
+        .as_ref()
+        .is_some_and(|path| std::path::Path::new(path) == std::path::Path::new(&error_log));
+    if retrying_own_log {
+        error_log = pending_error_log_path(&error_log);
+    }
+
+    // Load the ABI from IOrderBookV4.json unless another file, e.g. a proxy's implementation ABI, is given
+    let abi = args
+        .abi_path
+        .clone()
+        .map(AbiSource::Path)
+        .unwrap_or_default();
+
+    // Tune the batches to the network unless given explicitly
+    let chunk_size = args
+        .chunk_size
+        .unwrap_or_else(|| constants::default_chunk_size(&args.network));
+    let max_block_span = args
+        .max_block_span
+        .or_else(|| constants::max_block_span(&args.network));
+    let request_delay_ms = args
+        .delay_ms
+        .unwrap_or_else(|| constants::default_delay_ms(&args.network));
+    let rps_limit = args
+        .rps_limit
+        .unwrap_or_else(|| constants::default_rps_limit(&args.network));
+    let rate_limiter = (rps_limit > 0).then(|| Arc::new(RateLimiter::new(rps_limit)));
+
+    // Events are kept by the value of their transaction only between the given bounds
+    let value_range = ValueRange {
+        min: args.min_value,
+        max: args.max_value,
+    };
+    if let (Some(min), Some(max)) = (value_range.min, value_range.max) {
+        if min > max {
+            return Err(CollectorError::InvalidArgs(format!(
+                "--min-value {} wei is above --max-value {} wei",
+                min, max
+            ))
+            .into());
+        }
+    }
+
+    // Rows carry the chain ID only on request; the metadata sidecar records it regardless
+    let row_chain_id = args.chain_id_column.then_some(chain_id);
+
+    // A crash may have left the output and the checkpoint of a resumed run disagreeing
+    let reconcile_checkpoint = args.resume_from_checkpoint;
 
     // Collect order events within the block range
-    collect_order_events(
-        &ws_rpc_url,                 // WebSocket RPC URL
-        &args.contract_address,      // Target contract address
-        creation_block,              // Start block (contract deployment block)
-        end_block,                   // End block (latest block)
-        1_000_000,                   // Number of blocks to fetch per batch
-        &args.event_type,            // Filter for specific event types (optional)
-        constants::OUTPUT_FILE_PATH, // Output csv file path
-    )
-    .await?;
-
-    Ok(())
+    let mut config = CollectorConfig {
+        ws_rpc_url,                                    // WebSocket RPC URL
+        fallback_rpc_urls,                             // Failover RPC URLs
+        rpc_headers: args.rpc_headers.clone(),         // WebSocket upgrade headers
+        to_block: to_bound_block.unwrap_or(end_block), // End block (to-date, to-block or latest)
+        confirmations: args.confirmations,             // Blocks kept away from the chain tip
+        enrich_concurrency: args.enrich_concurrency,   // Logs enriched concurrently
+        chunk_size,                                    // Number of blocks to fetch per batch
+        max_block_span,                                // Blocks per get_logs call, at most
+        request_delay_ms,                              // Pause between batches
+        rate_limiter,                                  // Requests per second across all calls
+        event_type: args.event_type,                   // Filter for specific event types (optional)
+        topic0: args.topic0,                           // Raw event signatures, bypassing the ABI
+        abi,                                           // Contract ABI
+        sampling: args.sampling,                       // Subset of the events to keep
+        seed: args.seed,                               // Reproducible random sampling
+        head: args.head,                               // Stop after the first N events
+        tail: args.tail,                               // Keep only the last N events
+        dedup_key: args.dedup_key,                     // Fields identifying duplicate events
+        one_per_tx: args.one_per_tx,                   // Single event per transaction
+        value_range,                                   // Bounds on the transaction value
+        filename,                                      // Output file path
+        format: args.format,                           // Output file format
+        array_streaming: args.array_streaming,         // JSON array written chunk by chunk
+        compact_addresses: args.compact_addresses,     // Binary addresses in binary outputs
+        max_in_flight_bytes: args.max_in_flight_bytes, // Cap on buffered event bytes
+        csv_dialect: args.csv_dialect,                 // CSV delimiter and header row
+        partition: args.partition,                     // One output file per block window or day
+        postgres_url: args.postgres,                   // Database the events are inserted into
+        order: args.order,                             // Block traversal direction
+        pretty: args.pretty,                           // Thousands separators in logs
+        checkpoint_path: args.checkpoint,              // Completed chunk ranges
+        reconcile_checkpoint,                          // Checkpoint checked against the output
+        error_log_path: Some(error_log.clone()),       // Chunks failed on every provider
+        block_hash: args.block_hash,                   // Single block queried by hash
+        raw: args.raw,                                 // Raw log data and topics columns
+        flatten_args: args.flatten_args,               // A column per event parameter
+        indexed_addresses: args.indexed_addresses,     // A column per indexed address
+        fsync: args.fsync,                             // Fsync the output after every chunk
+        timing: args.timing,                           // Per-chunk timing logs and summary
+        dump_filter: args.dump_filter,                 // eth_getLogs filter logged as JSON
+        include_internal: args.include_internal,       // Direct caller column from traces
+        no_enrich: args.no_enrich,                     // Log data only, no block or transaction
+        gas_price: args.gas_price,                     // Gas price column, in wei
+        with_receipts: args.with_receipts,             // Effective gas price from receipts
+        calldata: args.calldata,                       // Transaction input column
+        aggregate_by_block: args.aggregate_by_block,   // Per-block counts instead of events
+        strict: args.strict,                           // Abort rather than skip any event
+        force: args.force,                             // Append despite another recorded ABI
+        chain_id: row_chain_id,                        // Chain ID column, when requested
+        lookback_overlap: args.lookback_overlap,       // Blocks re-queried on each new head
+        recent_logs: None,                             // Set up by the follow loop
+        ..Default::default()
+    };
+
+    // A misspelt sort column fails the run now rather than once the outputs are complete;
+    // the indexed address and argument columns depend on the ABI, so only their prefix is
+    // checked here
+    if let Some(order_by) = &args.order_by {
+        let labelled = contracts.iter().any(|contract| contract.label.is_some());
+        let columns = config.columns().with(Column::Label, labelled);
+        if config.format == OutputFormat::Jsonl {
+            order_by.check_columns(&json_fields(&columns), &[])?;
+        } else {
+            let mut prefixes = Vec::new();
+            if config.indexed_addresses {
+                prefixes.push(constants::TOPIC_COLUMN_PREFIX);
+            }
+            if config.flatten_args {
+                prefixes.push(constants::ARG_COLUMN_PREFIX);
+            }
+            order_by.check_columns(&columns.header(), &prefixes)?;
+        }
+    }
+
+    // Show what the run would do, once every bound is resolved, instead of running it
+    if args.print_config {
+        let effective = effective_config(
+            &args.network,
+            &config,
+            &contracts,
+            from_bound_block.or(file_ranges.first().map(|&(from, _)| from)),
+            &file_ranges,
+            args.webhook.as_ref(),
+            &keys,
+        );
+        println!("{}", serde_json::to_string_pretty(&effective)?);
+        return Ok(None);
+    }
+
+    // Expose Prometheus metrics for the lifetime of the run when requested
+    config.metrics = match args.metrics_port {
+        Some(port) => {
+            let metrics = Arc::new(Metrics::new()?);
+            let listener = tokio::net::TcpListener::bind(("0.0.0.0", port)).await?;
+            info!("Serving metrics on port {}", port);
+            tokio::spawn(serve_metrics(listener, metrics.clone()));
+            Some(metrics)
+        }
+        None => None,
+    };
+
+    // A pending log left by a retry that died is stale, its chunks are retried again
+    if retrying_own_log && std::path::Path::new(&error_log).exists() {
+        std::fs::remove_file(&error_log)?;
+    }
+
+    // Stop at the next chunk boundary on Ctrl-C, leaving a consistent output and metadata,
+    // and abandon the current chunk on a second one, still closing the output
+    let cancel = CancellationToken::new();
+    let hard_stop = CancellationToken::new();
+    tokio::spawn({
+        let cancel = cancel.clone();
+        let hard_stop = hard_stop.clone();
+        async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                warn!("Interrupted, stopping after the current chunk (press Ctrl-C again to quit)");
+                cancel.cancel();
+            }
+            if tokio::signal::ctrl_c().await.is_ok() {
+                warn!("Interrupted again, abandoning the current chunk");
+                hard_stop.cancel();
+            }
+        }
+    });
+    config.cancel = Some(cancel);
+    config.hard_stop = Some(hard_stop);
+
+    // A single delivery serves every contract and followed head, so its batches span them
+    let webhook = args
+        .webhook
+        .map(|webhook| Arc::new(Webhook::start(webhook)));
+    config.webhook = webhook.clone();
+
+    // Each contract gets its own descriptively named file in the output directory
+    if let Some(dir) = &args.output_dir {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    // Proxy slots are read through a connection of their own
+    let proxy_provider = if args.resolve_proxy {
+        Some(connect_ws(&config.ws_rpc_url, &args.rpc_headers).await?)
+    } else {
+        None
+    };
+
+    let mut stats = CollectionStats::default();
+    let mut outputs: Vec<String> = Vec::new();
+    let mut metadata: HashMap<String, OutputMetadata> = HashMap::new();
+    let collected = async {
+        for (idx, contract) in contracts.into_iter().enumerate() {
+            // Collect only the failed chunks of the contract when retrying, or the listed ranges
+            let ranges = match retries.as_deref() {
+                Some(failures) => failed_ranges(failures, &contract.address),
+                None => file_ranges.clone(),
+            };
+
+            // Start at the first retried or listed range, the from-date, from-block, from-tx or known
+            // creation block, or fetch the contract creation block using Etherscan API
+            let from_block = match (ranges.iter().map(|&(from, _)| from).min(), from_bound_block) {
+                _ if args.block_hash.is_some() => 0, // The queried block bounds nothing
+                (Some(block), _) | (None, Some(block)) => block,
+                (None, None) => {
+                    // Creation blocks never change, so earlier lookups are reused
+                    let cache = constants::CREATION_BLOCK_CACHE_PATH;
+                    let cached = if args.no_cache {
+                        None
+                    } else {
+                        creation_block_cache_get(cache, &args.network, &contract.address)?
+                    };
+                    let creation_block = match cached {
+                        Some(block) => block,
+                        None => {
+                            let block = get_contract_creation_block(
+                                constants::ETHERSCAN_BASIC_URL,
+                                keys.etherscan()?,
+                                &contract.address,
+                                &etherscan_retry,
+                            )?;
+                            creation_block_cache_put(
+                                cache,
+                                &args.network,
+                                &contract.address,
+                                block,
+                            )?;
+                            block
+                        }
+                    };
+                    info!(
+                        "Contract {} created at block: {}",
+                        contract.address,
+                        fmt_int(creation_block, args.pretty)
+                    );
+                    creation_block
+                }
+            };
+
+            // The proxy emits the events, but they are declared in its implementation's ABI
+            if let Some(provider) = &proxy_provider {
+                let proxy = contract.address.parse().map_err(|_| {
+                    CollectorError::InvalidArgs(format!(
+                        "Invalid contract address: {}",
+                        contract.address
+                    ))
+                })?;
+                config.abi = match resolve_implementation(provider, proxy)
+                    .await
+                    .map_err(|e| CollectorError::Rpc(e.to_string()))?
+                {
+                    Some(implementation) => {
+                        info!(
+                            "Contract {} is a proxy of {:?}, using its ABI",
+                            contract.address, implementation
+                        );
+                        AbiSource::Inline(get_contract_abi(
+                            constants::ETHERSCAN_BASIC_URL,
+                            keys.etherscan()?,
+                            &format!("{:?}", implementation),
+                            &etherscan_retry,
+                        )?)
+                    }
+                    None => {
+                        warn!(
+                            "Contract {} is not an EIP-1967 proxy, using the default ABI",
+                            contract.address
+                        );
+                        AbiSource::default()
+                    }
+                };
+            }
+
+            config.contract_address = contract.address; // Target contract address
+            config.label = contract.label; // Label column, for contracts files
+            config.from_block = from_block; // Start block (from-date, from-tx or contract deployment block)
+            config.ranges = ranges; // Failed chunks of a previous run, or the ranges file
+            if let Some(dir) = &args.output_dir {
+                let name = output_filename(&OutputNameParams {
+                    network: &args.network,
+                    contract: &config.contract_address,
+                    from_block,
+                    to_block: config.to_block,
+                    extension: config.format.extension(),
+                });
+                config.filename = std::path::Path::new(dir)
+                    .join(name)
+                    .to_string_lossy()
+                    .into_owned();
+                config.error_log_path = Some(error_log_path(&config.filename));
+            }
+
+            // Later contracts append to the shared output, and retries to the existing one
+            config.append_output = (idx > 0 && args.output_dir.is_none())
+                || (retries.is_some() && std::path::Path::new(&config.filename).exists());
+
+            // The collection keeps the sidecar describing every contract of the output up to date
+            let mut output_metadata =
+                metadata
+                    .remove(&config.filename)
+                    .unwrap_or_else(|| OutputMetadata {
+                        network: args.network.clone(),
+                        chain_id,
+                        from_block,
+                        to_block: config.to_block,
+                        format: config.format.extension().to_string(),
+                        ..Default::default()
+                    });
+            output_metadata
+                .contracts
+                .push(config.contract_address.clone());
+            output_metadata.from_block = output_metadata.from_block.min(from_block);
+            output_metadata.completed = false;
+            config.metadata = (!fileless).then_some(output_metadata);
+
+            let contract_stats = if args.catch_up_then_follow {
+                catch_up_then_follow(
+                    &config,
+                    || pooled_providers(&config),
+                    || subscribe_heads(&config.ws_rpc_url, &args.rpc_headers),
+                )
+                .await?
+            } else {
+                collect_order_events(&config).await?
+            };
+            stats.merge(&contract_stats);
+            if !outputs.contains(&config.filename) {
+                outputs.push(config.filename.clone());
+            }
+            if !fileless {
+                metadata.insert(
+                    config.filename.clone(),
+                    read_metadata(&metadata_path(&config.filename))?,
+                );
+            }
+            if contract_stats.cancelled {
+                break;
+            }
+        }
+        Ok::<_, Box<dyn Error>>(())
+    }
+    .await;
+
+    // Deliver the events still held for the webhook, also when the collection failed, as
+    // they are already in the output
+    match &webhook {
+        Some(webhook) => webhook.finish_after(collected).await?,
+        None => collected?,
+    }
+
+    // The chunks failing again replace the retried log, unless a cancelled retry left some
+    // of its chunks untried
+    if let Some(path) = args.retry_failed.as_ref().filter(|_| retrying_own_log) {
+        let pending = pending_error_log_path(path);
+        let pending_exists = std::path::Path::new(&pending).exists();
+        if stats.cancelled {
+            info!(
+                "Keeping {} for the chunks the cancelled run did not retry",
+                path
+            );
+            if pending_exists {
+                std::fs::remove_file(&pending)?;
+            }
+        } else if pending_exists {
+            std::fs::rename(&pending, path)?;
+        } else {
+            std::fs::remove_file(path)?;
+        }
+    }
+
+    // Sort the outputs, written chunk by chunk in block order, once they are complete
+    if let Some(order_by) = &args.order_by {
+        if stats.cancelled {
+            info!("Leaving the outputs of the cancelled run unsorted");
+        } else {
+            for output in &outputs {
+                sort_output(output, config.format, config.csv_dialect, order_by)?;
+            }
+        }
+    }
+
+    // Hand the output over to the data lake once it is complete
+    if let Some(target) = &args.upload {
+        if stats.cancelled {
+            info!("Skipping the upload of the cancelled run");
+        } else {
+            let credentials = s3::creds::Credentials::default()?;
+            let mut files = Vec::new();
+            for output in &outputs {
+                files.extend(output_files(output, config.partition.is_some())?);
+                files.push(metadata_path(output));
+            }
+            for file in files {
+                let key = upload_output(
+                    &file,
+                    target,
+                    args.s3_endpoint.as_deref(),
+                    credentials.clone(),
+                )
+                .await?;
+                info!("Uploaded {} to s3://{}/{}", file, target.bucket, key);
+            }
+        }
+    }
+
+    Ok(Some(stats))
 }
@@ -0,0 +1,187 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use ethers::abi::{Abi, EventExt};
+use ethers::types::H256;
+use ethers::utils::keccak256;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::error::CollectorError;
+
+/// Description of a collected output, written to a sidecar next to it so that
+/// datasets merged from several runs and chains stay unambiguous.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputMetadata {
+    /// Network selected with `--network`.
+    pub network: String,
+    /// Chain ID reported by the RPC node, or given with `--chain-id`.
+    pub chain_id: u64,
+    /// Addresses of the contracts collected into the output.
+    pub contracts: Vec<String>,
+    /// First block of the collected range.
+    pub from_block: u64,
+    /// Last block of the collected range.
+    pub to_block: u64,
+    /// Format of the output (`csv`, `jsonl`, `json` or `avro`).
+    pub format: String,
+    /// Number of events written to the output by the run.
+    pub total_events: u64,
+    /// Last block of the most recent chunk processed, if any.
+    pub last_block: Option<u64>,
+    /// Whether every contract was collected to the end of its range; `false` when
+    /// the run was cancelled or aborted, or is still in progress.
+    pub completed: bool,
+    /// ABI and events the output was collected with; absent in sidecars written
+    /// before it was recorded.
+    #[serde(default)]
+    pub schema: Option<OutputSchema>,
+}
+
+/// ABI and events an output was collected with, compared before appending to it
+/// so that rows of different shapes do not end up in one dataset.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputSchema {
+    /// Keccak-256 hash of the ABI, absent for events selected by raw topic0.
+    pub abi_hash: Option<String>,
+    /// Full signatures of the collected events, e.g. `Clear(address,...)`, or their
+    /// topic0 hashes without an ABI, sorted.
+    pub events: Vec<String>,
+}
+
+impl OutputSchema {
+    /// Describes a collection of the events with `signatures`, declared in `abi`
+    /// unless they were given as raw topic0 hashes.
+    pub fn new(abi: Option<&Abi>, signatures: &[H256]) -> Result<Self, Box<dyn Error>> {
+        let Some(abi) = abi else {
+            let mut events: Vec<String> =
+                signatures.iter().map(|sig| format!("{:?}", sig)).collect();
+            events.sort();
+            return Ok(OutputSchema {
+                abi_hash: None,
+                events,
+            });
+        };
+
+        let mut events: Vec<String> = abi
+            .events()
+            .filter(|event| signatures.contains(&event.signature()))
+            .map(|event| event.abi_signature())
+            .collect();
+        events.sort();
+        events.dedup();
+        Ok(OutputSchema {
+            abi_hash: Some(format!("{:?}", H256(keccak256(serde_json::to_vec(abi)?)))),
+            events,
+        })
+    }
+
+    /// Returns how this schema differs from the `recorded` one, if it does.
+    pub fn mismatch(&self, recorded: &OutputSchema) -> Option<String> {
+        if self.events != recorded.events {
+            return Some(format!(
+                "it holds {} events, not {}",
+                recorded.events.join(", "),
+                self.events.join(", ")
+            ));
+        }
+        if self.abi_hash != recorded.abi_hash {
+            return Some(format!(
+                "its ABI hash is {}, not {}",
+                recorded.abi_hash.as_deref().unwrap_or("absent"),
+                self.abi_hash.as_deref().unwrap_or("absent")
+            ));
+        }
+        None
+    }
+}
+
+/// Checks that the output described by the sidecar at `path`, if any, was
+/// collected with `schema`, before appending to it.
+///
+/// A mismatch is an invalid argument, or only a warning with `force` set.
+/// Sidecars without a recorded schema are accepted.
+pub fn check_schema(path: &str, schema: &OutputSchema, force: bool) -> Result<(), Box<dyn Error>> {
+    if !Path::new(path).exists() {
+        return Ok(());
+    }
+    let Some(recorded) = read_metadata(path)?.schema else {
+        return Ok(());
+    };
+    match schema.mismatch(&recorded) {
+        None => Ok(()),
+        Some(reason) if force => {
+            warn!(
+                "Appending with another ABI than {} records, as forced: {}",
+                path, reason
+            );
+            Ok(())
+        }
+        Some(reason) => Err(CollectorError::InvalidArgs(format!(
+            "{} records another ABI: {}; pass --force to append anyway",
+            path, reason
+        ))
+        .into()),
+    }
+}
+
+/// Returns the path of the metadata sidecar kept next to the output `filename`,
+/// e.g. `order_events.meta.json` for `order_events.csv`.
+pub fn metadata_path(filename: &str) -> String {
+    Path::new(filename)
+        .with_extension("meta.json")
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Writes `metadata` to the sidecar at `path`, replacing it atomically.
+pub fn write_metadata(path: &str, metadata: &OutputMetadata) -> Result<(), Box<dyn Error>> {
+    let tmp = format!("{}.tmp", path);
+    fs::write(&tmp, serde_json::to_string_pretty(metadata)?)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Reads the sidecar at `path`.
+pub fn read_metadata(path: &str) -> Result<OutputMetadata, Box<dyn Error>> {
+    let content = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&content)
+        .map_err(|e| format!("Invalid output metadata {}: {}", path, e))?)
+}
+
+/// Keeps the metadata sidecar of an output in step with a collection.
+///
+/// The sidecar is written when the guard is dropped, so a collection ending early,
+/// whether cancelled or aborted by an error, still leaves a record of what it
+/// collected, with `completed` unset.
+pub struct MetadataGuard {
+    path: String,
+    metadata: OutputMetadata,
+}
+
+impl MetadataGuard {
+    /// Tracks `metadata`, to be written to the sidecar at `path`.
+    pub fn new(path: String, metadata: OutputMetadata) -> Self {
+        MetadataGuard { path, metadata }
+    }
+
+    /// Records a processed chunk ending at `last_block` that wrote `events` events.
+    pub fn record_chunk(&mut self, last_block: u64, events: u64) {
+        self.metadata.last_block = Some(last_block);
+        self.metadata.total_events += events;
+    }
+
+    /// Marks the collection as having reached the end of its range.
+    pub fn complete(&mut self) {
+        self.metadata.completed = true;
+    }
+}
+
+impl Drop for MetadataGuard {
+    fn drop(&mut self) {
+        if let Err(e) = write_metadata(&self.path, &self.metadata) {
+            warn!("Failed to write the output metadata {}: {}", self.path, e);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use std::fmt;
+use std::time::Duration;
+
+use log::{error, warn};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// Prometheus metrics updated by the collector at its instrumentation points.
+pub struct Metrics {
+    registry: Registry,
+    /// Events written, labelled by event type.
+    pub events_collected: IntCounterVec,
+    /// Chunks whose logs were fetched and written.
+    pub chunks_processed: IntCounter,
+    /// Failed RPC requests, labelled by method.
+    pub rpc_errors: IntCounterVec,
+    /// Last block of the most recently processed chunk.
+    pub current_block: IntGauge,
+    /// RPC request latency in seconds, labelled by method.
+    pub rpc_request_duration: HistogramVec,
+}
+
+impl Metrics {
+    /// Creates the metrics and registers them in a dedicated registry.
+    pub fn new() -> Result<Self, prometheus::Error> {
+        let registry = Registry::new();
+
+        let events_collected = IntCounterVec::new(
+            Opts::new("events_collected_total", "Events written to the output"),
+            &["event_type"],
+        )?;
+        let chunks_processed =
+            IntCounter::new("chunks_processed_total", "Block chunks fetched and written")?;
+        let rpc_errors = IntCounterVec::new(
+            Opts::new("rpc_errors_total", "Failed RPC requests"),
+            &["method"],
+        )?;
+        let current_block = IntGauge::new(
+            "current_block",
+            "Last block of the most recently processed chunk",
+        )?;
+        let rpc_request_duration = HistogramVec::new(
+            HistogramOpts::new("rpc_request_duration_seconds", "RPC request latency"),
+            &["method"],
+        )?;
+
+        registry.register(Box::new(events_collected.clone()))?;
+        registry.register(Box::new(chunks_processed.clone()))?;
+        registry.register(Box::new(rpc_errors.clone()))?;
+        registry.register(Box::new(current_block.clone()))?;
+        registry.register(Box::new(rpc_request_duration.clone()))?;
+
+        Ok(Metrics {
+            registry,
+            events_collected,
+            chunks_processed,
+            rpc_errors,
+            current_block,
+            rpc_request_duration,
+        })
+    }
+
+    /// Records the outcome and latency of one RPC request.
+    pub fn observe_rpc(&self, method: &str, elapsed: Duration, success: bool) {
+        self.rpc_request_duration
+            .with_label_values(&[method])
+            .observe(elapsed.as_secs_f64());
+        if !success {
+            self.rpc_errors.with_label_values(&[method]).inc();
+        }
+    }
+
+    /// Renders every metric in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            error!("Failed to encode metrics: {}", e);
+        }
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+impl fmt::Debug for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Metrics").finish_non_exhaustive()
+    }
+}
+
+/// Serves the metrics over HTTP on `listener` until the process exits.
+///
+/// This is a deliberately tiny server: every request, whatever its path,
+/// receives the current metrics.
+pub async fn serve_metrics(listener: TcpListener, metrics: std::sync::Arc<Metrics>) {
+    loop {
+        let (mut stream, _) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                warn!("Failed to accept metrics connection: {}", e);
+                continue;
+            }
+        };
+
+        // The request itself is irrelevant, read it so the client sees a clean reply
+        let mut request = [0u8; 1024];
+        let _ = stream.read(&mut request).await;
+
+        let body = metrics.render();
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        if let Err(e) = stream.write_all(response.as_bytes()).await {
+            warn!("Failed to write metrics response: {}", e);
+        }
+    }
+}
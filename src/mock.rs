@@ -0,0 +1,203 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use ethers::types::{Filter, Log, Transaction, ValueOrArray, H160, H256};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use tokio_util::sync::CancellationToken;
+
+use crate::source::{ChainSource, SourceError};
+
+/// Timestamp of block 0 of a [`MockProvider`], whose blocks are 12 seconds apart.
+pub const MOCK_GENESIS_TIMESTAMP: u64 = 1_700_000_000;
+
+/// An in-memory [`ChainSource`] serving a fixed log set, for tests, benchmarks and
+/// demos of the retry, progress and concurrency features without a network.
+///
+/// Logs are filtered by block range or hash and by topic0, as a node does. Every
+/// transaction is sent by the configured origin and sends the low 8 bytes of its
+/// hash, in wei, so values can be chosen through the hashes.
+///
+/// Every call but [`ChainSource::latest_block_number`], which connecting relies
+/// on, can be delayed by an artificial latency and fail at a given rate. Failures
+/// are drawn from a seeded generator, so a run is reproducible call for call.
+pub struct MockProvider {
+    latest_block: u64,
+    logs: Vec<Log>,
+    origin: H160,
+    latency: Duration,
+    error_rate: f64,
+    rng: Mutex<StdRng>,
+    cancel_on_logs: Option<CancellationToken>,
+    calls: AtomicU64,
+    failures: AtomicU64,
+}
+
+/// Builder of a [`MockProvider`], returned by [`MockProvider::builder`].
+#[derive(Debug, Clone, Default)]
+pub struct MockProviderBuilder {
+    latest_block: u64,
+    logs: Vec<Log>,
+    origin: H160,
+    latency: Duration,
+    error_rate: f64,
+    seed: u64,
+    cancel_on_logs: Option<CancellationToken>,
+}
+
+impl MockProviderBuilder {
+    /// Sets the latest block of the chain (default: 0).
+    pub fn latest_block(mut self, latest_block: u64) -> Self {
+        self.latest_block = latest_block;
+        self
+    }
+
+    /// Sets the logs served, matched by block number or block hash.
+    pub fn logs(mut self, logs: Vec<Log>) -> Self {
+        self.logs = logs;
+        self
+    }
+
+    /// Sets the sender of every transaction (default: the zero address).
+    pub fn origin(mut self, origin: H160) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    /// Delays every call by `ms` milliseconds (default: 0).
+    pub fn latency(mut self, ms: u64) -> Self {
+        self.latency = Duration::from_millis(ms);
+        self
+    }
+
+    /// Fails each call with probability `p`, clamped to `0.0..=1.0` (default: 0).
+    pub fn error_rate(mut self, p: f64) -> Self {
+        self.error_rate = p.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Seeds the generator drawing the failures (default: 0).
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Cancels `token` as soon as logs are first requested, to stop a collection
+    /// in the middle of its first chunk.
+    pub fn cancel_on_logs(mut self, token: CancellationToken) -> Self {
+        self.cancel_on_logs = Some(token);
+        self
+    }
+
+    /// Builds the provider.
+    pub fn build(self) -> MockProvider {
+        MockProvider {
+            latest_block: self.latest_block,
+            logs: self.logs,
+            origin: self.origin,
+            latency: self.latency,
+            error_rate: self.error_rate,
+            rng: Mutex::new(StdRng::seed_from_u64(self.seed)),
+            cancel_on_logs: self.cancel_on_logs,
+            calls: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+        }
+    }
+}
+
+impl MockProvider {
+    /// Returns a builder of an empty chain without latency or failures.
+    pub fn builder() -> MockProviderBuilder {
+        MockProviderBuilder::default()
+    }
+
+    /// Returns the number of simulated calls made so far, failed ones included.
+    pub fn calls(&self) -> u64 {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of calls failed on purpose so far.
+    pub fn failures(&self) -> u64 {
+        self.failures.load(Ordering::Relaxed)
+    }
+
+    /// Waits for the latency, then fails with the error rate.
+    async fn simulate(&self, method: &str) -> Result<(), SourceError> {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+        let failed = self.error_rate > 0.0
+            && self
+                .rng
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .gen_bool(self.error_rate);
+        if failed {
+            self.failures.fetch_add(1, Ordering::Relaxed);
+            return Err(format!("simulated {} failure", method).into());
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ChainSource for MockProvider {
+    async fn latest_block_number(&self) -> Result<u64, SourceError> {
+        Ok(self.latest_block)
+    }
+
+    async fn block_timestamp(&self, number: u64) -> Result<Option<u64>, SourceError> {
+        self.simulate("eth_getBlockByNumber").await?;
+        Ok((number <= self.latest_block).then_some(MOCK_GENESIS_TIMESTAMP + number * 12))
+    }
+
+    async fn logs(&self, filter: &Filter) -> Result<Vec<Log>, SourceError> {
+        if let Some(cancel) = &self.cancel_on_logs {
+            cancel.cancel();
+        }
+        self.simulate("eth_getLogs").await?;
+        if let Some(hash) = filter.get_block_hash() {
+            return Ok(self
+                .logs
+                .iter()
+                .filter(|log| log.block_hash == Some(hash))
+                .filter(|log| matches_topic0(filter, log))
+                .cloned()
+                .collect());
+        }
+        let from = filter.get_from_block().unwrap_or_default();
+        let to = filter.get_to_block().unwrap_or(u64::MAX.into());
+        Ok(self
+            .logs
+            .iter()
+            .filter(|log| log.block_number.is_some_and(|n| n >= from && n <= to))
+            .filter(|log| matches_topic0(filter, log))
+            .cloned()
+            .collect())
+    }
+
+    async fn transaction(&self, hash: H256) -> Result<Option<Transaction>, SourceError> {
+        self.simulate("eth_getTransactionByHash").await?;
+        Ok(Some(Transaction {
+            hash,
+            from: self.origin,
+            value: hash.to_low_u64_be().into(),
+            ..Default::default()
+        }))
+    }
+}
+
+/// Returns whether the topic0 of `log` is one `filter` selects, as a node checks it.
+fn matches_topic0(filter: &Filter, log: &Log) -> bool {
+    let topic0 = log.topics.first();
+    match &filter.topics[0] {
+        None | Some(ValueOrArray::Value(None)) => true,
+        Some(ValueOrArray::Value(Some(topic))) => topic0 == Some(topic),
+        Some(ValueOrArray::Array(topics)) => topics
+            .iter()
+            .any(|topic| topic.is_none() || topic0 == topic.as_ref()),
+    }
+}
@@ -0,0 +1,258 @@
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Write};
+use std::str::FromStr;
+
+use serde_json::Value;
+
+use crate::csv_manager::CsvDialect;
+use crate::error::CollectorError;
+use crate::sink::OutputFormat;
+
+/// A key of `--order-by`: a column, sorted in ascending order unless `:desc`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortKey {
+    /// Name of the column, as in the output or in snake case (`txn_hash`).
+    pub column: String,
+    /// Whether larger values come first.
+    pub descending: bool,
+}
+
+/// The keys of `--order-by`, each breaking the ties of the previous ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrderBy(pub Vec<SortKey>);
+
+impl FromStr for OrderBy {
+    type Err = String;
+
+    /// Parses comma-separated `column[:asc|:desc]` keys, e.g. `timestamp,log_index:desc`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut keys = Vec::new();
+        for key in s.split(',') {
+            let (column, direction) = match key.trim().split_once(':') {
+                Some((column, direction)) => (column.trim(), direction.trim()),
+                None => (key.trim(), "asc"),
+            };
+            if column.is_empty() {
+                return Err(format!("Empty sort key in: {}", s));
+            }
+            let descending = match direction.to_ascii_lowercase().as_str() {
+                "asc" => false,
+                "desc" => true,
+                _ => {
+                    return Err(format!(
+                        "Invalid sort direction: {} (expected asc or desc)",
+                        direction
+                    ))
+                }
+            };
+            keys.push(SortKey {
+                column: column.to_string(),
+                descending,
+            });
+        }
+        Ok(OrderBy(keys))
+    }
+}
+
+/// Spells a column name in snake case, so that `txn hash`, `tx.origin` and
+/// `txn_hash`, `tx_origin` name the same columns.
+fn normalize(name: &str) -> String {
+    name.trim().to_ascii_lowercase().replace([' ', '.'], "_")
+}
+
+/// Returns the error of a key naming none of the columns `names`.
+fn unknown_column(column: &str, names: &[String]) -> CollectorError {
+    CollectorError::InvalidArgs(format!(
+        "Unknown --order-by column: {} (available: {})",
+        column,
+        names.join(", ")
+    ))
+}
+
+impl OrderBy {
+    /// Checks before collecting that every key names one of `columns`, or starts
+    /// with one of `prefixes`, for the columns only known once the ABI is loaded.
+    pub fn check_columns<S: AsRef<str>>(
+        &self,
+        columns: &[S],
+        prefixes: &[&str],
+    ) -> Result<(), CollectorError> {
+        let names: Vec<String> = columns
+            .iter()
+            .map(|name| normalize(name.as_ref()))
+            .collect();
+        for key in &self.0 {
+            let column = normalize(&key.column);
+            let known = names.contains(&column)
+                || prefixes
+                    .iter()
+                    .any(|prefix| column.starts_with(&normalize(prefix)));
+            if !known {
+                return Err(unknown_column(&key.column, &names));
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds the comparator of rows with the columns of `header`, failing with the
+    /// available columns when a key names none of them.
+    pub fn comparator<S: AsRef<str>>(&self, header: &[S]) -> Result<RowComparator, CollectorError> {
+        let names: Vec<String> = header.iter().map(|name| normalize(name.as_ref())).collect();
+        let mut keys = Vec::with_capacity(self.0.len());
+        for key in &self.0 {
+            let Some(idx) = names
+                .iter()
+                .position(|name| *name == normalize(&key.column))
+            else {
+                return Err(unknown_column(&key.column, &names));
+            };
+            keys.push((idx, key.descending));
+        }
+        Ok(RowComparator { keys })
+    }
+}
+
+/// Compares rows by the columns of an [`OrderBy`], resolved to their positions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowComparator {
+    keys: Vec<(usize, bool)>,
+}
+
+impl RowComparator {
+    /// Compares rows `a` and `b` key by key; missing cells are empty.
+    pub fn compare<S: AsRef<str>>(&self, a: &[S], b: &[S]) -> Ordering {
+        fn cell<S: AsRef<str>>(row: &[S], idx: usize) -> &str {
+            row.get(idx).map_or("", AsRef::as_ref)
+        }
+        self.keys
+            .iter()
+            .map(|&(idx, descending)| {
+                let ordering = compare_cells(cell(a, idx), cell(b, idx));
+                if descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+/// Compares two cells as numbers when both are, so that block 10 follows block 9,
+/// and as text otherwise, which orders fixed-width hex hashes and addresses too.
+fn compare_cells(a: &str, b: &str) -> Ordering {
+    match (a.parse::<u128>(), b.parse::<u128>()) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        _ => a.cmp(b),
+    }
+}
+
+/// Sorts the rows of the complete output `filename` in place, stably, by the
+/// keys of `order_by`. Only CSV outputs with a header and JSON lines outputs,
+/// which are written chunk by chunk, are supported.
+pub fn sort_output(
+    filename: &str,
+    format: OutputFormat,
+    dialect: CsvDialect,
+    order_by: &OrderBy,
+) -> Result<(), Box<dyn Error>> {
+    match format {
+        OutputFormat::Csv if dialect.has_header => sort_csv(filename, dialect, order_by),
+        OutputFormat::Jsonl => sort_jsonl(filename, order_by),
+        _ => Err(CollectorError::InvalidArgs(
+            "--order-by is only supported with --format csv (with a header) or jsonl".to_string(),
+        )
+        .into()),
+    }
+}
+
+/// Sorts the rows of the CSV file `filename`, keeping its header first.
+fn sort_csv(filename: &str, dialect: CsvDialect, order_by: &OrderBy) -> Result<(), Box<dyn Error>> {
+    let mut reader = dialect.reader().from_path(filename)?;
+    let header = reader.headers()?.clone();
+    let comparator = order_by.comparator(&header.iter().collect::<Vec<_>>())?;
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        rows.push(record?.iter().map(str::to_string).collect::<Vec<_>>());
+    }
+    rows.sort_by(|a, b| comparator.compare(a, b));
+
+    replace_file(filename, |file| {
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(dialect.delimiter)
+            .from_writer(file);
+        writer.write_record(&header)?;
+        for row in &rows {
+            writer.write_record(row)?;
+        }
+        writer.flush()?;
+        Ok(())
+    })
+}
+
+/// Sorts the lines of the JSON lines file `filename` by the fields of their
+/// objects, available when any object has them. An output without any line is
+/// left as is.
+fn sort_jsonl(filename: &str, order_by: &OrderBy) -> Result<(), Box<dyn Error>> {
+    let mut lines = Vec::new();
+    let mut fields: Vec<String> = Vec::new();
+    for line in BufReader::new(File::open(filename)?).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value: Value = serde_json::from_str(&line)?;
+        if let Value::Object(object) = &value {
+            for field in object.keys() {
+                if !fields.contains(field) {
+                    fields.push(field.clone());
+                }
+            }
+        }
+        lines.push((value, line));
+    }
+    if lines.is_empty() {
+        return Ok(());
+    }
+
+    let comparator = order_by.comparator(&fields)?;
+    let mut rows: Vec<(Vec<String>, String)> = lines
+        .into_iter()
+        .map(|(value, line)| {
+            let cells = fields
+                .iter()
+                .map(|field| match value.get(field) {
+                    None | Some(Value::Null) => String::new(),
+                    Some(Value::String(cell)) => cell.clone(),
+                    Some(cell) => cell.to_string(),
+                })
+                .collect();
+            (cells, line)
+        })
+        .collect();
+    rows.sort_by(|(a, _), (b, _)| comparator.compare(a, b));
+
+    replace_file(filename, |file| {
+        for (_, line) in &rows {
+            writeln!(file, "{}", line)?;
+        }
+        Ok(())
+    })
+}
+
+/// Replaces `filename` with the content written by `write`, through a temporary
+/// file so a crash leaves either the unsorted or the sorted output.
+fn replace_file(
+    filename: &str,
+    write: impl FnOnce(&mut File) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let tmp = format!("{}.tmp", filename);
+    let mut file = File::create(&tmp)?;
+    write(&mut file)?;
+    file.sync_all()?;
+    fs::rename(&tmp, filename)?;
+    Ok(())
+}
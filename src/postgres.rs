@@ -0,0 +1,192 @@
+use std::error::Error;
+use std::future::Future;
+
+use log::warn;
+use serde_json::Value;
+use tokio::task::JoinHandle;
+use tokio_postgres::types::ToSql;
+use tokio_postgres::{Client, NoTls};
+
+use crate::sink::EventSink;
+use crate::utils::OrderEvent;
+
+/// Columns of the Postgres table and their SQL types, in insertion order.
+pub const POSTGRES_COLUMNS: [(&str, &str); 12] = [
+    ("tx_origin", "TEXT NOT NULL"),
+    ("event_type", "TEXT NOT NULL"),
+    ("txn_hash", "TEXT NOT NULL"),
+    ("timestamp", "BIGINT NOT NULL"),
+    ("block_number", "BIGINT NOT NULL"),
+    ("log_index", "BIGINT NOT NULL"),
+    ("raw_data", "TEXT"),
+    ("raw_topics", "TEXT"),
+    ("direct_caller", "TEXT"),
+    ("label", "TEXT"),
+    ("chain_id", "BIGINT"),
+    ("args", "JSONB"),
+];
+
+/// Maximum number of rows per `INSERT`, keeping its parameters well below the
+/// 65,535 bind parameters Postgres accepts in one statement.
+pub const POSTGRES_BATCH_ROWS: usize = 1_000;
+
+/// Returns the DDL creating `table`, if absent, keyed on the log of each event.
+pub fn create_table_sql(table: &str) -> String {
+    let columns: Vec<String> = POSTGRES_COLUMNS
+        .iter()
+        .map(|(name, kind)| format!("    {} {}", name, kind))
+        .collect();
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} (\n{},\n    PRIMARY KEY (txn_hash, log_index)\n)",
+        table,
+        columns.join(",\n")
+    )
+}
+
+/// Returns an `INSERT` of `rows` rows into `table`, skipping the logs already
+/// stored so chunks can be inserted again after a restart.
+pub fn insert_sql(table: &str, rows: usize) -> String {
+    let width = POSTGRES_COLUMNS.len();
+    let values: Vec<String> = (0..rows)
+        .map(|row| {
+            let placeholders: Vec<String> = (1..=width)
+                .map(|column| format!("${}", row * width + column))
+                .collect();
+            format!("({})", placeholders.join(", "))
+        })
+        .collect();
+    let columns: Vec<&str> = POSTGRES_COLUMNS.iter().map(|(name, _)| *name).collect();
+    format!(
+        "INSERT INTO {} ({}) VALUES {} ON CONFLICT (txn_hash, log_index) DO NOTHING",
+        table,
+        columns.join(", "),
+        values.join(", ")
+    )
+}
+
+/// An [`OrderEvent`] as the parameters of a row of [`POSTGRES_COLUMNS`].
+struct PostgresRow {
+    tx_origin: String,
+    event_type: String,
+    txn_hash: String,
+    timestamp: i64,
+    block_number: i64,
+    log_index: i64,
+    raw_data: Option<String>,
+    raw_topics: Option<String>,
+    direct_caller: Option<String>,
+    label: Option<String>,
+    chain_id: Option<i64>,
+    args: Option<Value>,
+}
+
+impl PostgresRow {
+    fn from_event(event: &OrderEvent) -> Result<Self, Box<dyn Error>> {
+        Ok(PostgresRow {
+            tx_origin: format!("{:?}", event.tx_origin),
+            event_type: event.event_type.clone(),
+            txn_hash: format!("{:?}", event.txn_hash),
+            timestamp: i64::try_from(event.timestamp)?,
+            block_number: i64::try_from(event.block_number)?,
+            log_index: i64::try_from(event.log_index)?,
+            raw_data: event.raw_data.clone(),
+            raw_topics: event.raw_topics.clone(),
+            direct_caller: event.direct_caller.clone(),
+            label: event.label.clone(),
+            chain_id: event.chain_id.map(i64::try_from).transpose()?,
+            args: event.args.clone(),
+        })
+    }
+
+    fn params(&self) -> [&(dyn ToSql + Sync); POSTGRES_COLUMNS.len()] {
+        [
+            &self.tx_origin,
+            &self.event_type,
+            &self.txn_hash,
+            &self.timestamp,
+            &self.block_number,
+            &self.log_index,
+            &self.raw_data,
+            &self.raw_topics,
+            &self.direct_caller,
+            &self.label,
+            &self.chain_id,
+            &self.args,
+        ]
+    }
+}
+
+/// Runs `future` to completion from the synchronous [`EventSink`] methods.
+///
+/// This needs the multi-threaded Tokio runtime, which the binary uses.
+fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::task::block_in_place(|| tokio::runtime::Handle::current().block_on(future))
+}
+
+/// Postgres output, inserting the events of each chunk into a table within one
+/// transaction.
+pub struct PostgresSink {
+    client: Client,
+    table: String,
+    connection: JoinHandle<()>,
+}
+
+impl PostgresSink {
+    /// Connects to the database at `url`, without TLS, and creates `table` if absent.
+    pub async fn connect(url: &str, table: &str) -> Result<Self, Box<dyn Error>> {
+        let (client, connection) = tokio_postgres::connect(url, NoTls).await?;
+        let connection = tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                warn!("Postgres connection closed: {}", e);
+            }
+        });
+        client.batch_execute(&create_table_sql(table)).await?;
+        Ok(PostgresSink {
+            client,
+            table: table.to_string(),
+            connection,
+        })
+    }
+
+    /// Connects as [`PostgresSink::connect`] does, from synchronous code.
+    pub fn open(url: &str, table: &str) -> Result<Self, Box<dyn Error>> {
+        block_on(PostgresSink::connect(url, table))
+    }
+
+    /// Inserts `events` within one transaction, in batches of [`POSTGRES_BATCH_ROWS`],
+    /// and returns the number of rows that were not stored yet.
+    pub async fn insert_events(&mut self, events: &[OrderEvent]) -> Result<u64, Box<dyn Error>> {
+        let transaction = self.client.transaction().await?;
+        let mut inserted = 0;
+        for batch in events.chunks(POSTGRES_BATCH_ROWS) {
+            let rows = batch
+                .iter()
+                .map(PostgresRow::from_event)
+                .collect::<Result<Vec<_>, _>>()?;
+            let params: Vec<&(dyn ToSql + Sync)> =
+                rows.iter().flat_map(|row| row.params()).collect();
+            inserted += transaction
+                .execute(&insert_sql(&self.table, rows.len()), &params)
+                .await?;
+        }
+        transaction.commit().await?;
+        Ok(inserted)
+    }
+}
+
+impl EventSink for PostgresSink {
+    fn write_events(&mut self, events: &[OrderEvent]) -> Result<(), Box<dyn Error>> {
+        block_on(self.insert_events(events))?;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), Box<dyn Error>> {
+        // Dropping the client closes the connection, ending its task
+        let PostgresSink {
+            client, connection, ..
+        } = *self;
+        drop(client);
+        block_on(connection)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+use std::time::{Duration, Instant};
+
+/// Share of a collection already done by earlier runs, seeding the progress of a
+/// resumed run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProgressStart {
+    /// Blocks of the range completed before this run.
+    pub blocks: u64,
+    /// Events written by the runs that completed those blocks.
+    pub events: u64,
+}
+
+/// Overall position of a collection within its range.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    /// Blocks completed so far, including those of earlier runs.
+    pub blocks: u64,
+    /// Blocks of the whole range.
+    pub total_blocks: u64,
+    /// Events written so far, including those of earlier runs.
+    pub events: u64,
+    /// Share of the range completed, from 0 to 100.
+    pub percent: f64,
+    /// Estimated time left, once this run has completed a block.
+    pub eta: Option<Duration>,
+}
+
+/// Computes the progress of a run that completed `blocks` blocks and wrote
+/// `events` events in `elapsed`, starting from `start` out of `total_blocks`.
+///
+/// The percentage covers the whole range, while the ETA extrapolates the rate of
+/// this run alone, since the blocks of earlier runs took no time in it.
+pub fn progress(
+    total_blocks: u64,
+    start: ProgressStart,
+    blocks: u64,
+    events: u64,
+    elapsed: Duration,
+) -> Progress {
+    let done = (start.blocks + blocks).min(total_blocks);
+    let percent = if total_blocks == 0 {
+        100.0
+    } else {
+        done as f64 * 100.0 / total_blocks as f64
+    };
+    let eta = (blocks > 0).then(|| elapsed.mul_f64((total_blocks - done) as f64 / blocks as f64));
+    Progress {
+        blocks: done,
+        total_blocks,
+        events: start.events + events,
+        percent,
+        eta,
+    }
+}
+
+/// Tracks the blocks and events of a run to report its [`Progress`].
+#[derive(Debug)]
+pub struct ProgressTracker {
+    total_blocks: u64,
+    start: ProgressStart,
+    blocks: u64,
+    events: u64,
+    started: Instant,
+}
+
+impl ProgressTracker {
+    /// Starts tracking a run over `total_blocks` blocks, `start` of which were
+    /// completed by earlier runs.
+    pub fn new(total_blocks: u64, start: ProgressStart) -> Self {
+        ProgressTracker {
+            total_blocks,
+            start,
+            blocks: 0,
+            events: 0,
+            started: Instant::now(),
+        }
+    }
+
+    /// Records a chunk of `blocks` blocks that wrote `events` events.
+    pub fn record(&mut self, blocks: u64, events: u64) {
+        self.blocks += blocks;
+        self.events += events;
+    }
+
+    /// Returns the progress so far.
+    pub fn report(&self) -> Progress {
+        progress(
+            self.total_blocks,
+            self.start,
+            self.blocks,
+            self.events,
+            self.started.elapsed(),
+        )
+    }
+}
@@ -0,0 +1,196 @@
+use std::error::Error;
+use std::sync::Arc;
+
+use log::warn;
+
+use crate::constants;
+use crate::error::CollectorError;
+use crate::rpc_headers::{connect_ws, RpcHeader};
+use crate::source::ChainSource;
+
+/// Tracks the health of an ordered list of RPC endpoints and selects which one
+/// to use next.
+///
+/// The first URL is the primary. Selection always prefers the lowest-index
+/// healthy endpoint, so traffic returns to the primary once it recovers. A
+/// failing endpoint is considered unhealthy for `cooldown` subsequent
+/// selections. When every endpoint is unhealthy, the one recovering first is
+/// chosen.
+#[derive(Debug, Clone)]
+pub struct ProviderPool {
+    urls: Vec<String>,
+    unhealthy_until: Vec<u64>,
+    failures: Vec<u64>,
+    tick: u64,
+    cooldown: u64,
+}
+
+impl ProviderPool {
+    /// Creates a pool over `urls`, which must not be empty.
+    pub fn new(urls: Vec<String>, cooldown: u64) -> Result<Self, CollectorError> {
+        if urls.is_empty() {
+            return Err(CollectorError::InvalidArgs(
+                "At least one RPC URL is required".to_string(),
+            ));
+        }
+
+        let count = urls.len();
+        Ok(ProviderPool {
+            urls,
+            unhealthy_until: vec![0; count],
+            failures: vec![0; count],
+            tick: 0,
+            cooldown,
+        })
+    }
+
+    /// Returns the number of endpoints in the pool.
+    pub fn len(&self) -> usize {
+        self.urls.len()
+    }
+
+    /// Returns whether the pool has no endpoints (never true for a constructed pool).
+    pub fn is_empty(&self) -> bool {
+        self.urls.is_empty()
+    }
+
+    /// Returns the URL of the endpoint at `idx`.
+    pub fn url(&self, idx: usize) -> &str {
+        &self.urls[idx]
+    }
+
+    /// Returns the total number of failures recorded for the endpoint at `idx`.
+    pub fn failures(&self, idx: usize) -> u64 {
+        self.failures[idx]
+    }
+
+    /// Returns whether the endpoint at `idx` is currently considered healthy.
+    pub fn is_healthy(&self, idx: usize) -> bool {
+        self.tick >= self.unhealthy_until[idx]
+    }
+
+    /// Selects the endpoint to use for the next request.
+    pub fn select(&mut self) -> usize {
+        self.tick += 1;
+        (0..self.len())
+            .find(|&idx| self.is_healthy(idx))
+            .unwrap_or_else(|| {
+                (0..self.len())
+                    .min_by_key(|&idx| self.unhealthy_until[idx])
+                    .unwrap_or(0)
+            })
+    }
+
+    /// Marks the endpoint at `idx` healthy after a successful request.
+    pub fn record_success(&mut self, idx: usize) {
+        self.unhealthy_until[idx] = 0;
+    }
+
+    /// Marks the endpoint at `idx` unhealthy for the cooldown period.
+    pub fn record_failure(&mut self, idx: usize) {
+        self.failures[idx] += 1;
+        self.unhealthy_until[idx] = self.tick + self.cooldown + 1;
+    }
+}
+
+/// Chain sources backing a [`ProviderPool`].
+///
+/// URL-backed pools connect WebSocket providers lazily and reconnect them after
+/// failures; pools built from existing sources keep them for the whole run.
+pub struct PooledProviders {
+    pool: ProviderPool,
+    providers: Vec<Option<Arc<dyn ChainSource>>>,
+    reconnect: bool,
+    headers: Vec<RpcHeader>,
+}
+
+impl PooledProviders {
+    /// Wraps `pool`; connections are opened on first use.
+    pub fn new(pool: ProviderPool) -> Self {
+        let providers = (0..pool.len()).map(|_| None).collect();
+        PooledProviders {
+            pool,
+            providers,
+            reconnect: true,
+            headers: Vec::new(),
+        }
+    }
+
+    /// Sends `headers` with the WebSocket upgrade request of every connection.
+    pub fn with_headers(mut self, headers: Vec<RpcHeader>) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Builds a pool over already constructed sources, in order of preference.
+    pub fn from_sources(sources: Vec<Arc<dyn ChainSource>>) -> Result<Self, CollectorError> {
+        let names = (0..sources.len())
+            .map(|idx| format!("source #{}", idx))
+            .collect();
+        Ok(PooledProviders {
+            pool: ProviderPool::new(names, constants::PROVIDER_COOLDOWN_CHUNKS)?,
+            providers: sources.into_iter().map(Some).collect(),
+            reconnect: false,
+            headers: Vec::new(),
+        })
+    }
+
+    /// Returns the number of endpoints in the pool.
+    pub fn len(&self) -> usize {
+        self.pool.len()
+    }
+
+    /// Returns whether the pool has no endpoints.
+    pub fn is_empty(&self) -> bool {
+        self.pool.is_empty()
+    }
+
+    /// Selects a healthy endpoint and makes sure it is connected, failing over
+    /// to the next endpoint when a connection attempt fails.
+    pub async fn connect(&mut self) -> Result<usize, Box<dyn Error>> {
+        let mut last_error: Option<Box<dyn Error>> = None;
+
+        for _ in 0..self.pool.len() {
+            let idx = self.pool.select();
+            if self.providers[idx].is_some() {
+                return Ok(idx);
+            }
+
+            match connect_ws(self.pool.url(idx), &self.headers).await {
+                Ok(provider) => {
+                    self.providers[idx] = Some(Arc::new(provider));
+                    return Ok(idx);
+                }
+                Err(e) => {
+                    warn!("Failed to connect to RPC provider #{}: {}", idx, e);
+                    self.pool.record_failure(idx);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| "No RPC provider available".into()))
+    }
+
+    /// Returns the connected source at `idx`, as returned by [`Self::connect`].
+    pub fn provider(&self, idx: usize) -> &dyn ChainSource {
+        self.providers[idx]
+            .as_deref()
+            .expect("provider must be connected before use")
+    }
+
+    /// Records a successful request on the endpoint at `idx`.
+    pub fn record_success(&mut self, idx: usize) {
+        self.pool.record_success(idx);
+    }
+
+    /// Records a failed request on the endpoint at `idx`. URL-backed
+    /// connections are dropped so they are re-established when the endpoint is
+    /// next selected.
+    pub fn record_failure(&mut self, idx: usize) {
+        self.pool.record_failure(idx);
+        if self.reconnect {
+            self.providers[idx] = None;
+        }
+    }
+}
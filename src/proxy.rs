@@ -0,0 +1,35 @@
+use ethers::types::{H160, H256};
+use hex_literal::hex;
+
+use crate::source::{ChainSource, SourceError};
+
+/// Storage slot holding the implementation address of an EIP-1967 proxy,
+/// `keccak256("eip1967.proxy.implementation") - 1`.
+pub const EIP1967_IMPLEMENTATION_SLOT: H256 = H256(hex!(
+    "360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc"
+));
+
+/// Decodes the implementation address stored in the EIP-1967 slot.
+///
+/// Returns `None` for an empty slot, as read from contracts that are not
+/// proxies, and for values that are not a left-padded address.
+pub fn implementation_from_slot(value: H256) -> Option<H160> {
+    let (padding, address) = value.as_bytes().split_at(12);
+    if padding.iter().any(|&byte| byte != 0) {
+        return None;
+    }
+    let address = H160::from_slice(address);
+    (!address.is_zero()).then_some(address)
+}
+
+/// Resolves the implementation behind the EIP-1967 proxy at `proxy`, or `None`
+/// if the contract does not use the EIP-1967 layout.
+pub async fn resolve_implementation(
+    source: &dyn ChainSource,
+    proxy: H160,
+) -> Result<Option<H160>, SourceError> {
+    let value = source
+        .storage_at(proxy, EIP1967_IMPLEMENTATION_SLOT)
+        .await?;
+    Ok(implementation_from_slot(value))
+}
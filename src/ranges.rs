@@ -0,0 +1,88 @@
+use std::fs;
+
+use crate::error::CollectorError;
+
+/// Parses a list of inclusive `from,to` block ranges, one per line.
+///
+/// Blank lines, `#` comments and an optional `from,to` header on the first other
+/// line are skipped. The
+/// ranges are returned in ascending order. Malformed lines and ranges ending
+/// before they start are reported with their line numbers in a single error, as
+/// are overlapping ranges.
+pub fn parse_ranges(content: &str) -> Result<Vec<(u64, u64)>, CollectorError> {
+    let mut ranges = Vec::new();
+    let mut malformed = Vec::new();
+    let mut first_entry = true;
+
+    for (idx, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        // The header may follow comments and blank lines, but no range
+        if std::mem::take(&mut first_entry) && line.eq_ignore_ascii_case("from,to") {
+            continue;
+        }
+
+        let bounds = line.split_once(',').and_then(|(from, to)| {
+            Some((
+                from.trim().parse::<u64>().ok()?,
+                to.trim().parse::<u64>().ok()?,
+            ))
+        });
+        match bounds {
+            Some((from, to)) if from <= to => ranges.push((from, to, idx + 1)),
+            Some((from, to)) => malformed.push(format!(
+                "line {}: range ends at {} before it starts at {}",
+                idx + 1,
+                to,
+                from
+            )),
+            None => malformed.push(format!(
+                "line {}: expected `from,to`, got '{}'",
+                idx + 1,
+                line
+            )),
+        }
+    }
+
+    if !malformed.is_empty() {
+        return Err(CollectorError::InvalidArgs(format!(
+            "Malformed ranges file entries: {}",
+            malformed.join("; ")
+        )));
+    }
+
+    // Overlapping ranges would collect the same events twice
+    ranges.sort_unstable();
+    let overlaps: Vec<String> = ranges
+        .windows(2)
+        .filter(|pair| pair[1].0 <= pair[0].1)
+        .map(|pair| {
+            format!(
+                "lines {} and {}: {}-{} overlaps {}-{}",
+                pair[0].2, pair[1].2, pair[0].0, pair[0].1, pair[1].0, pair[1].1
+            )
+        })
+        .collect();
+    if !overlaps.is_empty() {
+        return Err(CollectorError::InvalidArgs(format!(
+            "Overlapping ranges file entries: {}",
+            overlaps.join("; ")
+        )));
+    }
+    if ranges.is_empty() {
+        return Err(CollectorError::InvalidArgs(
+            "Ranges file does not list any range".to_string(),
+        ));
+    }
+    Ok(ranges.into_iter().map(|(from, to, _)| (from, to)).collect())
+}
+
+/// Reads and parses the block ranges at `path`, see [`parse_ranges`].
+pub fn load_ranges_file(path: &str) -> Result<Vec<(u64, u64)>, CollectorError> {
+    let content = fs::read_to_string(path).map_err(|e| {
+        CollectorError::InvalidArgs(format!("Failed to read ranges file {}: {}", path, e))
+    })?;
+    parse_ranges(&content)
+}
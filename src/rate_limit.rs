@@ -0,0 +1,48 @@
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use tokio::time::{sleep_until, Instant};
+
+/// Token bucket shared by every RPC call of a run, capping them at a number of
+/// requests per second whatever the enrichment concurrency.
+///
+/// The bucket holds a single token, refilled every `1 / rps` seconds, so calls
+/// are evenly paced rather than let through in bursts: no window of one second
+/// ever sees more than `rps` of them.
+#[derive(Debug)]
+pub struct RateLimiter {
+    rps: u32,
+    interval: Duration,
+    /// Instant at which the next token becomes available.
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    /// Creates a limiter letting through at most `rps` calls per second; zero is
+    /// treated as one.
+    pub fn new(rps: u32) -> Self {
+        let rps = rps.max(1);
+        RateLimiter {
+            rps,
+            interval: Duration::from_secs(1) / rps,
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Returns the number of calls let through per second.
+    pub fn rps(&self) -> u32 {
+        self.rps
+    }
+
+    /// Waits for a token. Tokens are handed out in the order they are requested,
+    /// each reserving the next free slot, so concurrent callers queue fairly.
+    pub async fn acquire(&self) {
+        let slot = {
+            let mut next = self.next.lock().unwrap_or_else(PoisonError::into_inner);
+            let slot = (*next).max(Instant::now());
+            *next = slot + self.interval;
+            slot
+        };
+        sleep_until(slot).await;
+    }
+}
@@ -0,0 +1,80 @@
+use std::fmt::Display;
+use std::thread::sleep;
+use std::time::Duration;
+
+use log::warn;
+
+use crate::constants;
+
+/// Failure of one attempt of a retried request.
+#[derive(Debug)]
+pub enum RetryError<E> {
+    /// A failure that may not happen again, such as a network error or a rate limit.
+    Transient(E),
+    /// A failure that retrying cannot fix, returned at once.
+    Permanent(E),
+}
+
+/// Exponential backoff between the attempts of a request failing transiently.
+///
+/// The attempts are blocking, like the Etherscan requests it retries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts made after the first one before giving up.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled before each of the next ones.
+    pub base_delay: Duration,
+    /// Upper bound of the delay between two attempts.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: constants::DEFAULT_MAX_RETRIES,
+            base_delay: Duration::from_millis(constants::RETRY_BASE_DELAY_MS),
+            max_delay: Duration::from_millis(constants::RETRY_MAX_DELAY_MS),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy making a single attempt.
+    pub fn none() -> Self {
+        RetryPolicy {
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the delay before retry number `retry`, counting from 0.
+    pub fn delay(&self, retry: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay)
+    }
+
+    /// Runs `attempt` until it succeeds, fails permanently, or has been retried
+    /// `max_retries` times, and returns its last outcome.
+    pub fn run<T, E: Display>(
+        &self,
+        mut attempt: impl FnMut() -> Result<T, RetryError<E>>,
+    ) -> Result<T, E> {
+        let mut retries = 0;
+        loop {
+            match attempt() {
+                Ok(value) => return Ok(value),
+                Err(RetryError::Transient(e)) if retries < self.max_retries => {
+                    let delay = self.delay(retries);
+                    retries += 1;
+                    warn!(
+                        "{}, retrying in {:?} ({} of {})",
+                        e, delay, retries, self.max_retries
+                    );
+                    sleep(delay);
+                }
+                Err(RetryError::Transient(e) | RetryError::Permanent(e)) => return Err(e),
+            }
+        }
+    }
+}
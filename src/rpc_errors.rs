@@ -0,0 +1,67 @@
+use std::fmt;
+
+use ethers::providers::{ProviderError, RpcError};
+
+use crate::source::SourceError;
+
+/// JSON-RPC error code returned when a query exceeds a node limit, such as the
+/// maximum number of logs or blocks of an `eth_getLogs` call.
+pub const LIMIT_EXCEEDED_CODE: i64 = -32005;
+
+/// JSON-RPC error codes returned by throttling providers.
+pub const RATE_LIMITED_CODES: [i64; 2] = [429, -32029];
+
+/// Classification of a failed RPC request, keeping the JSON-RPC code and message
+/// of error responses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RpcErrorKind {
+    /// The query exceeded a node limit; a smaller block range may succeed.
+    LimitExceeded { code: i64, message: String },
+    /// The provider throttled the request; it may succeed later.
+    RateLimited { code: i64, message: String },
+    /// Any other JSON-RPC error response.
+    Response { code: i64, message: String },
+    /// The request failed without a JSON-RPC error response, e.g. a lost connection.
+    Transport(String),
+}
+
+impl fmt::Display for RpcErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RpcErrorKind::LimitExceeded { code, message }
+            | RpcErrorKind::RateLimited { code, message }
+            | RpcErrorKind::Response { code, message } => write!(f, "{} {}", code, message),
+            RpcErrorKind::Transport(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+/// Classifies `err` by the JSON-RPC error code of its response, if it has one.
+///
+/// Some providers report throttling under the limit code too, so a limit error
+/// whose message mentions a rate limit is classified as rate limited.
+pub fn classify_rpc_error(err: &ProviderError) -> RpcErrorKind {
+    let Some(response) = err.as_error_response() else {
+        return RpcErrorKind::Transport(err.to_string());
+    };
+    let (code, message) = (response.code, response.message.clone());
+
+    if RATE_LIMITED_CODES.contains(&code)
+        || (code == LIMIT_EXCEEDED_CODE && message.to_ascii_lowercase().contains("rate limit"))
+    {
+        RpcErrorKind::RateLimited { code, message }
+    } else if code == LIMIT_EXCEEDED_CODE {
+        RpcErrorKind::LimitExceeded { code, message }
+    } else {
+        RpcErrorKind::Response { code, message }
+    }
+}
+
+/// Classifies the error of a [`ChainSource`](crate::source::ChainSource) call,
+/// treating errors not raised by an ethers provider as transport errors.
+pub fn classify_source_error(err: &SourceError) -> RpcErrorKind {
+    match err.downcast_ref::<ProviderError>() {
+        Some(err) => classify_rpc_error(err),
+        None => RpcErrorKind::Transport(err.to_string()),
+    }
+}
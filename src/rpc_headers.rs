@@ -0,0 +1,54 @@
+use std::error::Error;
+use std::str::FromStr;
+
+use ethers::providers::{Authorization, ConnectionDetails, Provider, Ws};
+
+/// A header sent with the WebSocket upgrade request, given as `Name: value`.
+///
+/// The WebSocket transport can only attach an `Authorization` header to the
+/// upgrade request, so any other header name is rejected when parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpcHeader {
+    pub name: String,
+    pub value: String,
+}
+
+impl FromStr for RpcHeader {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = s
+            .split_once(':')
+            .ok_or_else(|| format!("Malformed RPC header (expected `Name: value`): {}", s))?;
+        let (name, value) = (name.trim(), value.trim());
+
+        let is_token = |c: char| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c);
+        if name.is_empty() || !name.chars().all(is_token) {
+            return Err(format!("Malformed RPC header name: {:?}", name));
+        }
+        if value.is_empty() || value.chars().any(|c| c.is_ascii_control()) {
+            return Err(format!("Malformed RPC header value for {}", name));
+        }
+        if !name.eq_ignore_ascii_case("authorization") {
+            return Err(format!(
+                "Unsupported RPC header {}: only Authorization can be sent on WebSocket connections",
+                name
+            ));
+        }
+
+        Ok(RpcHeader {
+            name: name.to_string(),
+            value: value.to_string(),
+        })
+    }
+}
+
+/// Connects to a WebSocket RPC endpoint, sending `headers` with the upgrade request.
+pub async fn connect_ws(url: &str, headers: &[RpcHeader]) -> Result<Provider<Ws>, Box<dyn Error>> {
+    let auth = match headers {
+        [] => None,
+        [header] => Some(Authorization::Raw(header.value.clone())),
+        _ => return Err("At most one Authorization RPC header can be given".into()),
+    };
+    Ok(Provider::<Ws>::connect(ConnectionDetails::new(url, auth)).await?)
+}
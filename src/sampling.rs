@@ -0,0 +1,48 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Which subset of the matching events is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Sampling {
+    /// Every event is kept.
+    #[default]
+    All,
+    /// Each event is kept independently with this probability, in `(0, 1]`.
+    Rate(f64),
+    /// Every Nth event is kept, starting with the first.
+    Stride(u64),
+}
+
+/// Decides, event by event, whether an event is kept according to a [`Sampling`].
+#[derive(Debug)]
+pub struct Sampler {
+    sampling: Sampling,
+    rng: StdRng,
+    seen: u64,
+}
+
+impl Sampler {
+    /// Creates a sampler; the same `seed` always keeps the same events.
+    pub fn new(sampling: Sampling, seed: Option<u64>) -> Self {
+        let rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        Sampler {
+            sampling,
+            rng,
+            seen: 0,
+        }
+    }
+
+    /// Returns whether the next event is kept.
+    pub fn keep(&mut self) -> bool {
+        let index = self.seen;
+        self.seen += 1;
+        match self.sampling {
+            Sampling::All => true,
+            Sampling::Rate(rate) => self.rng.gen_bool(rate.clamp(0.0, 1.0)),
+            Sampling::Stride(stride) => index.is_multiple_of(stride.max(1)),
+        }
+    }
+}
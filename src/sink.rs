@@ -0,0 +1,593 @@
+use std::collections::{HashSet, VecDeque};
+use std::error::Error;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use log::warn;
+use serde_json::Value;
+
+use crate::aggregate::BlockAggregate;
+use crate::error::CollectorError;
+use crate::utils::OrderEvent;
+
+/// Format of the collected output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// One CSV row per event.
+    #[default]
+    Csv,
+    /// One JSON object per line, including the decoded event arguments.
+    Jsonl,
+    /// A single JSON array, including the decoded event arguments. The array is
+    /// kept in memory and written when the run finishes, unless it is streamed.
+    Json,
+    /// An Avro object container, one record per event including the decoded event
+    /// arguments.
+    Avro,
+    /// An aligned table printed to stdout when the run finishes, instead of a file,
+    /// for a quick look at small ranges.
+    Table,
+}
+
+impl OutputFormat {
+    /// Returns the extension of output files in this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Csv => "csv",
+            OutputFormat::Jsonl => "jsonl",
+            OutputFormat::Json => "json",
+            OutputFormat::Avro => "avro",
+            OutputFormat::Table => "txt",
+        }
+    }
+
+    /// Returns whether events carry their decoded arguments in this format.
+    pub fn includes_args(self) -> bool {
+        matches!(
+            self,
+            OutputFormat::Jsonl | OutputFormat::Json | OutputFormat::Avro
+        )
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(OutputFormat::Csv),
+            "jsonl" => Ok(OutputFormat::Jsonl),
+            "json" => Ok(OutputFormat::Json),
+            "avro" => Ok(OutputFormat::Avro),
+            "table" => Ok(OutputFormat::Table),
+            _ => Err(format!("Unsupported output format: {}", s)),
+        }
+    }
+}
+
+/// Destination of the collected events.
+pub trait EventSink {
+    /// Writes a chunk of events.
+    fn write_events(&mut self, events: &[OrderEvent]) -> Result<(), Box<dyn Error>>;
+
+    /// Writes a chunk of per-block aggregates, for outputs of `--aggregate-by-block`.
+    fn write_block_aggregates(
+        &mut self,
+        _aggregates: &[BlockAggregate],
+    ) -> Result<(), Box<dyn Error>> {
+        Err("Block aggregates cannot be written to an event output".into())
+    }
+
+    /// Returns the estimated bytes of events held in memory until the output is
+    /// finished. Outputs writing each chunk as it arrives hold none.
+    fn buffered_bytes(&self) -> usize {
+        0
+    }
+
+    /// Completes the output once every chunk has been written.
+    fn finish(self: Box<Self>) -> Result<(), Box<dyn Error>>;
+}
+
+/// Fails when `sink` holds more than `max_bytes` of buffered events, before a
+/// huge collection gets the process killed for running out of memory.
+///
+/// No buffered format can be flushed before the run finishes, so the run stops
+/// with a hint towards the formats writing events as they are collected.
+pub fn check_buffered_bytes(sink: &dyn EventSink, max_bytes: u64) -> Result<(), CollectorError> {
+    let buffered = sink.buffered_bytes() as u64;
+    if buffered <= max_bytes {
+        return Ok(());
+    }
+    Err(CollectorError::InvalidArgs(format!(
+        "the output buffers about {} bytes of events, over --max-in-flight-bytes {}; \
+         use --format jsonl or --array-streaming to write events as they are collected",
+        buffered, max_bytes
+    )))
+}
+
+/// JSON output, either as JSON lines or as a single array.
+pub struct JsonSink {
+    file: File,
+    format: OutputFormat,
+    buffered: Vec<Value>,
+    buffered_bytes: usize,
+    fsync: bool,
+}
+
+impl JsonSink {
+    /// Creates (or truncates) `filename`.
+    pub fn create(
+        filename: &str,
+        format: OutputFormat,
+        fsync: bool,
+    ) -> Result<Self, Box<dyn Error>> {
+        let file = File::options()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(filename)?;
+        Ok(JsonSink {
+            file,
+            format,
+            buffered: Vec::new(),
+            buffered_bytes: 0,
+            fsync,
+        })
+    }
+
+    /// Opens an existing `filename` to add events after its current content.
+    pub fn append(
+        filename: &str,
+        format: OutputFormat,
+        fsync: bool,
+    ) -> Result<Self, Box<dyn Error>> {
+        if format == OutputFormat::Jsonl {
+            let file = File::options().append(true).open(filename)?;
+            return Ok(JsonSink {
+                file,
+                format,
+                buffered: Vec::new(),
+                buffered_bytes: 0,
+                fsync,
+            });
+        }
+
+        // The array is rewritten as a whole, so its current elements are kept in memory
+        let content = std::fs::read_to_string(filename)?;
+        let buffered = if content.trim().is_empty() {
+            Vec::new()
+        } else {
+            serde_json::from_str(&content)?
+        };
+        let file = File::options().write(true).open(filename)?;
+        Ok(JsonSink {
+            file,
+            format,
+            buffered,
+            // The parsed elements take at least the size of their text
+            buffered_bytes: content.len(),
+            fsync,
+        })
+    }
+
+    fn sync(&mut self) -> Result<(), Box<dyn Error>> {
+        self.file.flush()?;
+        if self.fsync {
+            self.file.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+impl EventSink for JsonSink {
+    fn write_events(&mut self, events: &[OrderEvent]) -> Result<(), Box<dyn Error>> {
+        if self.format != OutputFormat::Jsonl {
+            for event in events {
+                self.buffered.push(serde_json::to_value(event)?);
+                self.buffered_bytes += event.estimated_size();
+            }
+            return Ok(());
+        }
+
+        for event in events {
+            let mut line = serde_json::to_vec(event)?;
+            line.push(b'\n');
+            self.file.write_all(&line)?;
+        }
+        self.sync()
+    }
+
+    fn buffered_bytes(&self) -> usize {
+        self.buffered_bytes
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), Box<dyn Error>> {
+        if self.format != OutputFormat::Jsonl {
+            self.file.set_len(0)?;
+            self.file.seek(SeekFrom::Start(0))?;
+            serde_json::to_writer(&mut self.file, &self.buffered)?;
+        }
+        self.sync()
+    }
+}
+
+/// JSON array output written incrementally, for `--array-streaming`.
+///
+/// The array is opened when the file is created and each chunk of events is
+/// written as it arrives, so memory stays flat however long the run. The array is
+/// closed by [`EventSink::finish`], or when the sink is dropped after an early exit,
+/// so the file stays valid JSON either way.
+pub struct JsonArraySink {
+    file: File,
+    empty: bool,
+    closed: bool,
+    fsync: bool,
+}
+
+impl JsonArraySink {
+    /// Creates (or truncates) `filename` and opens the array.
+    pub fn create(filename: &str, fsync: bool) -> Result<Self, Box<dyn Error>> {
+        let mut file = File::options()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(filename)?;
+        file.write_all(b"[")?;
+        Ok(JsonArraySink {
+            file,
+            empty: true,
+            closed: false,
+            fsync,
+        })
+    }
+
+    /// Opens an existing array in `filename`, reopening it after its last element.
+    pub fn append(filename: &str, fsync: bool) -> Result<Self, Box<dyn Error>> {
+        let mut file = File::options().read(true).write(true).open(filename)?;
+        let len = file.metadata()?.len();
+        let close = match last_non_whitespace(&mut file, len)? {
+            Some((close, b']')) => close,
+            Some(_) => return Err(format!("{} does not end with a JSON array", filename).into()),
+            None => return JsonArraySink::create(filename, fsync),
+        };
+
+        // Cut the closing bracket and the whitespace before it
+        let (last, byte) = last_non_whitespace(&mut file, close)?
+            .ok_or_else(|| format!("{} does not hold a JSON array", filename))?;
+        file.set_len(last + 1)?;
+        file.seek(SeekFrom::End(0))?;
+        Ok(JsonArraySink {
+            file,
+            empty: byte == b'[',
+            closed: false,
+            fsync,
+        })
+    }
+
+    fn close(&mut self) -> Result<(), Box<dyn Error>> {
+        self.closed = true;
+        self.file.write_all(b"\n]\n")?;
+        self.file.flush()?;
+        if self.fsync {
+            self.file.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the position and value of the last non-whitespace byte of `file`
+/// before `end`, if any.
+fn last_non_whitespace(file: &mut File, end: u64) -> Result<Option<(u64, u8)>, Box<dyn Error>> {
+    let mut byte = [0u8];
+    for position in (0..end).rev() {
+        file.seek(SeekFrom::Start(position))?;
+        file.read_exact(&mut byte)?;
+        if !byte[0].is_ascii_whitespace() {
+            return Ok(Some((position, byte[0])));
+        }
+    }
+    Ok(None)
+}
+
+impl EventSink for JsonArraySink {
+    fn write_events(&mut self, events: &[OrderEvent]) -> Result<(), Box<dyn Error>> {
+        for event in events {
+            let separator: &[u8] = if self.empty { b"\n" } else { b",\n" };
+            self.file.write_all(separator)?;
+            serde_json::to_writer(&mut self.file, event)?;
+            self.empty = false;
+        }
+        self.file.flush()?;
+        if self.fsync {
+            self.file.sync_all()?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), Box<dyn Error>> {
+        self.close()
+    }
+}
+
+impl Drop for JsonArraySink {
+    fn drop(&mut self) {
+        if !self.closed {
+            if let Err(e) = self.close() {
+                warn!("Failed to close the JSON array: {}", e);
+            }
+        }
+    }
+}
+
+/// Opens the output file at a path, appending to it when the flag is set.
+pub type OpenSink<'a> =
+    Box<dyn FnMut(&str, bool) -> Result<Box<dyn EventSink>, Box<dyn Error>> + 'a>;
+
+/// Returns the first and last block of the window of `window_blocks` blocks
+/// containing `block`. Windows are aligned on multiples of `window_blocks`.
+pub fn partition_window(block: u64, window_blocks: u64) -> (u64, u64) {
+    let window_blocks = window_blocks.max(1);
+    let start = block - block % window_blocks;
+    (start, start.saturating_add(window_blocks - 1))
+}
+
+/// Returns the path of the partition of `filename` holding blocks `start` to
+/// `end`, e.g. `order_events.21000000-21999999.csv`.
+pub fn partition_path(filename: &str, start: u64, end: u64) -> String {
+    labelled_path(filename, &format!("{}-{}", start, end))
+}
+
+/// Returns the path of the partition of `filename` holding the UTC day that
+/// contains `timestamp`, e.g. `order_events.2024-01-15.csv`.
+pub fn day_partition_path(filename: &str, timestamp: u64) -> String {
+    let date = i64::try_from(timestamp)
+        .ok()
+        .and_then(|seconds| chrono::DateTime::from_timestamp(seconds, 0))
+        .unwrap_or_default()
+        .format("%Y-%m-%d");
+    labelled_path(filename, &date.to_string())
+}
+
+/// Inserts `label` between the stem and the extension of `filename`.
+fn labelled_path(filename: &str, label: &str) -> String {
+    let path = Path::new(filename);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}.{}.{}", stem, label, extension.to_string_lossy()),
+        None => format!("{}.{}", stem, label),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+/// How a [`PartitionedSink`] splits its output into files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Partitioning {
+    /// One file per aligned window of this many blocks.
+    Blocks(u64),
+    /// One file per UTC day of the block timestamps.
+    Day,
+}
+
+impl Partitioning {
+    /// Returns the key of the partition holding `block`, mined at `timestamp`:
+    /// the first block of its window, or its day number since the epoch.
+    fn key(self, block: u64, timestamp: u64) -> u64 {
+        match self {
+            Partitioning::Blocks(window_blocks) => partition_window(block, window_blocks).0,
+            Partitioning::Day => timestamp / SECONDS_PER_DAY,
+        }
+    }
+
+    /// Returns the path of the partition of `filename` with key `key`.
+    fn path(self, filename: &str, key: u64) -> String {
+        match self {
+            Partitioning::Blocks(window_blocks) => {
+                let (start, end) = partition_window(key, window_blocks);
+                partition_path(filename, start, end)
+            }
+            Partitioning::Day => day_partition_path(filename, key * SECONDS_PER_DAY),
+        }
+    }
+}
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Output split into one file per window of blocks or per day, each with its
+/// own header.
+///
+/// Events are routed to the file of their partition, which is opened when its
+/// first event arrives and finished when events move on to another partition.
+/// Partitions without events produce no file.
+pub struct PartitionedSink<'a> {
+    filename: String,
+    partitioning: Partitioning,
+    append_existing: bool,
+    open: OpenSink<'a>,
+    current: Option<(u64, Box<dyn EventSink>)>,
+    opened: HashSet<u64>,
+}
+
+impl<'a> PartitionedSink<'a> {
+    /// Partitions `filename` as set by `partitioning`. With `append_existing`
+    /// set, partitions already on disk are appended to.
+    pub fn new(
+        filename: &str,
+        partitioning: Partitioning,
+        append_existing: bool,
+        open: OpenSink<'a>,
+    ) -> Self {
+        let partitioning = match partitioning {
+            Partitioning::Blocks(window_blocks) => Partitioning::Blocks(window_blocks.max(1)),
+            Partitioning::Day => Partitioning::Day,
+        };
+        PartitionedSink {
+            filename: filename.to_string(),
+            partitioning,
+            append_existing,
+            open,
+            current: None,
+            opened: HashSet::new(),
+        }
+    }
+
+    /// Returns the sink of the partition with key `key`, opening it if needed.
+    fn partition_sink(&mut self, key: u64) -> Result<&mut Box<dyn EventSink>, Box<dyn Error>> {
+        if self.current.as_ref().map(|(current, _)| *current) != Some(key) {
+            if let Some((_, sink)) = self.current.take() {
+                sink.finish()?;
+            }
+
+            let path = self.partitioning.path(&self.filename, key);
+            let append =
+                self.opened.contains(&key) || (self.append_existing && Path::new(&path).exists());
+            let sink = (self.open)(&path, append)?;
+            self.opened.insert(key);
+            self.current = Some((key, sink));
+        }
+
+        match &mut self.current {
+            Some((_, sink)) => Ok(sink),
+            None => Err("No partition is open".into()),
+        }
+    }
+
+    /// Writes `items` to the files of their partitions, in runs of consecutive
+    /// items sharing a partition. `position` gives the block number and the
+    /// block timestamp of an item.
+    fn write_partitioned<T>(
+        &mut self,
+        items: &[T],
+        position: impl Fn(&T) -> (u64, u64),
+        mut write: impl FnMut(&mut dyn EventSink, &[T]) -> Result<(), Box<dyn Error>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let partitioning = self.partitioning;
+        let key = |item: &T| {
+            let (block, timestamp) = position(item);
+            partitioning.key(block, timestamp)
+        };
+        let mut rest = items;
+        while let Some(first) = rest.first() {
+            let first_key = key(first);
+            let len = rest
+                .iter()
+                .take_while(|item| key(item) == first_key)
+                .count();
+            let (partition, tail) = rest.split_at(len);
+            write(self.partition_sink(first_key)?.as_mut(), partition)?;
+            rest = tail;
+        }
+        Ok(())
+    }
+}
+
+impl EventSink for PartitionedSink<'_> {
+    fn write_events(&mut self, events: &[OrderEvent]) -> Result<(), Box<dyn Error>> {
+        self.write_partitioned(
+            events,
+            |event| (event.block_number, event.timestamp),
+            |sink, events| sink.write_events(events),
+        )
+    }
+
+    fn write_block_aggregates(
+        &mut self,
+        aggregates: &[BlockAggregate],
+    ) -> Result<(), Box<dyn Error>> {
+        self.write_partitioned(
+            aggregates,
+            |aggregate| (aggregate.block_number, aggregate.timestamp),
+            |sink, aggregates| sink.write_block_aggregates(aggregates),
+        )
+    }
+
+    fn buffered_bytes(&self) -> usize {
+        self.current
+            .as_ref()
+            .map_or(0, |(_, sink)| sink.buffered_bytes())
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), Box<dyn Error>> {
+        match self.current {
+            Some((_, sink)) => sink.finish(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Output keeping only the last `limit` events written to it, in a bounded buffer
+/// handed to the inner output when the run finishes, for `--tail`.
+pub struct TailSink<'a> {
+    inner: Box<dyn EventSink + 'a>,
+    limit: usize,
+    events: VecDeque<OrderEvent>,
+}
+
+impl<'a> TailSink<'a> {
+    pub fn new(inner: Box<dyn EventSink + 'a>, limit: usize) -> Self {
+        TailSink {
+            inner,
+            limit,
+            events: VecDeque::with_capacity(limit),
+        }
+    }
+}
+
+impl EventSink for TailSink<'_> {
+    fn write_events(&mut self, events: &[OrderEvent]) -> Result<(), Box<dyn Error>> {
+        // Only the last `limit` events of the chunk can outlast it
+        let skip = events.len().saturating_sub(self.limit);
+        for event in &events[skip..] {
+            if self.events.len() == self.limit {
+                self.events.pop_front();
+            }
+            self.events.push_back(event.clone());
+        }
+        Ok(())
+    }
+
+    fn buffered_bytes(&self) -> usize {
+        self.events
+            .iter()
+            .map(OrderEvent::estimated_size)
+            .sum::<usize>()
+            + self.inner.buffered_bytes()
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), Box<dyn Error>> {
+        if !self.events.is_empty() {
+            self.inner.write_events(self.events.make_contiguous())?;
+        }
+        self.inner.finish()
+    }
+}
+
+/// Output writing every chunk to a primary output and to a secondary one, such as
+/// a webhook notified of the events also written to a file.
+pub struct TeeSink<'a> {
+    primary: Box<dyn EventSink + 'a>,
+    secondary: Box<dyn EventSink + 'a>,
+}
+
+impl<'a> TeeSink<'a> {
+    pub fn new(primary: Box<dyn EventSink + 'a>, secondary: Box<dyn EventSink + 'a>) -> Self {
+        TeeSink { primary, secondary }
+    }
+}
+
+impl EventSink for TeeSink<'_> {
+    fn write_events(&mut self, events: &[OrderEvent]) -> Result<(), Box<dyn Error>> {
+        self.primary.write_events(events)?;
+        self.secondary.write_events(events)
+    }
+
+    fn buffered_bytes(&self) -> usize {
+        self.primary.buffered_bytes() + self.secondary.buffered_bytes()
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), Box<dyn Error>> {
+        self.primary.finish()?;
+        self.secondary.finish()
+    }
+}
@@ -0,0 +1,128 @@
+use std::error::Error;
+
+use async_trait::async_trait;
+use ethers::providers::{JsonRpcClient, Middleware, Provider, ProviderError};
+use ethers::types::{
+    BlockId, BlockNumber, Filter, Log, Trace, Transaction, TransactionReceipt, H160, H256,
+};
+
+/// Error type returned by [`ChainSource`] implementations.
+pub type SourceError = Box<dyn Error + Send + Sync>;
+
+/// The subset of chain queries the collector relies on.
+///
+/// Abstracting them behind a trait keeps the collection logic independent of
+/// the transport, so it can be exercised against in-memory chains in tests.
+#[async_trait]
+pub trait ChainSource: Send + Sync {
+    /// Returns the number of the latest block.
+    async fn latest_block_number(&self) -> Result<u64, SourceError>;
+
+    /// Returns the timestamp of block `number`, or `None` if it does not exist.
+    async fn block_timestamp(&self, number: u64) -> Result<Option<u64>, SourceError>;
+
+    /// Returns the number of the block tagged `tag`, such as the `safe` or
+    /// `finalized` block, or `None` if the chain has none yet.
+    ///
+    /// Only needed for tagged block bounds, so sources may keep this default,
+    /// which always fails.
+    async fn tagged_block_number(&self, _tag: BlockNumber) -> Result<Option<u64>, SourceError> {
+        Err("block tags are not supported by this source".into())
+    }
+
+    /// Returns the logs matching `filter`.
+    async fn logs(&self, filter: &Filter) -> Result<Vec<Log>, SourceError>;
+
+    /// Returns the transaction with hash `hash`, or `None` if it is unknown.
+    async fn transaction(&self, hash: H256) -> Result<Option<Transaction>, SourceError>;
+
+    /// Returns the receipt of transaction `hash`, or `None` if it is unknown.
+    ///
+    /// Only needed for `--with-receipts`, so sources may keep this default, which
+    /// always fails.
+    async fn transaction_receipt(
+        &self,
+        _hash: H256,
+    ) -> Result<Option<TransactionReceipt>, SourceError> {
+        Err("eth_getTransactionReceipt is not supported by this source".into())
+    }
+
+    /// Returns the execution traces of transaction `hash`.
+    ///
+    /// Trace APIs are not offered by every provider, so sources without them can
+    /// keep this default, which always fails.
+    async fn transaction_traces(&self, _hash: H256) -> Result<Vec<Trace>, SourceError> {
+        Err("trace_transaction is not supported by this source".into())
+    }
+
+    /// Returns the value of storage slot `slot` of the contract at `address`.
+    ///
+    /// Only needed to resolve proxies, so sources may keep this default, which
+    /// always fails.
+    async fn storage_at(&self, _address: H160, _slot: H256) -> Result<H256, SourceError> {
+        Err("eth_getStorageAt is not supported by this source".into())
+    }
+
+    /// Returns the address the ENS name `name` resolves to, or `None` if it has none.
+    ///
+    /// Only needed for ENS names given on the command line, so sources may keep
+    /// this default, which always fails.
+    async fn ens_address(&self, _name: &str) -> Result<Option<H160>, SourceError> {
+        Err("ENS resolution is not supported by this source".into())
+    }
+}
+
+#[async_trait]
+impl<P: JsonRpcClient + 'static> ChainSource for Provider<P> {
+    async fn latest_block_number(&self) -> Result<u64, SourceError> {
+        Ok(self.get_block_number().await?.as_u64())
+    }
+
+    async fn block_timestamp(&self, number: u64) -> Result<Option<u64>, SourceError> {
+        Ok(self
+            .get_block(number)
+            .await?
+            .map(|block| block.timestamp.as_u64()))
+    }
+
+    async fn tagged_block_number(&self, tag: BlockNumber) -> Result<Option<u64>, SourceError> {
+        Ok(self
+            .get_block(BlockId::Number(tag))
+            .await?
+            .and_then(|block| block.number)
+            .map(|number| number.as_u64()))
+    }
+
+    async fn logs(&self, filter: &Filter) -> Result<Vec<Log>, SourceError> {
+        Ok(self.get_logs(filter).await?)
+    }
+
+    async fn transaction(&self, hash: H256) -> Result<Option<Transaction>, SourceError> {
+        Ok(self.get_transaction(hash).await?)
+    }
+
+    async fn transaction_receipt(
+        &self,
+        hash: H256,
+    ) -> Result<Option<TransactionReceipt>, SourceError> {
+        Ok(self.get_transaction_receipt(hash).await?)
+    }
+
+    async fn transaction_traces(&self, hash: H256) -> Result<Vec<Trace>, SourceError> {
+        Ok(self.trace_transaction(hash).await?)
+    }
+
+    async fn storage_at(&self, address: H160, slot: H256) -> Result<H256, SourceError> {
+        Ok(self.get_storage_at(address, slot, None).await?)
+    }
+
+    async fn ens_address(&self, name: &str) -> Result<Option<H160>, SourceError> {
+        match self.resolve_name(name).await {
+            Ok(address) if address.is_zero() => Ok(None),
+            Ok(address) => Ok(Some(address)),
+            // Raised for names without a resolver, i.e. not registered
+            Err(ProviderError::EnsError(_)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
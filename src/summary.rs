@@ -0,0 +1,97 @@
+use owo_colors::OwoColorize;
+
+use crate::event_collector::CollectionStats;
+use crate::utils::fmt_int;
+
+/// Decides whether terminal output is colorized.
+///
+/// Colors are used only on a terminal, and never with `--no-color` or a
+/// non-empty `NO_COLOR` environment variable (see <https://no-color.org>).
+pub fn should_use_color(no_color_flag: bool, no_color_env: Option<&str>, is_tty: bool) -> bool {
+    let no_color_env = no_color_env.is_some_and(|value| !value.is_empty());
+    is_tty && !no_color_flag && !no_color_env
+}
+
+/// Renders the end-of-run summary of `stats` as an aligned table.
+///
+/// With `color` set, the title is bold, counts of left-out data are red when
+/// non-zero and green otherwise, and a cancelled run is flagged in yellow. The
+/// events left out by the value filters, the events of the earlier runs of a
+/// resumed collection, and chunk durations when they were timed, are added below
+/// the counts.
+pub fn render_summary(stats: &CollectionStats, pretty: bool, color: bool) -> String {
+    let rows = [
+        ("Events collected", stats.total_events, false),
+        ("Chunks processed", stats.chunks_processed, false),
+        ("Failed chunks", stats.failed_chunks, true),
+        ("Logs skipped", stats.failed_enrichments, true),
+        ("Pending logs", stats.pending_logs, true),
+    ];
+    let values: Vec<String> = rows
+        .iter()
+        .map(|&(_, value, _)| fmt_int(value, pretty))
+        .collect();
+    let label_width = rows
+        .iter()
+        .map(|(label, _, _)| label.len())
+        .max()
+        .unwrap_or(0);
+    let value_width = values.iter().map(String::len).max().unwrap_or(0);
+
+    let title = "Collection summary";
+    let mut lines = vec![if color {
+        title.bold().to_string()
+    } else {
+        title.to_string()
+    }];
+    for (&(label, value, is_loss), text) in rows.iter().zip(&values) {
+        let cell = format!("{:>width$}", text, width = value_width);
+        let cell = match (color, is_loss) {
+            (false, _) | (true, false) => cell,
+            (true, true) if value > 0 => cell.red().to_string(),
+            (true, true) => cell.green().to_string(),
+        };
+        lines.push(format!(
+            "  {:<width$}  {}",
+            label,
+            cell,
+            width = label_width
+        ));
+    }
+    if stats.value_filtered > 0 {
+        lines.push(format!(
+            "  {:<width$}  {}",
+            "Value filtered",
+            fmt_int(stats.value_filtered, pretty),
+            width = label_width
+        ));
+    }
+    if stats.resumed_events > 0 {
+        lines.push(format!(
+            "  {:<width$}  {} ({} from earlier runs)",
+            "Events in output",
+            fmt_int(stats.total_events + stats.resumed_events, pretty),
+            fmt_int(stats.resumed_events, pretty),
+            width = label_width
+        ));
+    }
+    if let Some(average) = stats.chunk_durations.average() {
+        lines.push(format!(
+            "  {:<width$}  min {:.2?}, avg {:.2?}, max {:.2?}",
+            "Chunk duration",
+            stats.chunk_durations.min,
+            average,
+            stats.chunk_durations.max,
+            width = label_width
+        ));
+    }
+    if stats.cancelled {
+        let note = "Cancelled before the end of the range";
+        lines.push(if color {
+            format!("  {}", note.yellow())
+        } else {
+            format!("  {}", note)
+        });
+    }
+    lines.join("\n")
+}
@@ -0,0 +1,106 @@
+use std::error::Error;
+
+use log::warn;
+
+use crate::columns::ColumnSet;
+use crate::sink::EventSink;
+use crate::utils::OrderEvent;
+
+/// Shortens a long `0x` hex value to its first and last digits, e.g.
+/// `0x1234…cdef`, leaving other values unchanged.
+pub fn truncate_hex(value: &str) -> String {
+    match value.strip_prefix("0x") {
+        Some(digits) if digits.len() > 10 && digits.is_ascii() => {
+            format!("0x{}…{}", &digits[..4], &digits[digits.len() - 4..])
+        }
+        _ => value.to_string(),
+    }
+}
+
+/// Renders `rows` under `header` as an aligned ASCII table.
+pub fn render_table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = header.iter().map(|name| name.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let separator = widths
+        .iter()
+        .map(|&width| "-".repeat(width + 2))
+        .collect::<Vec<_>>()
+        .join("+");
+    let separator = format!("+{}+", separator);
+    let line = |cells: Vec<&str>| {
+        let cells: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &width)| {
+                // Padding counts characters, as `…` takes one column but three bytes
+                let padding = width - cell.chars().count();
+                format!(" {}{} ", cell, " ".repeat(padding))
+            })
+            .collect();
+        format!("|{}|", cells.join("|"))
+    };
+
+    let mut lines = vec![separator.clone(), line(header.to_vec()), separator.clone()];
+    for row in rows {
+        lines.push(line(row.iter().map(String::as_str).collect()));
+    }
+    lines.push(separator);
+    lines.join("\n")
+}
+
+/// Terminal output printing the events as a table on stdout once the run
+/// finishes, instead of writing a file. At most `max_rows` events are kept.
+pub struct TableSink {
+    columns: ColumnSet,
+    rows: Vec<Vec<String>>,
+    max_rows: usize,
+    total: u64,
+}
+
+impl TableSink {
+    /// Creates a table of the `columns`, keeping at most `max_rows` events.
+    pub fn new(columns: ColumnSet, max_rows: usize) -> Self {
+        TableSink {
+            columns,
+            rows: Vec::new(),
+            max_rows,
+            total: 0,
+        }
+    }
+
+    /// Renders the events written so far.
+    pub fn render(&self) -> String {
+        render_table(&self.columns.header(), &self.rows)
+    }
+}
+
+impl EventSink for TableSink {
+    fn write_events(&mut self, events: &[OrderEvent]) -> Result<(), Box<dyn Error>> {
+        for event in events {
+            self.total += 1;
+            if self.rows.len() < self.max_rows {
+                let row = self.columns.record(event);
+                self.rows
+                    .push(row.iter().map(|cell| truncate_hex(cell)).collect());
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), Box<dyn Error>> {
+        println!("{}", self.render());
+        if self.total > self.rows.len() as u64 {
+            warn!(
+                "Printed the first {} of {} events; use a file format to export them all",
+                self.rows.len(),
+                self.total
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,48 @@
+use std::time::Duration;
+
+/// Minimum, average and maximum duration of the chunks of a run, gathered with
+/// `--timing` to tune the chunk size.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChunkDurations {
+    /// Number of chunks timed.
+    pub count: u64,
+    pub min: Duration,
+    pub max: Duration,
+    /// Sum of the durations of every timed chunk.
+    pub total: Duration,
+}
+
+impl ChunkDurations {
+    /// Adds the duration of one chunk.
+    pub fn record(&mut self, duration: Duration) {
+        self.min = if self.count == 0 {
+            duration
+        } else {
+            self.min.min(duration)
+        };
+        self.max = self.max.max(duration);
+        self.total += duration;
+        self.count += 1;
+    }
+
+    /// Returns the average chunk duration, if any chunk was timed.
+    pub fn average(&self) -> Option<Duration> {
+        (self.count > 0)
+            .then(|| Duration::from_nanos((self.total.as_nanos() / u128::from(self.count)) as u64))
+    }
+
+    /// Adds the chunks timed in another run, e.g. of the next contract in a list.
+    pub fn merge(&mut self, other: &ChunkDurations) {
+        if other.count == 0 {
+            return;
+        }
+        self.min = if self.count == 0 {
+            other.min
+        } else {
+            self.min.min(other.min)
+        };
+        self.max = self.max.max(other.max);
+        self.total += other.total;
+        self.count += other.count;
+    }
+}
@@ -0,0 +1,138 @@
+use std::error::Error;
+use std::path::Path;
+use std::str::FromStr;
+
+use s3::creds::Credentials;
+use s3::{Bucket, Region};
+
+/// Bucket and key prefix the output is uploaded to, given as `s3://bucket/prefix`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadTarget {
+    pub bucket: String,
+    /// Key prefix without surrounding slashes; empty for the bucket root.
+    pub prefix: String,
+}
+
+impl UploadTarget {
+    /// Returns the object key of the file at `path`: its file name under the prefix.
+    pub fn key(&self, path: &str) -> String {
+        let name = Path::new(path)
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        if self.prefix.is_empty() {
+            name.into_owned()
+        } else {
+            format!("{}/{}", self.prefix, name)
+        }
+    }
+}
+
+impl FromStr for UploadTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let location = s.strip_prefix("s3://").ok_or_else(|| {
+            format!(
+                "Malformed upload target (expected s3://bucket/prefix): {}",
+                s
+            )
+        })?;
+        let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+        if bucket.is_empty() {
+            return Err(format!("Upload target has no bucket: {}", s));
+        }
+
+        Ok(UploadTarget {
+            bucket: bucket.to_string(),
+            prefix: prefix.trim_matches('/').to_string(),
+        })
+    }
+}
+
+/// Returns the files written for the output `filename`: the file itself, or
+/// every partition of it when the output is partitioned.
+pub fn output_files(filename: &str, partitioned: bool) -> std::io::Result<Vec<String>> {
+    if !partitioned {
+        return Ok(vec![filename.to_string()]);
+    }
+
+    // Partitions are named `<stem>.<start>-<end>.<extension>`, or
+    // `<stem>.<YYYY-MM-DD>.<extension>` per day, next to `filename`
+    let path = Path::new(filename);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().map(|e| e.to_string_lossy());
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry_path = entry?.path();
+        let Some(name) = entry_path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+        else {
+            continue;
+        };
+        let Some(window) = name.strip_prefix(&format!("{}.", stem)) else {
+            continue;
+        };
+        let window = match &extension {
+            Some(extension) => match window.strip_suffix(&format!(".{}", extension)) {
+                Some(window) => window,
+                None => continue,
+            },
+            None => window,
+        };
+        let is_window = window
+            .split_once('-')
+            .is_some_and(|(start, end)| start.parse::<u64>().is_ok() && end.parse::<u64>().is_ok());
+        let is_day = chrono::NaiveDate::parse_from_str(window, "%Y-%m-%d").is_ok();
+        if is_window || is_day {
+            files.push(entry_path.to_string_lossy().into_owned());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Uploads the file at `path` to `target` and returns its object key.
+///
+/// Without `endpoint`, the AWS region is read from `AWS_REGION` (default
+/// `us-east-1`); with it, the S3-compatible endpoint is addressed path-style.
+pub async fn upload_output(
+    path: &str,
+    target: &UploadTarget,
+    endpoint: Option<&str>,
+    credentials: Credentials,
+) -> Result<String, Box<dyn Error>> {
+    let region_name = std::env::var("AWS_REGION").unwrap_or_else(|_| "us-east-1".to_string());
+    let region = match endpoint {
+        Some(endpoint) => Region::Custom {
+            region: region_name,
+            endpoint: endpoint.to_string(),
+        },
+        None => region_name.parse()?,
+    };
+    let mut bucket = Bucket::new(&target.bucket, region, credentials)?;
+    if endpoint.is_some() {
+        bucket = bucket.with_path_style();
+    }
+
+    let key = target.key(path);
+    let content = tokio::fs::read(path).await?;
+    let response = bucket.put_object(&key, &content).await?;
+    if !(200..300).contains(&response.status_code()) {
+        return Err(format!(
+            "Upload of {} to s3://{}/{} failed with status {}",
+            path,
+            target.bucket,
+            key,
+            response.status_code()
+        )
+        .into());
+    }
+    Ok(key)
+}
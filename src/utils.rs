@@ -1,39 +1,290 @@
 use std::error::Error;
 use std::fs;
+use std::str::FromStr;
 
-use ethers::providers::{Middleware, Provider, Ws};
-use ethers::types::{BlockId, BlockNumber};
-use ethers::types::{H160, H256};
+use ethers::providers::Middleware;
+use ethers::types::{Block, BlockId, BlockNumber};
+use ethers::types::{Transaction, TransactionReceipt, H160, H256, U256};
 
-use serde::Serialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize, Serializer};
 use serde_json::Value;
 use ureq;
 
 use crate::constants;
+use crate::error::CollectorError;
+use crate::retry::{RetryError, RetryPolicy};
+use crate::rpc_headers::{connect_ws, RpcHeader};
+use crate::source::ChainSource;
+use crate::table::truncate_hex;
 use ethers_contract::Abigen;
 
 /// Represents a blockchain order event.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, JsonSchema)]
 pub struct OrderEvent {
+    /// Sender of the transaction, as `0x` hex.
+    #[schemars(with = "String")]
     pub tx_origin: H160,
+    /// Name of the event, e.g. `TakeOrderV2`.
     pub event_type: String,
+    /// Hash of the transaction, as `0x` hex.
+    #[schemars(with = "String")]
     pub txn_hash: H256,
+    /// Unix timestamp of the block, in seconds.
     pub timestamp: u64,
+    /// Number of the block the event was emitted in.
+    pub block_number: u64,
+    /// Index of the log within its block.
+    pub log_index: u64,
+    /// Hex-encoded `log.data`, present when raw output is requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_data: Option<String>,
+    /// Hex-encoded log topics joined with `;`, present when raw output is requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_topics: Option<String>,
+    /// Immediate caller of the contract taken from the transaction traces, present
+    /// when internal calls are included (empty when traces are unavailable).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub direct_caller: Option<String>,
+    /// Label of the contract the event came from, present for `--contracts-file` runs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Chain ID of the network the event came from, present when requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chain_id: Option<u64>,
+    /// Gas price paid by the transaction, present when gas prices are requested.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_decimal"
+    )]
+    #[schemars(with = "Option<String>")]
+    pub gas_price: Option<U256>,
+    /// Effective gas price from the transaction receipt, present when receipts are
+    /// fetched.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_decimal"
+    )]
+    #[schemars(with = "Option<String>")]
+    pub effective_gas_price: Option<U256>,
+    /// Hex-encoded transaction input, or its 4-byte selector, present when calldata
+    /// is requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input: Option<String>,
+    /// Event parameters decoded with the ABI, present in the JSON output formats.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub args: Option<serde_json::Value>,
+    /// Addresses of the indexed address parameters, decoded from the log topics
+    /// when their columns are requested. The JSON formats hold them in `args`.
+    #[serde(skip)]
+    pub indexed_addresses: Vec<(String, H160)>,
+    /// Value sent with the transaction, in wei, captured to filter on when
+    /// `--min-value` or `--max-value` is set. It is not written.
+    #[serde(skip)]
+    pub value: Option<U256>,
 }
+
+/// Serializes a wei amount as a decimal string, which JSON numbers cannot hold
+/// without losing precision.
+fn serialize_decimal<S: Serializer>(
+    value: &Option<U256>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => serializer.serialize_str(&value.to_string()),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Returns the gas price paid by `txn`.
+///
+/// Legacy and access list transactions carry their gas price, as mined EIP-1559
+/// transactions normally do too; those lacking it fall back to their maximum fee
+/// per gas, the most they could have paid.
+pub fn transaction_gas_price(txn: &Transaction) -> Option<U256> {
+    txn.gas_price.or(txn.max_fee_per_gas)
+}
+
+/// Returns the gas price the transaction of `receipt` actually paid.
+///
+/// Receipts from before EIP-1559 have no effective gas price, as every
+/// transaction then paid its own `gas_price`, which is used instead.
+pub fn effective_gas_price(receipt: &TransactionReceipt, gas_price: Option<U256>) -> Option<U256> {
+    receipt.effective_gas_price.or(gas_price)
+}
+
+/// How much of the transaction input `--with-calldata` writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Calldata {
+    /// The whole input, which can be kilobytes per row.
+    #[default]
+    Full,
+    /// The 4-byte function selector only.
+    Selector,
+}
+
+impl FromStr for Calldata {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "full" => Ok(Calldata::Full),
+            "selector" => Ok(Calldata::Selector),
+            _ => Err(format!("Unsupported calldata mode: {}", s)),
+        }
+    }
+}
+
+/// Returns the input of `txn` as `0x` hex, whole or cut to its selector. Inputs
+/// shorter than a selector, such as plain transfers, are kept whole.
+pub fn transaction_calldata(txn: &Transaction, mode: Calldata) -> String {
+    let input = match mode {
+        Calldata::Full => &txn.input[..],
+        Calldata::Selector => &txn.input[..txn.input.len().min(4)],
+    };
+    format!("0x{}", hex::encode(input))
+}
+
+impl OrderEvent {
+    /// Returns a rough estimate of the memory held by the event, counting its
+    /// strings and decoded arguments, for the `--max-in-flight-bytes` guard.
+    pub fn estimated_size(&self) -> usize {
+        let strings: usize = [
+            &self.raw_data,
+            &self.raw_topics,
+            &self.direct_caller,
+            &self.label,
+            &self.input,
+        ]
+        .iter()
+        .map(|field| field.as_ref().map_or(0, String::len))
+        .sum();
+        std::mem::size_of::<Self>()
+            + self.event_type.len()
+            + strings
+            + self.args.as_ref().map_or(0, value_size)
+    }
+}
+
+/// Returns a rough estimate of the memory held by a JSON value.
+fn value_size(value: &Value) -> usize {
+    let nested = match value {
+        Value::String(s) => s.len(),
+        Value::Array(items) => items.iter().map(value_size).sum(),
+        Value::Object(map) => map.iter().map(|(key, v)| key.len() + value_size(v)).sum(),
+        _ => 0,
+    };
+    std::mem::size_of::<Value>() + nested
+}
+
+impl std::fmt::Display for OrderEvent {
+    /// Formats the event on one line with shortened hashes, e.g.
+    /// `TakeOrderV2 tx=0x1234…cdef origin=0x0ea6…d37c ts=1617912345`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} tx={} origin={} ts={}",
+            self.event_type,
+            truncate_hex(&format!("{:?}", self.txn_hash)),
+            truncate_hex(&format!("{:?}", self.tx_origin)),
+            self.timestamp
+        )
+    }
+}
+
+/// Formats an integer for human-facing output.
+///
+/// With `pretty` set, digits are grouped in thousands (`22,094,919`); otherwise
+/// the plain decimal representation is returned for machine parsing.
+pub fn fmt_int(value: u64, pretty: bool) -> String {
+    let digits = value.to_string();
+    if !pretty {
+        return digits;
+    }
+
+    let mut formatted = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+    formatted
+}
+
+/// Run parameters naming an output file written to `--output-dir`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputNameParams<'a> {
+    pub network: &'a str,
+    pub contract: &'a str,
+    pub from_block: u64,
+    pub to_block: u64,
+    /// Extension of the output format, without the dot.
+    pub extension: &'a str,
+}
+
+/// Returns a descriptive output file name, `<network>_<contract>_<from>-<to>.<extension>`.
+///
+/// The network and contract are lowercased and every character other than an
+/// ASCII letter or digit is replaced with `_`, so the name is safe on any platform.
+pub fn output_filename(params: &OutputNameParams) -> String {
+    let sanitize = |part: &str| -> String {
+        part.chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_lowercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect()
+    };
+    format!(
+        "{}_{}_{}-{}.{}",
+        sanitize(params.network),
+        sanitize(params.contract),
+        params.from_block,
+        params.to_block,
+        sanitize(params.extension)
+    )
+}
+
+/// Generic envelope returned by every Etherscan API endpoint.
+///
+/// `result` is kept generic because its shape depends on the endpoint, and on
+/// failure Etherscan replaces it with a plain error string.
+#[derive(Debug, Deserialize)]
+pub struct EtherscanResponse<T> {
+    pub status: String,
+    pub message: String,
+    pub result: T,
+}
+
+/// A single entry of the `getcontractcreation` result list.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContractCreationResult {
+    pub contract_address: String,
+    pub contract_creator: Option<String>,
+    pub tx_hash: Option<String>,
+    pub block_number: String,
+}
+
 /// Retrieves the WebSocket RPC URL for a given blockchain network.
 ///
-/// This function checks the constant variables for the corresponding WebSocket
-/// RPC URL of the specified network. If the network is unsupported or the constant
-/// variable is not set, an error is returned.
+/// This function completes the WebSocket RPC URL constant of the specified network
+/// with the Infura key. If the network is unsupported or the key is absent, an
+/// error is returned.
 ///
 /// # Arguments
 ///
 /// * `network` - A string slice representing the blockchain network (e.g., "Mainnet", "Arbitrum").
+/// * `infura_key` - The Infura API key as resolved by [`crate::keys::resolve_keys`], if any.
 ///
 /// # Returns
 ///
-/// * `Ok(String)` - The WebSocket RPC URL if the constant variable is set.
-/// * `Err(String)` - An error message if the network is unsupported or the constant variable is missing.
+/// * `Ok(String)` - The WebSocket RPC URL of the network.
+/// * `Err(String)` - An error message if the network is unsupported or the key is absent.
 ///
 /// # Supported Networks
 ///
@@ -45,7 +296,7 @@ pub struct OrderEvent {
 /// | Optimism  | OPTIMISM_WS_RPC_URL        |
 /// | Linear    | LINEAR_WS_RPC_URL          |
 ///
-pub fn get_ws_rpc_url(network: &str) -> Result<String, String> {
+pub fn get_ws_rpc_url(network: &str, infura_key: Option<&str>) -> Result<String, String> {
     // Determine the corresponding constant variable for the given network
     let basic_url = match network {
         "Base" => constants::BASE_WS_RPC_BASE_URL,
@@ -56,12 +307,14 @@ pub fn get_ws_rpc_url(network: &str) -> Result<String, String> {
         _ => return Err(format!("Unsupported network: {}", network)),
     };
 
-    // Retrieve the WebSocket RPC URL from constant variable
-    Ok(format!(
-        "{}{}",
-        basic_url,
-        std::env::var("INFURA_API_KEY").expect("INFURA_API_KEY environment variable not set")
-    ))
+    // Complete the WebSocket RPC URL with the key
+    let infura_key = infura_key.ok_or_else(|| {
+        format!(
+            "{} not set; add it to the environment or pass --infura-key",
+            constants::INFURA_API_KEY_VAR
+        )
+    })?;
+    Ok(format!("{}{}", basic_url, infura_key))
 }
 
 /// Retrieves the block number where a given smart contract was first deployed.
@@ -72,6 +325,7 @@ pub fn get_ws_rpc_url(network: &str) -> Result<String, String> {
 ///
 /// * `api_key` - A string slice containing the Etherscan API key.
 /// * `contract_address` - The address of the smart contract in hexadecimal format.
+/// * `retry` - Backoff applied to network errors and rate-limited responses.
 ///
 /// # Returns
 ///
@@ -82,32 +336,117 @@ pub fn get_contract_creation_block(
     base_url: &str,
     api_key: &str,
     contract_address: &str,
+    retry: &RetryPolicy,
 ) -> Result<u64, Box<dyn Error>> {
     let url = format!(
         "{}/api?module=contract&action=getcontractcreation&contractaddresses={}&apikey={}",
         base_url, contract_address, api_key
     );
 
-    // Send the request to Etherscan API and parse the JSON response
-    let res: String = ureq::get(&url).call()?.into_string()?;
-    let res: Value = serde_json::from_str(&res)?;
+    // Send the request to Etherscan API and parse the response envelope
+    let res = etherscan_get(&url, retry)?;
 
     // Check if the API response status is successful
-    if res["status"] == "1" {
-        // Extract and parse the block number
-        if let Some(block_number_str) = res["result"][0]["blockNumber"].as_str() {
-            block_number_str
-                .parse::<u64>()
-                .map_err(|_| "Failed to parse block number".into())
-        } else {
-            Err("Block number not found in contract creation details.".into())
-        }
-    } else {
-        Err(format!(
+    if res.status != "1" {
+        return Err(format!(
             "Failed to retrieve contract creation transaction: {}",
-            res["message"]
+            res.message
+        )
+        .into());
+    }
+
+    // On success the result is a list of creation records
+    let results: Vec<ContractCreationResult> = serde_json::from_value(res.result)
+        .map_err(|e| format!("Malformed contract creation result: {}", e))?;
+
+    // Extract and parse the block number
+    match results.first() {
+        Some(creation) => creation
+            .block_number
+            .parse::<u64>()
+            .map_err(|_| "Failed to parse block number".into()),
+        None => Err("Block number not found in contract creation details.".into()),
+    }
+}
+
+/// Sends a GET request to the Etherscan API and parses its response envelope.
+///
+/// Network errors, HTTP 429 and 5xx statuses, and responses reporting the rate
+/// limit (`Max rate limit reached`) are retried according to `retry`.
+fn etherscan_get(
+    url: &str,
+    retry: &RetryPolicy,
+) -> Result<EtherscanResponse<Value>, Box<dyn Error>> {
+    retry.run(|| {
+        let body = match ureq::get(url).call() {
+            Ok(response) => response
+                .into_string()
+                .map_err(|e| RetryError::Transient(e.into()))?,
+            Err(ureq::Error::Status(status, _)) if status == 429 || status >= 500 => {
+                return Err(RetryError::Transient(
+                    format!("Etherscan responded with HTTP {}", status).into(),
+                ))
+            }
+            Err(e @ ureq::Error::Transport(_)) => return Err(RetryError::Transient(e.into())),
+            Err(e) => return Err(RetryError::Permanent(e.into())),
+        };
+        let res: EtherscanResponse<Value> = serde_json::from_str(&body).map_err(|e| {
+            RetryError::Permanent(format!("Malformed Etherscan response: {}", e).into())
+        })?;
+
+        // A rate-limited request is reported in the envelope, with a successful HTTP status
+        let rate_limited = res.status != "1"
+            && res
+                .result
+                .as_str()
+                .is_some_and(|reason| reason.to_ascii_lowercase().contains("rate limit"));
+        if rate_limited {
+            return Err(RetryError::Transient(
+                format!("Etherscan rate limit reached: {}", res.result).into(),
+            ));
+        }
+        Ok(res)
+    })
+}
+
+/// Retrieves the verified ABI of a smart contract from the Etherscan API.
+///
+/// # Arguments
+///
+/// * `api_key` - A string slice containing the Etherscan API key.
+/// * `contract_address` - The address of the smart contract in hexadecimal format.
+/// * `retry` - Backoff applied to network errors and rate-limited responses.
+///
+/// # Returns
+///
+/// * `Ok(String)` - The ABI JSON of the contract.
+/// * `Err(Box<dyn Error>)` - An error message if the API request fails or the contract is not verified.
+///
+pub fn get_contract_abi(
+    base_url: &str,
+    api_key: &str,
+    contract_address: &str,
+    retry: &RetryPolicy,
+) -> Result<String, Box<dyn Error>> {
+    let url = format!(
+        "{}/api?module=contract&action=getabi&address={}&apikey={}",
+        base_url, contract_address, api_key
+    );
+
+    // Send the request to Etherscan API and parse the response envelope
+    let res = etherscan_get(&url, retry)?;
+
+    // On failure the result holds the reason, e.g. an unverified contract
+    if res.status != "1" {
+        return Err(format!(
+            "Failed to retrieve the ABI of {}: {} ({})",
+            contract_address, res.message, res.result
         )
-        .into())
+        .into());
+    }
+    match res.result {
+        Value::String(abi) => Ok(abi),
+        result => Err(format!("Malformed contract ABI result: {}", result).into()),
     }
 }
 
@@ -117,24 +456,187 @@ pub fn get_contract_creation_block(
 ///
 /// # Arguments
 ///
-/// * `provider` - A reference to an `ethers::providers::Provider<Ws>` instance to interact with the blockchain.
+/// * `ws_rpc_url` - The WebSocket RPC URL of the Ethereum node.
+/// * `headers` - Headers sent with the WebSocket upgrade request, such as `Authorization`.
 ///
 /// # Returns
 ///
 /// * `Ok(u64)` - The latest block number on the chain.
-/// * `Err(Box<dyn Error>)` - An error message if the latest block cannot be fetched.
+/// * `Err(Box<dyn Error>)` - An error message if the latest block cannot be fetched, or
+///   `CollectorError::PendingBlock` if it has no number yet.
 ///
-pub async fn get_latest_block_number(ws_rpc_url: &str) -> Result<u64, Box<dyn Error>> {
-    let provider = Provider::<Ws>::connect(ws_rpc_url).await?;
+pub async fn get_latest_block_number(
+    ws_rpc_url: &str,
+    headers: &[RpcHeader],
+) -> Result<u64, Box<dyn Error>> {
+    let provider = connect_ws(ws_rpc_url, headers).await?;
     match provider
         .get_block(BlockId::Number(BlockNumber::Latest))
         .await?
     {
-        Some(block) => Ok(block.number.unwrap_or_default().as_u64()), // Extracts and returns the block number
+        Some(block) => latest_block_number_of(&block),
         None => Err("Failed to fetch the latest block".into()),
     }
 }
 
+/// Fetches the chain ID served by the node at `ws_rpc_url`.
+pub async fn get_chain_id(ws_rpc_url: &str, headers: &[RpcHeader]) -> Result<u64, Box<dyn Error>> {
+    let provider = connect_ws(ws_rpc_url, headers).await?;
+    Ok(provider.get_chainid().await?.as_u64())
+}
+
+/// Returns the number of the latest block, which a pending block does not have yet.
+pub(crate) fn latest_block_number_of<T>(block: &Block<T>) -> Result<u64, Box<dyn Error>> {
+    match block.number {
+        Some(number) => Ok(number.as_u64()),
+        None => Err(CollectorError::PendingBlock.into()),
+    }
+}
+
+/// Parses an ISO-8601 date (`2024-01-15`, midnight UTC) or RFC 3339 date-time
+/// (`2024-01-15T12:30:00Z`) into a Unix timestamp in seconds.
+pub fn parse_date(value: &str) -> Result<u64, CollectorError> {
+    let timestamp = if let Ok(datetime) = chrono::DateTime::parse_from_rfc3339(value) {
+        datetime.timestamp()
+    } else if let Ok(date) = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        date.and_hms_opt(0, 0, 0)
+            .expect("midnight is a valid time")
+            .and_utc()
+            .timestamp()
+    } else {
+        return Err(CollectorError::InvalidArgs(format!(
+            "Invalid date '{}', expected YYYY-MM-DD or an RFC 3339 date-time",
+            value
+        )));
+    };
+
+    u64::try_from(timestamp)
+        .map_err(|_| CollectorError::InvalidArgs(format!("Date '{}' is before 1970", value)))
+}
+
+/// Latest block of a chain, and the last block far enough below it to be collected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SafeLatestBlock {
+    /// Number of the latest block.
+    pub latest: u64,
+    /// Last block with the requested confirmations, or `None` when it is before
+    /// the start of the range, leaving no block safe to collect.
+    pub safe: Option<u64>,
+}
+
+/// Returns the last block with `confirmations` blocks on top of it when `latest`
+/// is the latest block, or `None` when it is before `from_block`.
+///
+/// No confirmations make `latest` itself safe.
+pub fn safe_block(latest: u64, confirmations: u64, from_block: u64) -> Option<u64> {
+    latest
+        .checked_sub(confirmations)
+        .filter(|&safe| safe >= from_block)
+}
+
+/// Fetches the latest block of `source` and the last block with `confirmations`
+/// blocks on top of it, not before `from_block` (see [`safe_block`]).
+///
+/// Every mode bounding its scan away from the chain tip goes through this, so
+/// they agree on which blocks are safe.
+pub async fn safe_latest_block(
+    source: &dyn ChainSource,
+    confirmations: u64,
+    from_block: u64,
+) -> Result<SafeLatestBlock, Box<dyn Error>> {
+    let latest = source
+        .latest_block_number()
+        .await
+        .map_err(|e| -> Box<dyn Error> { e })?;
+    Ok(SafeLatestBlock {
+        latest,
+        safe: safe_block(latest, confirmations, from_block),
+    })
+}
+
+/// Returns the timestamp of block `number`, failing if the block does not exist.
+async fn required_block_timestamp(
+    source: &dyn ChainSource,
+    number: u64,
+) -> Result<u64, Box<dyn Error>> {
+    source
+        .block_timestamp(number)
+        .await
+        .map_err(|e| -> Box<dyn Error> { e })?
+        .ok_or_else(|| format!("Block {} not found", number).into())
+}
+
+/// Finds the first block whose timestamp is at or after `timestamp`.
+///
+/// Block timestamps are monotonic, so the block is located by bisecting over
+/// `[0, latest]` with `get_block` calls. A timestamp before genesis resolves to
+/// block 0; one after the latest block yields `None`.
+pub async fn block_at_timestamp(
+    source: &dyn ChainSource,
+    timestamp: u64,
+) -> Result<Option<u64>, Box<dyn Error>> {
+    let latest = source
+        .latest_block_number()
+        .await
+        .map_err(|e| -> Box<dyn Error> { e })?;
+    if required_block_timestamp(source, latest).await? < timestamp {
+        return Ok(None);
+    }
+
+    let (mut low, mut high) = (0u64, latest);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if required_block_timestamp(source, mid).await? >= timestamp {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    Ok(Some(low))
+}
+
+/// Finds the last block whose timestamp is at or before `timestamp`.
+///
+/// A timestamp after the latest block resolves to the latest block; one before
+/// genesis is an error since no block qualifies.
+pub async fn last_block_at_or_before(
+    source: &dyn ChainSource,
+    timestamp: u64,
+) -> Result<u64, Box<dyn Error>> {
+    match block_at_timestamp(source, timestamp.saturating_add(1)).await? {
+        Some(0) => Err(CollectorError::InvalidArgs(format!(
+            "Timestamp {} is before the genesis block",
+            timestamp
+        ))
+        .into()),
+        Some(block) => Ok(block - 1),
+        None => Ok(source
+            .latest_block_number()
+            .await
+            .map_err(|e| -> Box<dyn Error> { e })?),
+    }
+}
+
+/// Returns the number of the block transaction `hash` was mined in.
+///
+/// A transaction the node does not know and one still pending, which has no block
+/// yet, are reported as invalid arguments, since neither can anchor a range.
+pub async fn block_of_tx(source: &dyn ChainSource, hash: H256) -> Result<u64, CollectorError> {
+    let txn = source
+        .transaction(hash)
+        .await
+        .map_err(|e| CollectorError::Rpc(format!("Failed to fetch transaction {:?}: {}", hash, e)))?
+        .ok_or_else(|| CollectorError::InvalidArgs(format!("Transaction {:?} not found", hash)))?;
+    txn.block_number
+        .map(|number| number.as_u64())
+        .ok_or_else(|| {
+            CollectorError::InvalidArgs(format!(
+                "Transaction {:?} is pending and has no block yet",
+                hash
+            ))
+        })
+}
+
 /// Loads an ABI (Application Binary Interface) file and generates Rust contract bindings.
 ///
 /// # Arguments
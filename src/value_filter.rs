@@ -0,0 +1,61 @@
+use ethers::types::U256;
+
+/// Parses an amount of ether into wei, given in `wei` (the default when no unit
+/// is given), `gwei` or `eth` (also `ether`), e.g. `1.5eth` or `20 gwei`.
+///
+/// Fractions finer than a wei are refused rather than rounded.
+pub fn parse_wei(input: &str) -> Result<U256, String> {
+    let lower = input.trim().to_ascii_lowercase();
+    let split = lower
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(lower.len());
+    let (number, unit) = lower.split_at(split);
+    let decimals = match unit.trim() {
+        "" | "wei" => 0,
+        "gwei" => 9,
+        "eth" | "ether" => 18,
+        other => {
+            return Err(format!(
+                "Unknown unit '{}' in {}: expected wei, gwei or eth",
+                other, input
+            ))
+        }
+    };
+
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+    if whole.is_empty() && fraction.is_empty() {
+        return Err(format!("Missing amount in {}", input));
+    }
+    if fraction.len() > decimals {
+        return Err(format!("{} is not a whole number of wei", input));
+    }
+    let digits = format!(
+        "{}{}{}",
+        whole,
+        fraction,
+        "0".repeat(decimals - fraction.len())
+    );
+    U256::from_dec_str(&digits).map_err(|_| format!("Invalid amount: {}", input))
+}
+
+/// Inclusive bounds on the value of the transaction emitting an event, outside
+/// of which the event is skipped.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ValueRange {
+    /// Smallest value kept, in wei, if bounded.
+    pub min: Option<U256>,
+    /// Largest value kept, in wei, if bounded.
+    pub max: Option<U256>,
+}
+
+impl ValueRange {
+    /// Returns whether every value is kept, so values need not be captured.
+    pub fn is_unbounded(&self) -> bool {
+        self.min.is_none() && self.max.is_none()
+    }
+
+    /// Returns whether `value` is within the bounds.
+    pub fn contains(&self, value: U256) -> bool {
+        self.min.into_iter().all(|min| value >= min) && self.max.into_iter().all(|max| value <= max)
+    }
+}
@@ -0,0 +1,226 @@
+use std::error::Error;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use log::error;
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep_until, Instant};
+
+use crate::retry::{RetryError, RetryPolicy};
+use crate::sink::EventSink;
+use crate::utils::OrderEvent;
+
+/// Where and how collected events are delivered by `--webhook`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookConfig {
+    /// URL the batches of events are POSTed to.
+    pub url: String,
+    /// Number of events per request.
+    pub batch_size: usize,
+    /// Longest time events are held before being delivered in a smaller batch.
+    pub flush_interval: Duration,
+    /// Backoff of the failed deliveries.
+    pub retry: RetryPolicy,
+}
+
+/// Returns the JSON body delivering `events`: `{"events": [...]}`, each event in
+/// the shape of the JSON output formats.
+pub fn webhook_payload(events: &[Value]) -> Value {
+    json!({ "events": events })
+}
+
+/// Delivery of the events of a whole run to a webhook, for real-time
+/// notifications, shared by every collection of the run.
+///
+/// Events written to its [`WebhookSink`]s are delivered by a task of their own in
+/// batches of `batch_size`; a partial batch is sent once `flush_interval` has
+/// passed since the previous delivery, whether or not more events arrive, and when
+/// the run finishes. Each request runs on a blocking thread, so the collection
+/// never waits on the webhook. Network errors, rate limits and server errors are
+/// retried with backoff; a delivery still failing stops the task and fails the
+/// next write and [`Webhook::finish`].
+#[derive(Debug)]
+pub struct Webhook {
+    config: WebhookConfig,
+    sender: Mutex<Option<mpsc::UnboundedSender<Value>>>,
+    task: Mutex<Option<JoinHandle<Result<(), String>>>>,
+    failure: Arc<Mutex<Option<String>>>,
+}
+
+impl Webhook {
+    /// Starts delivering to the webhook of `config`; nothing is sent until events
+    /// are written. Must be called within a Tokio runtime.
+    pub fn start(config: WebhookConfig) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let failure = Arc::new(Mutex::new(None));
+        let task = tokio::spawn(deliver_events(config.clone(), receiver, failure.clone()));
+        Webhook {
+            config,
+            sender: Mutex::new(Some(sender)),
+            task: Mutex::new(Some(task)),
+            failure,
+        }
+    }
+
+    /// Returns where and how the events are delivered.
+    pub fn config(&self) -> &WebhookConfig {
+        &self.config
+    }
+
+    /// Returns an output queueing the events it is written for delivery.
+    pub fn sink(&self) -> WebhookSink {
+        WebhookSink {
+            sender: self
+                .sender
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone(),
+            failure: self.failure.clone(),
+        }
+    }
+
+    /// Delivers the events still pending once every sink is dropped, and returns
+    /// the error of a failed delivery, if any.
+    pub async fn finish(&self) -> Result<(), Box<dyn Error>> {
+        self.sender
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        let task = self
+            .task
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        match task {
+            Some(task) => Ok(task.await??),
+            None => Ok(()),
+        }
+    }
+
+    /// Finishes the deliveries once the run ends with `result`, also when it failed,
+    /// as the pending events are already in the output. The error of the run takes
+    /// precedence over a failed delivery, which is then only logged.
+    pub async fn finish_after<T>(
+        &self,
+        result: Result<T, Box<dyn Error>>,
+    ) -> Result<T, Box<dyn Error>> {
+        let delivered = self.finish().await;
+        match (result, delivered) {
+            (Ok(value), delivered) => delivered.map(|()| value),
+            (Err(e), Ok(())) => Err(e),
+            (Err(e), Err(delivery)) => {
+                error!("Failed to deliver the pending webhook events: {}", delivery);
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Receives the events of a run and delivers them, until every sender is dropped.
+async fn deliver_events(
+    config: WebhookConfig,
+    mut receiver: mpsc::UnboundedReceiver<Value>,
+    failure: Arc<Mutex<Option<String>>>,
+) -> Result<(), String> {
+    let batch_size = config.batch_size.max(1);
+    let mut pending = Vec::new();
+    let mut last_delivery = Instant::now();
+    let result = async {
+        loop {
+            tokio::select! {
+                event = receiver.recv() => match event {
+                    Some(event) => pending.push(event),
+                    None => break,
+                },
+                _ = sleep_until(last_delivery + config.flush_interval), if !pending.is_empty() => {
+                    deliver(&config, pending.drain(..).collect()).await?;
+                    last_delivery = Instant::now();
+                }
+            }
+            while pending.len() >= batch_size {
+                deliver(&config, pending.drain(..batch_size).collect()).await?;
+                last_delivery = Instant::now();
+            }
+        }
+        if !pending.is_empty() {
+            deliver(&config, pending).await?;
+        }
+        Ok::<_, String>(())
+    }
+    .await;
+
+    if let Err(e) = &result {
+        *failure.lock().unwrap_or_else(PoisonError::into_inner) = Some(e.clone());
+    }
+    result
+}
+
+/// POSTs `events` on a blocking thread, retrying as configured.
+async fn deliver(config: &WebhookConfig, events: Vec<Value>) -> Result<(), String> {
+    let body = webhook_payload(&events).to_string();
+    let url = config.url.clone();
+    let retry = config.retry;
+    tokio::task::spawn_blocking(move || {
+        retry.run(|| {
+            match ureq::post(&url)
+                .set("Content-Type", "application/json")
+                .send_string(&body)
+            {
+                Ok(_) => Ok(()),
+                Err(ureq::Error::Status(status, _)) if status == 429 || status >= 500 => Err(
+                    RetryError::Transient(format!("Webhook responded with HTTP {}", status)),
+                ),
+                Err(e @ ureq::Error::Transport(_)) => Err(RetryError::Transient(format!(
+                    "Webhook delivery failed: {}",
+                    e
+                ))),
+                Err(e) => Err(RetryError::Permanent(format!(
+                    "Webhook rejected the events: {}",
+                    e
+                ))),
+            }
+        })
+    })
+    .await
+    .map_err(|e| format!("Webhook delivery stopped: {}", e))?
+}
+
+/// Output queueing the events for delivery by its [`Webhook`].
+pub struct WebhookSink {
+    sender: Option<mpsc::UnboundedSender<Value>>,
+    failure: Arc<Mutex<Option<String>>>,
+}
+
+impl WebhookSink {
+    /// Returns the error that stopped the deliveries, if any.
+    fn failure(&self) -> Result<(), Box<dyn Error>> {
+        match &*self.failure.lock().unwrap_or_else(PoisonError::into_inner) {
+            Some(e) => Err(e.clone().into()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl EventSink for WebhookSink {
+    fn write_events(&mut self, events: &[OrderEvent]) -> Result<(), Box<dyn Error>> {
+        self.failure()?;
+        let Some(sender) = &self.sender else {
+            return Err("The webhook deliveries are finished".into());
+        };
+        for event in events {
+            if sender.send(serde_json::to_value(event)?).is_err() {
+                self.failure()?;
+                return Err("The webhook deliveries stopped".into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Leaves the pending events to the [`Webhook`], which delivers them when the
+    /// whole run finishes.
+    fn finish(self: Box<Self>) -> Result<(), Box<dyn Error>> {
+        self.failure()
+    }
+}